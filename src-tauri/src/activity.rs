@@ -0,0 +1,232 @@
+use crate::ansi::strip_ansi;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Approval prompts common to the supported agents, always checked
+const APPROVAL_PATTERNS: &[&str] = &[
+    r"(?i)do you want to (proceed|continue|make this edit|create|run|allow)",
+    r"(?i)\[y/n\]",
+    r"(?i)\(y/n\)",
+    r"(?i)allow (once|always|this)",
+    r"(?i)approve (this|the)",
+    r"(?i)press enter to continue",
+];
+
+/// Quiet time after the last output before a prompt match counts, so that
+/// a prompt line that is still being redrawn doesn't flap the state
+const SETTLE_MS: u64 = 300;
+/// Stripped output retained for pattern matching
+const TAIL_MAX_BYTES: usize = 2048;
+/// Only the last few lines of the tail are checked; older prompts are stale
+const TAIL_MATCH_LINES: usize = 8;
+
+/// What an agent session is doing, as inferred from its output stream
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentActivity {
+    /// Output arrived recently
+    Working,
+    /// Output settled on a prompt or approval question
+    WaitingForInput,
+    /// No output for a while and no recognizable prompt
+    Idle,
+    /// The process has exited
+    Exited,
+}
+
+/// Classifies a session from its output: recency of output, the agent's
+/// configured prompt patterns and the built-in approval prompts
+pub struct ActivityTracker {
+    patterns: Vec<Regex>,
+    tail: String,
+    last_output: Instant,
+    state: AgentActivity,
+    idle_after: Duration,
+}
+
+impl ActivityTracker {
+    pub fn new(prompt_patterns: &[String], idle_after: Duration, now: Instant) -> Self {
+        let patterns = APPROVAL_PATTERNS
+            .iter()
+            .map(|p| p.to_string())
+            .chain(prompt_patterns.iter().cloned())
+            .filter_map(|p| match Regex::new(&p) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    eprintln!("ignoring invalid prompt pattern {p:?}: {e}");
+                    None
+                }
+            })
+            .collect();
+
+        Self {
+            patterns,
+            tail: String::new(),
+            last_output: now,
+            state: AgentActivity::Working,
+            idle_after,
+        }
+    }
+
+    pub fn state(&self) -> AgentActivity {
+        self.state
+    }
+
+    /// Record output; returns the new state if it changed
+    pub fn on_output(&mut self, text: &str, now: Instant) -> Option<AgentActivity> {
+        if self.state == AgentActivity::Exited {
+            return None;
+        }
+
+        self.tail.push_str(&strip_ansi(text));
+        if self.tail.len() > TAIL_MAX_BYTES {
+            let mut cut = self.tail.len() - TAIL_MAX_BYTES;
+            while !self.tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.tail.drain(..cut);
+        }
+
+        self.last_output = now;
+        self.transition(AgentActivity::Working)
+    }
+
+    /// Re-evaluate after time has passed; returns the new state if it changed
+    pub fn tick(&mut self, now: Instant) -> Option<AgentActivity> {
+        if self.state == AgentActivity::Exited {
+            return None;
+        }
+
+        let quiet = now.saturating_duration_since(self.last_output);
+        if quiet < Duration::from_millis(SETTLE_MS) {
+            return None;
+        }
+
+        if self.prompt_visible() {
+            self.transition(AgentActivity::WaitingForInput)
+        } else if quiet >= self.idle_after {
+            self.transition(AgentActivity::Idle)
+        } else {
+            None
+        }
+    }
+
+    pub fn on_exit(&mut self) -> Option<AgentActivity> {
+        self.transition(AgentActivity::Exited)
+    }
+
+    fn prompt_visible(&self) -> bool {
+        let recent: Vec<&str> = self
+            .tail
+            .lines()
+            .rev()
+            .filter(|l| !l.trim().is_empty())
+            .take(TAIL_MATCH_LINES)
+            .collect();
+        recent
+            .iter()
+            .any(|line| self.patterns.iter().any(|re| re.is_match(line)))
+    }
+
+    fn transition(&mut self, next: AgentActivity) -> Option<AgentActivity> {
+        if self.state == next {
+            None
+        } else {
+            self.state = next;
+            Some(next)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(patterns: &[&str]) -> (ActivityTracker, Instant) {
+        let now = Instant::now();
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        (ActivityTracker::new(&patterns, Duration::from_secs(5), now), now)
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_starts_working() {
+        let (t, _) = tracker(&[]);
+        assert_eq!(t.state(), AgentActivity::Working);
+    }
+
+    #[test]
+    fn test_approval_prompt_becomes_waiting_after_settle() {
+        let (mut t, start) = tracker(&[]);
+        t.on_output("Edit file src/main.rs\nDo you want to proceed?\n", start);
+        assert_eq!(t.tick(start + ms(100)), None);
+        assert_eq!(t.tick(start + ms(400)), Some(AgentActivity::WaitingForInput));
+        assert_eq!(t.tick(start + ms(600)), None);
+    }
+
+    #[test]
+    fn test_custom_prompt_pattern() {
+        let (mut t, start) = tracker(&[r"\? for shortcuts"]);
+        t.on_output("\x1b[2m? for shortcuts\x1b[0m", start);
+        assert_eq!(t.tick(start + ms(500)), Some(AgentActivity::WaitingForInput));
+    }
+
+    #[test]
+    fn test_quiet_without_prompt_becomes_idle() {
+        let (mut t, start) = tracker(&[]);
+        t.on_output("compiling...\n", start);
+        assert_eq!(t.tick(start + ms(1000)), None);
+        assert_eq!(t.tick(start + ms(5000)), Some(AgentActivity::Idle));
+    }
+
+    #[test]
+    fn test_new_output_returns_to_working() {
+        let (mut t, start) = tracker(&[]);
+        t.on_output("Continue? [y/N] ", start);
+        t.tick(start + ms(500));
+        assert_eq!(t.state(), AgentActivity::WaitingForInput);
+        assert_eq!(
+            t.on_output("y\nrunning tests\n", start + ms(600)),
+            Some(AgentActivity::Working)
+        );
+        assert_eq!(t.on_output("more\n", start + ms(700)), None);
+    }
+
+    #[test]
+    fn test_stale_prompt_scrolled_out_is_ignored() {
+        let (mut t, start) = tracker(&[]);
+        let mut text = String::from("Do you want to proceed?\n");
+        for i in 0..20 {
+            text.push_str(&format!("line {i}\n"));
+        }
+        t.on_output(&text, start);
+        assert_eq!(t.tick(start + ms(500)), None);
+    }
+
+    #[test]
+    fn test_exit_is_terminal() {
+        let (mut t, start) = tracker(&[]);
+        assert_eq!(t.on_exit(), Some(AgentActivity::Exited));
+        assert_eq!(t.on_output("late output", start), None);
+        assert_eq!(t.tick(start + ms(10_000)), None);
+        assert_eq!(t.state(), AgentActivity::Exited);
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_skipped() {
+        let (mut t, start) = tracker(&["(unclosed"]);
+        t.on_output("[y/n]", start);
+        assert_eq!(t.tick(start + ms(500)), Some(AgentActivity::WaitingForInput));
+    }
+
+    #[test]
+    fn test_tail_is_bounded() {
+        let (mut t, start) = tracker(&[]);
+        t.on_output(&"✔".repeat(4000), start);
+        assert!(t.tail.len() <= TAIL_MAX_BYTES);
+    }
+}
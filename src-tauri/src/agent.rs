@@ -1,55 +1,406 @@
-use crate::config::AgentConfig;
+use crate::config::{
+    AgentConfig, AgentDescriptor, ApprovalPrompt, EventFormat, HeadlessMode, ModeArgs, ModelCost,
+    PermissionArgs, PermissionMode, PromptDelivery, SessionIdSource,
+};
+use crate::usage::{self, UsageExtractor};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Agents with built-in descriptors
+const BUILTIN_AGENTS: &[&str] = &["claude", "codex", "gemini"];
 
 /// Agent adapter — normalizes different CLI behaviors
 pub struct AgentAdapter {
     pub name: String,
     pub config: AgentConfig,
+    pub permission_mode: PermissionMode,
+    /// Conversation to resume, for agents with `resume_args`
+    pub resume_session: Option<String>,
 }
 
 impl AgentAdapter {
-    pub fn new(name: String, config: AgentConfig) -> Self {
-        Self { name, config }
+    /// Parts of the descriptor the config leaves unset are filled in from
+    /// the built-in one
+    pub fn new(name: String, mut config: AgentConfig) -> Self {
+        let builtin = builtin_name(&name, &config.command).unwrap_or_default();
+        config.descriptor = builtin_descriptor(builtin).overridden_by(&config.descriptor);
+        if config.prompt_patterns.is_empty() {
+            config.prompt_patterns = builtin_prompt_patterns(builtin);
+        }
+        Self {
+            name,
+            config,
+            permission_mode: PermissionMode::Auto,
+            resume_session: None,
+        }
+    }
+
+    /// Run the agent in `mode`; fails for read-only when the agent has no
+    /// args for it
+    pub fn with_permission_mode(mut self, mode: PermissionMode) -> Result<Self, String> {
+        if mode == PermissionMode::ReadOnly && self.mode_args(None, mode).is_none() {
+            return Err(format!("agent {} has no read-only mode", self.name));
+        }
+        self.permission_mode = mode;
+        Ok(self)
+    }
+
+    /// Permission prompt to answer; only agents in `ask` mode are asked
+    pub fn approval_prompt(&self) -> Option<&ApprovalPrompt> {
+        match self.permission_mode {
+            PermissionMode::Ask => self.config.descriptor.approval_prompt.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Args for `mode`, from the headless mode when given and else from the
+    /// descriptor
+    fn mode_args<'a>(&'a self, headless: Option<&'a HeadlessMode>, mode: PermissionMode) -> Option<&'a ModeArgs> {
+        let interactive = self.config.descriptor.permissions.as_ref();
+        headless
+            .and_then(|h| h.permissions.as_ref())
+            .and_then(|p| p.get(mode))
+            .or_else(|| interactive.and_then(|p| p.get(mode)))
     }
 
     /// Build the full command + args for spawning
     pub fn build_command(&self, model: Option<&str>) -> (String, Vec<String>) {
         let mut args = self.config.args.clone();
 
-        // Inject model flag if specified
-        if let (Some(model), Some(flag)) = (model, &self.config.model_flag) {
+        self.push_model_args(model, &mut args);
+
+        if let Some(mode_args) = self.mode_args(None, self.permission_mode) {
+            apply_mode_args(mode_args, &mut args);
+        }
+
+        if let (Some(id), Some(resume)) = (&self.resume_session, &self.config.descriptor.resume_args) {
+            args.extend(resume.iter().map(|arg| arg.replace("{session_id}", id)));
+        }
+
+        (self.config.command.clone(), args)
+    }
+
+    /// Command for a session that starts working on `prompt`, passed the way
+    /// the agent's descriptor says. Agents taking it on stdin (and agents
+    /// without a descriptor) get the prompt back to type in after startup.
+    pub fn build_command_with_prompt(
+        &self,
+        model: Option<&str>,
+        prompt: Option<&str>,
+    ) -> (String, Vec<String>, Option<String>) {
+        let (command, mut args) = self.build_command(model);
+        let prompt = match prompt {
+            Some(p) if !p.trim().is_empty() => p.to_string(),
+            _ => return (command, args, None),
+        };
+
+        let stdin = match &self.config.descriptor.initial_prompt {
+            Some(delivery) => pass_prompt(delivery, &mut args, prompt),
+            None => Some(prompt),
+        };
+        (command, args, stdin)
+    }
+
+    /// Command for a one-shot run in the agent's structured output mode,
+    /// plus the prompt to write to its stdin when it takes it there
+    pub fn build_headless_command(
+        &self,
+        model: Option<&str>,
+        prompt: &str,
+    ) -> Result<(String, Vec<String>, Option<String>, EventFormat), String> {
+        let headless = self
+            .config
+            .descriptor
+            .headless
+            .as_ref()
+            .ok_or_else(|| format!("agent {} has no headless mode", self.name))?;
+
+        let mode_args = match self.permission_mode {
+            PermissionMode::Ask => {
+                return Err("headless runs can't ask for approval; use the auto or read_only permission mode".into())
+            }
+            mode => self.mode_args(Some(headless), mode),
+        };
+        if self.permission_mode == PermissionMode::ReadOnly && mode_args.is_none() {
+            return Err(format!("agent {} has no read-only headless mode", self.name));
+        }
+
+        let mut args = self.config.args.clone();
+        args.extend(headless.args.iter().cloned());
+        self.push_model_args(model, &mut args);
+        if let Some(mode_args) = mode_args {
+            apply_mode_args(mode_args, &mut args);
+        }
+        let stdin = pass_prompt(&headless.prompt, &mut args, prompt.to_string());
+        Ok((self.config.command.clone(), args, stdin, headless.format))
+    }
+
+    /// Usage extractor from the config, or the built-in one for known agents
+    pub fn usage_extractor(&self) -> UsageExtractor {
+        if self.config.usage_patterns.is_empty() && self.config.usage_format.is_none() {
+            let builtin = builtin_name(&self.name, &self.config.command).unwrap_or_default();
+            let (patterns, format) = usage::builtin_extractor(builtin);
+            return UsageExtractor::new(&patterns, format);
+        }
+        UsageExtractor::new(&self.config.usage_patterns, self.config.usage_format)
+    }
+
+    /// Environment variables to set for the agent; the model's own ones
+    /// win over the agent's
+    pub fn env_vars(&self, model: Option<&str>) -> HashMap<String, String> {
+        let mut env = self.config.env.clone();
+        if let Some(entry) = model.and_then(|m| self.config.model(m)) {
+            env.extend(entry.env.clone());
+        }
+        env
+    }
+
+    /// Token prices of the models that have them
+    pub fn model_costs(&self) -> HashMap<String, ModelCost> {
+        self.config
+            .models
+            .iter()
+            .filter_map(|m| m.cost.map(|cost| (m.id.clone(), cost)))
+            .collect()
+    }
+
+    /// Model flag and the model entry's args
+    fn push_model_args(&self, model: Option<&str>, args: &mut Vec<String>) {
+        let model = match model {
+            Some(model) => model,
+            None => return,
+        };
+        if let Some(flag) = &self.config.model_flag {
             args.push(flag.clone());
             args.push(model.to_string());
         }
+        if let Some(entry) = self.config.model(model) {
+            args.extend(entry.args.iter().cloned());
+        }
+    }
+}
 
-        // Per-agent adaptations
-        match self.name.as_str() {
-            "claude" => {
-                // Claude Code: uses --dangerously-skip-permissions for non-interactive
-                if !args.iter().any(|a| a.contains("dangerously")) {
-                    args.push("--dangerously-skip-permissions".into());
-                }
-            }
-            "codex" => {
-                // Codex: exec mode for non-interactive, full-auto for auto-approval
-                if args.is_empty() {
-                    args.extend(["exec".into(), "--full-auto".into()]);
-                }
-            }
-            "gemini" => {
-                // Gemini: yolo mode for auto-approval
-                if !args.iter().any(|a| a == "--yolo" || a == "-y") {
-                    args.push("--yolo".into());
-                }
+/// The built-in agent an agent entry is: by name, or else by the file name
+/// of its command (so `my-claude` running `/opt/bin/claude` counts as claude)
+fn builtin_name<'a>(name: &'a str, command: &'a str) -> Option<&'a str> {
+    if BUILTIN_AGENTS.contains(&name) {
+        return Some(name);
+    }
+    Path::new(command)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .filter(|f| BUILTIN_AGENTS.contains(f))
+}
+
+/// Add `prompt` to `args`; a prompt for stdin is handed back instead.
+/// Positional prompts follow `--`, so one starting with `-` stays a prompt.
+fn pass_prompt(delivery: &PromptDelivery, args: &mut Vec<String>, prompt: String) -> Option<String> {
+    match delivery {
+        PromptDelivery::Positional => args.extend(["--".to_string(), prompt]),
+        PromptDelivery::Flag { flag } => args.extend([flag.clone(), prompt]),
+        PromptDelivery::Subcommand { name } => {
+            if !args.contains(name) {
+                args.insert(0, name.clone());
             }
-            _ => {}
+            args.extend(["--".to_string(), prompt]);
         }
+        PromptDelivery::Stdin => return Some(prompt),
+    }
+    None
+}
 
-        (self.config.command.clone(), args)
+fn apply_mode_args(mode_args: &ModeArgs, args: &mut Vec<String>) {
+    args.retain(|arg| !mode_args.conflicts.contains(arg));
+    if mode_args.only_without_args {
+        if args.is_empty() {
+            args.extend(mode_args.args.iter().cloned());
+        }
+        return;
     }
+    let present = match mode_args.args.as_slice() {
+        [flag, value] if flag.starts_with('-') && !value.starts_with('-') => {
+            let names: Vec<&String> = std::iter::once(flag).chain(&mode_args.aliases).collect();
+            keep_flag_value(args, &names, value)
+        }
+        _ => mode_args
+            .args
+            .iter()
+            .chain(&mode_args.aliases)
+            .any(|flag| args.contains(flag)),
+    };
+    if !present {
+        args.extend(mode_args.args.iter().cloned());
+    }
+}
 
-    /// Get environment variables to set for the agent
-    pub fn env_vars(&self) -> &std::collections::HashMap<String, String> {
-        &self.config.env
+/// Drop the flags `names` from `args` unless set to `value` (as `--flag
+/// value` or `--flag=value`); returns whether one is set to it
+fn keep_flag_value(args: &mut Vec<String>, names: &[&String], value: &str) -> bool {
+    let mut present = false;
+    let mut i = 0;
+    while i < args.len() {
+        let (name, inline) = match args[i].split_once('=') {
+            Some((name, v)) => (name, Some(v)),
+            None => (args[i].as_str(), None),
+        };
+        if !names.iter().any(|n| n.as_str() == name) {
+            i += 1;
+            continue;
+        }
+        let width = if inline.is_some() { 1 } else { 2.min(args.len() - i) };
+        let given = inline.or_else(|| args.get(i + 1).map(String::as_str));
+        if given == Some(value) {
+            present = true;
+            i += width;
+        } else {
+            args.drain(i..i + width);
+        }
+    }
+    present
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+/// Canonical (lowercase, hyphenated) UUID, the form agents name their
+/// conversations by
+const UUID: &str = "[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}";
+
+fn mode_args(args: &[&str], aliases: &[&str]) -> ModeArgs {
+    ModeArgs {
+        args: strings(args),
+        aliases: strings(aliases),
+        only_without_args: false,
+        conflicts: vec![],
+    }
+}
+
+/// Descriptor of a built-in agent; empty for anything else
+pub fn builtin_descriptor(name: &str) -> AgentDescriptor {
+    match name {
+        "claude" => AgentDescriptor {
+            // Plan mode reads and proposes but doesn't edit or run anything
+            permissions: Some(PermissionArgs {
+                auto: Some(mode_args(&["--dangerously-skip-permissions"], &[])),
+                ask: Some(ModeArgs {
+                    conflicts: strings(&["--dangerously-skip-permissions"]),
+                    ..mode_args(&[], &[])
+                }),
+                read_only: Some(ModeArgs {
+                    conflicts: strings(&["--dangerously-skip-permissions"]),
+                    ..mode_args(&["--permission-mode", "plan"], &[])
+                }),
+            }),
+            initial_prompt: Some(PromptDelivery::Positional),
+            resume_args: Some(strings(&["--resume", "{session_id}"])),
+            // One `<id>.jsonl` per conversation, grouped by project
+            session_id: Some(SessionIdSource {
+                pattern: None,
+                dir: Some("~/.claude/projects/{project}".into()),
+                file_pattern: Some(format!(r"^({UUID})\.jsonl$")),
+            }),
+            headless: Some(HeadlessMode {
+                args: strings(&["--output-format", "stream-json", "--verbose"]),
+                prompt: PromptDelivery::Flag { flag: "-p".into() },
+                format: EventFormat::ClaudeJson,
+                permissions: None,
+            }),
+            approval_prompt: Some(ApprovalPrompt {
+                pattern: r"(?i)do you want to (proceed|make this edit|create|allow)[^?\n]*\?".into(),
+                approve: "1".into(),
+                deny: "\x1b".into(),
+            }),
+            version_args: Some(strings(&["--version"])),
+            required_env: None,
+        },
+        // Codex: exec mode for non-interactive, full-auto for auto-approval
+        "codex" => AgentDescriptor {
+            permissions: Some(PermissionArgs {
+                auto: Some(ModeArgs {
+                    args: strings(&["exec", "--full-auto"]),
+                    aliases: vec![],
+                    only_without_args: true,
+                    conflicts: vec![],
+                }),
+                // Exec mode runs without asking, so ask mode is interactive
+                ask: Some(ModeArgs {
+                    conflicts: strings(&["exec", "--full-auto", "--dangerously-bypass-approvals-and-sandbox"]),
+                    ..mode_args(&[], &[])
+                }),
+                read_only: Some(ModeArgs {
+                    conflicts: strings(&["--full-auto", "--dangerously-bypass-approvals-and-sandbox"]),
+                    ..mode_args(&["--sandbox", "read-only"], &["-s"])
+                }),
+            }),
+            initial_prompt: Some(PromptDelivery::Positional),
+            resume_args: Some(strings(&["resume", "{session_id}"])),
+            // Printed as `session id: <id>`; rollouts of every project share
+            // dated dirs
+            session_id: Some(SessionIdSource {
+                pattern: Some(format!(r"(?i)session(?: id)?:\s*({UUID})")),
+                dir: Some("~/.codex/sessions".into()),
+                file_pattern: Some(format!(r"^rollout-.+-({UUID})\.jsonl$")),
+            }),
+            headless: Some(HeadlessMode {
+                args: strings(&["exec", "--json"]),
+                prompt: PromptDelivery::Positional,
+                format: EventFormat::CodexJson,
+                // Already in exec mode, so auto only needs its flag
+                permissions: Some(PermissionArgs {
+                    auto: Some(mode_args(&["--full-auto"], &[])),
+                    ..Default::default()
+                }),
+            }),
+            approval_prompt: Some(ApprovalPrompt {
+                pattern: r"(?i)(would you like to (run the following command|make the following edits)|allow command)\?".into(),
+                approve: "y".into(),
+                deny: "\x1b".into(),
+            }),
+            version_args: Some(strings(&["--version"])),
+            required_env: None,
+        },
+        "gemini" => AgentDescriptor {
+            permissions: Some(PermissionArgs {
+                auto: Some(mode_args(&["--yolo"], &["-y"])),
+                ask: Some(ModeArgs {
+                    conflicts: strings(&["--yolo", "-y"]),
+                    ..mode_args(&[], &[])
+                }),
+                read_only: None,
+            }),
+            initial_prompt: Some(PromptDelivery::Flag {
+                flag: "--prompt-interactive".into(),
+            }),
+            resume_args: Some(strings(&["--resume", "{session_id}"])),
+            // Chats are filed under a hash of the project and named by a
+            // short id that `--resume` doesn't take
+            session_id: None,
+            headless: Some(HeadlessMode {
+                args: strings(&["--output-format", "stream-json"]),
+                prompt: PromptDelivery::Flag { flag: "-p".into() },
+                format: EventFormat::GeminiJson,
+                permissions: None,
+            }),
+            approval_prompt: Some(ApprovalPrompt {
+                pattern: r"(?i)(allow execution[^?\n]*|apply this change|do you want to proceed)\?".into(),
+                approve: "1".into(),
+                deny: "\x1b".into(),
+            }),
+            version_args: Some(strings(&["--version"])),
+            required_env: None,
+        },
+        _ => AgentDescriptor::default(),
+    }
+}
+
+/// Idle prompt patterns of a built-in agent
+pub fn builtin_prompt_patterns(name: &str) -> Vec<String> {
+    match name {
+        "claude" => strings(&[r"\? for shortcuts"]),
+        "codex" => strings(&[r"(?i)send a message"]),
+        "gemini" => strings(&[r"(?i)type your message"]),
+        _ => vec![],
     }
 }
 
@@ -68,6 +419,12 @@ mod tests {
                 models: vec![],
                 default_model: None,
                 model_flag: None,
+                free_form_models: false,
+                prompt_patterns: vec![],
+                limits: Default::default(),
+                usage_patterns: vec![],
+                usage_format: None,
+                descriptor: AgentDescriptor::default(),
             },
         )
     }
@@ -190,16 +547,22 @@ mod tests {
                 models: vec![],
                 default_model: None,
                 model_flag: None,
+                free_form_models: false,
+                prompt_patterns: vec![],
+                limits: Default::default(),
+                usage_patterns: vec![],
+                usage_format: None,
+                descriptor: AgentDescriptor::default(),
             },
         );
 
-        assert_eq!(adapter.env_vars().get("API_KEY").unwrap(), "secret");
+        assert_eq!(adapter.env_vars(None).get("API_KEY").unwrap(), "secret");
     }
 
     #[test]
     fn test_env_vars_empty() {
         let adapter = make_agent("claude", vec![]);
-        assert!(adapter.env_vars().is_empty());
+        assert!(adapter.env_vars(None).is_empty());
     }
 
     // ── Model flag injection tests ──
@@ -211,9 +574,15 @@ mod tests {
                 command: name.to_string(),
                 args,
                 env: HashMap::new(),
-                models: vec!["model-a".to_string(), "model-b".to_string()],
+                models: vec!["model-a".into(), "model-b".into()],
                 default_model: Some("model-a".to_string()),
                 model_flag,
+                free_form_models: false,
+                prompt_patterns: vec![],
+                limits: Default::default(),
+                usage_patterns: vec![],
+                usage_format: None,
+                descriptor: AgentDescriptor::default(),
             },
         )
     }
@@ -229,6 +598,19 @@ mod tests {
         assert!(args.contains(&"--dangerously-skip-permissions".to_string()));
     }
 
+    #[test]
+    fn test_model_entry_args_and_env_follow_the_model() {
+        let mut adapter = make_agent_with_model_flag("custom", vec![], Some("-m".to_string()));
+        adapter.config.env.insert("CTX".into(), "small".into());
+        adapter.config.models[1].args = vec!["--effort".into(), "high".into()];
+        adapter.config.models[1].env.insert("CTX".into(), "large".into());
+
+        assert_eq!(adapter.build_command(Some("model-b")).1, vec!["-m", "model-b", "--effort", "high"]);
+        assert_eq!(adapter.build_command(Some("model-a")).1, vec!["-m", "model-a"]);
+        assert_eq!(adapter.env_vars(Some("model-b"))["CTX"], "large");
+        assert_eq!(adapter.env_vars(Some("model-a"))["CTX"], "small");
+    }
+
     #[test]
     fn test_build_command_model_injection_with_existing_args_preserved() {
         let adapter = make_agent_with_model_flag(
@@ -318,4 +700,239 @@ mod tests {
         assert!(args.contains(&"--model".to_string()));
         assert!(args.contains(&"gpt-4.1".to_string()));
     }
+
+    // ── Descriptor tests ──
+
+    #[test]
+    fn test_custom_agent_descriptor_from_config() {
+        let mut adapter = make_agent("aider", vec!["--no-git".to_string()]);
+        adapter.config.descriptor.permissions = Some(PermissionArgs {
+            auto: Some(mode_args(&["--yes-always"], &[])),
+            ..Default::default()
+        });
+        let (cmd, args) = adapter.build_command(None);
+        assert_eq!(cmd, "aider");
+        assert_eq!(args, vec!["--no-git", "--yes-always"]);
+    }
+
+    #[test]
+    fn test_config_overrides_builtin_descriptor() {
+        let mut config = make_agent("claude", vec![]).config;
+        config.descriptor.permissions = Some(PermissionArgs {
+            auto: Some(ModeArgs::default()),
+            ..Default::default()
+        });
+        let adapter = AgentAdapter::new("claude".to_string(), config);
+        let (_, args) = adapter.build_command(None);
+        assert!(args.is_empty());
+        // Modes the config leaves unset keep the built-in args
+        let adapter = adapter.with_permission_mode(PermissionMode::ReadOnly).unwrap();
+        assert_eq!(adapter.build_command(None).1, vec!["--permission-mode", "plan"]);
+        // Parts left unset still come from the built-in descriptor
+        assert_eq!(
+            adapter.config.descriptor.initial_prompt,
+            Some(PromptDelivery::Positional)
+        );
+        assert!(!adapter.config.prompt_patterns.is_empty());
+    }
+
+    #[test]
+    fn test_builtin_descriptor_found_by_command_name() {
+        let mut config = make_agent("x", vec![]).config;
+        config.command = "/opt/bin/gemini".to_string();
+        let adapter = AgentAdapter::new("gemini-work".to_string(), config);
+        let (cmd, args) = adapter.build_command(None);
+        assert_eq!(cmd, "/opt/bin/gemini");
+        assert_eq!(args, vec!["--yolo"]);
+    }
+
+    #[test]
+    fn test_descriptor_fields_deserialize_flat_in_agent_config() {
+        let json = r#"{
+            "command": "opencode",
+            "permissions": {"auto": {"args": ["--auto"]}},
+            "initial_prompt": {"type": "flag", "flag": "--prompt"},
+            "resume_args": ["--session", "{session_id}"]
+        }"#;
+        let config: AgentConfig = serde_json::from_str(json).unwrap();
+        let adapter = AgentAdapter::new("opencode".to_string(), config);
+        assert_eq!(
+            adapter.config.descriptor.initial_prompt,
+            Some(PromptDelivery::Flag { flag: "--prompt".into() })
+        );
+        assert!(adapter.config.descriptor.headless.is_none());
+        assert_eq!(adapter.build_command(None).1, vec!["--auto"]);
+    }
+
+    #[test]
+    fn test_resume_args_fill_in_session_id() {
+        let mut claude = make_agent("claude", vec![]);
+        claude.resume_session = Some("abc".into());
+        assert_eq!(
+            claude.build_command(None).1,
+            vec!["--dangerously-skip-permissions", "--resume", "abc"]
+        );
+
+        let mut codex = make_agent("codex", vec![]);
+        codex.resume_session = Some("abc".into());
+        assert_eq!(codex.build_command(None).1, vec!["exec", "--full-auto", "resume", "abc"]);
+
+        // Agents without resume args start a fresh conversation
+        let mut custom = make_agent("custom-agent", vec![]);
+        custom.resume_session = Some("abc".into());
+        assert!(custom.build_command(None).1.is_empty());
+    }
+
+    // ── Initial prompt tests ──
+
+    #[test]
+    fn test_initial_prompt_per_delivery() {
+        let claude = make_agent("claude", vec![]);
+        let (_, args, stdin) = claude.build_command_with_prompt(None, Some("fix the build"));
+        assert_eq!(args.last().unwrap(), "fix the build");
+        assert!(stdin.is_none());
+
+        let gemini = make_agent("gemini", vec![]);
+        let (_, args, _) = gemini.build_command_with_prompt(None, Some("fix it"));
+        assert_eq!(args, vec!["--yolo", "--prompt-interactive", "fix it"]);
+
+        let mut runner = make_agent("runner", vec!["--quiet".to_string()]);
+        runner.config.descriptor.initial_prompt = Some(PromptDelivery::Subcommand { name: "exec".into() });
+        let (_, args, _) = runner.build_command_with_prompt(None, Some("task"));
+        assert_eq!(args, vec!["exec", "--quiet", "--", "task"]);
+
+        // A prompt that looks like a flag is still passed as the prompt
+        let (_, args, _) = claude.build_command_with_prompt(None, Some("--help me"));
+        assert_eq!(args[args.len() - 2..], ["--", "--help me"]);
+    }
+
+    #[test]
+    fn test_initial_prompt_on_stdin_or_absent() {
+        let custom = make_agent("custom-agent", vec![]);
+        let (_, args, stdin) = custom.build_command_with_prompt(None, Some("do it"));
+        assert!(args.is_empty());
+        assert_eq!(stdin.as_deref(), Some("do it"));
+
+        let (_, args, stdin) = make_agent("claude", vec![]).build_command_with_prompt(None, Some("  "));
+        assert_eq!(args, vec!["--dangerously-skip-permissions"]);
+        assert!(stdin.is_none());
+    }
+
+    // ── Headless tests ──
+
+    #[test]
+    fn test_headless_command_uses_structured_mode() {
+        let claude = make_agent_with_model_flag("claude", vec![], Some("--model".to_string()));
+        let (cmd, args, stdin, format) = claude.build_headless_command(Some("haiku"), "summarize").unwrap();
+        assert_eq!(cmd, "claude");
+        assert_eq!(
+            args,
+            vec![
+                "--output-format", "stream-json", "--verbose", "--model", "haiku",
+                "--dangerously-skip-permissions", "-p", "summarize",
+            ]
+        );
+        assert!(stdin.is_none());
+        assert_eq!(format, EventFormat::ClaudeJson);
+
+        let (_, args, _, format) = make_agent("codex", vec![]).build_headless_command(None, "fix").unwrap();
+        assert_eq!(args, vec!["exec", "--json", "--full-auto", "--", "fix"]);
+        assert_eq!(format, EventFormat::CodexJson);
+
+        let err = make_agent("custom-agent", vec![]).build_headless_command(None, "x").unwrap_err();
+        assert!(err.contains("no headless mode"));
+    }
+
+    // ── Permission mode tests ──
+
+    #[test]
+    fn test_permission_modes_per_agent() {
+        let claude = |mode| make_agent("claude", vec![]).with_permission_mode(mode).unwrap();
+        assert!(claude(PermissionMode::Ask).build_command(None).1.is_empty());
+        assert_eq!(
+            claude(PermissionMode::ReadOnly).build_command(None).1,
+            vec!["--permission-mode", "plan"]
+        );
+
+        let codex = make_agent("codex", vec![]).with_permission_mode(PermissionMode::ReadOnly).unwrap();
+        assert_eq!(codex.build_command(None).1, vec!["--sandbox", "read-only"]);
+
+        let gemini = make_agent("gemini", vec![]).with_permission_mode(PermissionMode::Ask).unwrap();
+        assert!(gemini.build_command(None).1.is_empty());
+    }
+
+    #[test]
+    fn test_read_only_replaces_conflicting_args() {
+        let codex = make_agent("codex", vec!["-s".into(), "danger-full-access".into(), "--full-auto".into()])
+            .with_permission_mode(PermissionMode::ReadOnly)
+            .unwrap();
+        assert_eq!(codex.build_command(None).1, vec!["--sandbox", "read-only"]);
+
+        let codex = make_agent("codex", vec!["--sandbox=workspace-write".into(), "-s".into(), "read-only".into()])
+            .with_permission_mode(PermissionMode::ReadOnly)
+            .unwrap();
+        assert_eq!(codex.build_command(None).1, vec!["-s", "read-only"]);
+
+        let claude = make_agent(
+            "claude",
+            vec!["--dangerously-skip-permissions".into(), "--permission-mode".into(), "acceptEdits".into()],
+        )
+        .with_permission_mode(PermissionMode::ReadOnly)
+        .unwrap();
+        assert_eq!(claude.build_command(None).1, vec!["--permission-mode", "plan"]);
+    }
+
+    #[test]
+    fn test_ask_drops_configured_claude_skip_permissions() {
+        let claude = make_agent("claude", vec!["--dangerously-skip-permissions".into(), "--verbose".into()])
+            .with_permission_mode(PermissionMode::Ask)
+            .unwrap();
+        assert_eq!(claude.build_command(None).1, vec!["--verbose"]);
+    }
+
+    #[test]
+    fn test_ask_drops_configured_codex_full_auto() {
+        let codex = make_agent("codex", vec!["exec".into(), "--full-auto".into()])
+            .with_permission_mode(PermissionMode::Ask)
+            .unwrap();
+        assert!(codex.build_command(None).1.is_empty());
+
+        let bypass = make_agent("codex", vec!["--dangerously-bypass-approvals-and-sandbox".into()])
+            .with_permission_mode(PermissionMode::Ask)
+            .unwrap();
+        assert!(bypass.build_command(None).1.is_empty());
+    }
+
+    #[test]
+    fn test_ask_drops_configured_gemini_yolo() {
+        let gemini = make_agent("gemini", vec!["--yolo".into(), "-y".into(), "--debug".into()])
+            .with_permission_mode(PermissionMode::Ask)
+            .unwrap();
+        assert_eq!(gemini.build_command(None).1, vec!["--debug"]);
+    }
+
+    #[test]
+    fn test_read_only_requires_mode_args() {
+        let err = make_agent("gemini", vec![])
+            .with_permission_mode(PermissionMode::ReadOnly)
+            .err()
+            .unwrap();
+        assert!(err.contains("no read-only mode"));
+        assert!(make_agent("custom-agent", vec![]).with_permission_mode(PermissionMode::ReadOnly).is_err());
+        assert!(make_agent("custom-agent", vec![]).with_permission_mode(PermissionMode::Ask).is_ok());
+    }
+
+    #[test]
+    fn test_headless_permission_modes() {
+        let codex = make_agent("codex", vec![]);
+        let (_, args, _, _) = codex.build_headless_command(None, "fix").unwrap();
+        assert_eq!(args, vec!["exec", "--json", "--full-auto", "--", "fix"]);
+
+        let codex = codex.with_permission_mode(PermissionMode::ReadOnly).unwrap();
+        let (_, args, _, _) = codex.build_headless_command(None, "fix").unwrap();
+        assert_eq!(args, vec!["exec", "--json", "--sandbox", "read-only", "--", "fix"]);
+
+        let claude = make_agent("claude", vec![]).with_permission_mode(PermissionMode::Ask).unwrap();
+        assert!(claude.build_headless_command(None, "x").unwrap_err().contains("can't ask"));
+    }
 }
@@ -0,0 +1,280 @@
+use crate::agent::AgentAdapter;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long a version probe may take before it is killed
+const VERSION_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether an agent CLI can be started
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AgentHealth {
+    pub agent: String,
+    pub command: String,
+    /// Where the command resolved to; `None` when it wasn't found
+    pub path: Option<String>,
+    /// First line printed by the version probe
+    pub version: Option<String>,
+    /// Why the version probe failed, when it ran and failed
+    pub version_error: Option<String>,
+    /// Required env vars that are set nowhere
+    pub missing_credentials: Vec<String>,
+    /// Found and not missing credentials (a failed version probe doesn't
+    /// count against it)
+    pub ready: bool,
+}
+
+impl AgentHealth {
+    /// Error for spawning an agent that isn't ready
+    pub fn ensure_ready(&self) -> Result<(), String> {
+        if self.path.is_none() {
+            return Err(format!(
+                "agent {} is not installed: `{}` was not found on PATH",
+                self.agent, self.command
+            ));
+        }
+        if !self.missing_credentials.is_empty() {
+            return Err(format!(
+                "agent {} is missing credentials: {}",
+                self.agent,
+                self.missing_credentials.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Check an agent's command and credentials. `secrets` are the keychain
+/// secrets handed to agents as env vars; the version probe only runs when
+/// `probe_version` is set.
+pub fn check(adapter: &AgentAdapter, secrets: &HashMap<String, String>, probe_version: bool) -> AgentHealth {
+    let command = adapter.config.command.clone();
+    let path = resolve_command(&command, std::env::var_os("PATH").as_deref());
+
+    let (version, version_error) = match (&path, probe_version) {
+        (Some(path), true) => {
+            let args = adapter
+                .config
+                .descriptor
+                .version_args
+                .clone()
+                .unwrap_or_else(|| vec!["--version".into()]);
+            match run_version_probe(path, &args, VERSION_PROBE_TIMEOUT) {
+                Ok(version) => (Some(version), None),
+                Err(e) => (None, Some(e)),
+            }
+        }
+        _ => (None, None),
+    };
+
+    let missing_credentials: Vec<String> = adapter
+        .config
+        .descriptor
+        .required_env
+        .iter()
+        .flatten()
+        .filter(|key| {
+            !adapter.config.env.contains_key(*key)
+                && !secrets.contains_key(*key)
+                && std::env::var_os(key).is_none()
+        })
+        .cloned()
+        .collect();
+
+    AgentHealth {
+        agent: adapter.name.clone(),
+        command,
+        ready: path.is_some() && missing_credentials.is_empty(),
+        path: path.map(|p| p.to_string_lossy().into_owned()),
+        version,
+        version_error,
+        missing_credentials,
+    }
+}
+
+/// Find `command` the way a shell would: paths containing a separator are
+/// taken as they are, bare names are looked up in `path_var`
+pub fn resolve_command(command: &str, path_var: Option<&OsStr>) -> Option<PathBuf> {
+    if command.is_empty() {
+        return None;
+    }
+    if command.contains('/') || command.contains(std::path::MAIN_SEPARATOR) {
+        let path = PathBuf::from(command);
+        return is_executable(&path).then_some(path);
+    }
+    std::env::split_paths(path_var?)
+        .flat_map(|dir| candidates(&dir, command))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(windows)]
+fn candidates(dir: &Path, command: &str) -> Vec<PathBuf> {
+    ["", ".exe", ".cmd", ".bat"]
+        .iter()
+        .map(|ext| dir.join(format!("{command}{ext}")))
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn candidates(dir: &Path, command: &str) -> Vec<PathBuf> {
+    vec![dir.join(command)]
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Run the probe and return the first line it prints. Its output is read
+/// as it comes, so a probe printing more than a pipe holds doesn't stall.
+fn run_version_probe(path: &Path, args: &[String], timeout: Duration) -> Result<String, String> {
+    let mut child = Command::new(path)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("spawn error: {e}"))?;
+    let stdout = child.stdout.take().map(read_pipe);
+    let stderr = child.stderr.take().map(read_pipe);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(|e| format!("wait error: {e}"))? {
+            Some(status) => break status,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("no answer within {}s", timeout.as_secs()));
+            }
+            None => std::thread::sleep(Duration::from_millis(20)),
+        }
+    };
+
+    let collect = |reader: Option<std::thread::JoinHandle<Vec<u8>>>| {
+        reader.and_then(|r| r.join().ok()).unwrap_or_default()
+    };
+    let (stdout, stderr) = (collect(stdout), collect(stderr));
+    let first_line = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_string)
+    };
+    match first_line(&stdout).or_else(|| first_line(&stderr)) {
+        Some(line) if status.success() => Ok(line),
+        Some(line) => Err(format!("exited with {status}: {line}")),
+        None if status.success() => Err("printed no version".into()),
+        None => Err(format!("exited with {status}")),
+    }
+}
+
+fn read_pipe(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::config::{AgentConfig, AgentDescriptor, ResourceLimits};
+    use std::os::unix::fs::PermissionsExt;
+
+    fn script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn adapter(command: &str, required_env: Option<Vec<String>>) -> AgentAdapter {
+        AgentAdapter::new(
+            "tool".into(),
+            AgentConfig {
+                command: command.into(),
+                args: vec![],
+                env: HashMap::new(),
+                models: vec![],
+                default_model: None,
+                model_flag: None,
+                free_form_models: false,
+                prompt_patterns: vec![],
+                limits: ResourceLimits::default(),
+                usage_patterns: vec![],
+                usage_format: None,
+                descriptor: AgentDescriptor {
+                    required_env,
+                    ..Default::default()
+                },
+            },
+        )
+    }
+
+    #[test]
+    fn test_resolve_command_searches_path_for_executables() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = script(dir.path(), "tool", "true");
+        std::fs::write(dir.path().join("plain"), "not executable").unwrap();
+        let path_var = std::env::join_paths([Path::new("/nonexistent"), dir.path()]).unwrap();
+
+        assert_eq!(resolve_command("tool", Some(&path_var)), Some(tool.clone()));
+        assert_eq!(resolve_command("plain", Some(&path_var)), None);
+        assert_eq!(resolve_command(tool.to_str().unwrap(), None), Some(tool));
+        assert_eq!(resolve_command("tool", None), None);
+    }
+
+    #[test]
+    fn test_check_probes_version_and_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = script(dir.path(), "tool", "echo; echo 'tool 1.2.3'");
+        let required = Some(vec!["OCESTRATER_TEST_SECRET".to_string(), "OCESTRATER_TEST_UNSET".to_string()]);
+        let secrets = HashMap::from([("OCESTRATER_TEST_SECRET".to_string(), "x".to_string())]);
+
+        let health = check(&adapter(tool.to_str().unwrap(), required), &secrets, true);
+        assert_eq!(health.version.as_deref(), Some("tool 1.2.3"));
+        assert_eq!(health.missing_credentials, vec!["OCESTRATER_TEST_UNSET"]);
+        assert!(!health.ready);
+        assert!(health.ensure_ready().unwrap_err().contains("OCESTRATER_TEST_UNSET"));
+    }
+
+    #[test]
+    fn test_missing_command_is_not_ready() {
+        let health = check(&adapter("/nonexistent/agent-cli", None), &HashMap::new(), true);
+        assert!(health.path.is_none());
+        assert!(health.version.is_none() && health.version_error.is_none());
+        assert!(health.ensure_ready().unwrap_err().contains("not installed"));
+    }
+
+    #[test]
+    fn test_probe_printing_more_than_a_pipe_holds() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = script(dir.path(), "tool", "echo 'tool 2.0'; head -c 200000 /dev/zero | tr '\\0' x; echo");
+        let version = run_version_probe(&tool, &[], Duration::from_secs(5)).unwrap();
+        assert_eq!(version, "tool 2.0");
+    }
+
+    #[test]
+    fn test_failing_version_probe_is_reported_but_ready() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = script(dir.path(), "tool", "echo 'unknown option' >&2; exit 2");
+        let health = check(&adapter(tool.to_str().unwrap(), None), &HashMap::new(), true);
+        assert!(health.version_error.unwrap().contains("unknown option"));
+        assert!(health.ready);
+    }
+}
@@ -0,0 +1,131 @@
+/// Strip ANSI escape sequences (CSI, OSC, two-byte ESC) and non-printing
+/// control characters from terminal output, keeping newlines and tabs.
+/// A bare carriage return is turned into a newline so that redrawn lines
+/// (spinners, progress bars) read as separate lines rather than run together.
+pub fn strip_ansi(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters/intermediates until a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: until BEL or ST (ESC \)
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // Charset designation takes one more byte, e.g. ESC ( B
+                Some('(') | Some(')') => {
+                    chars.next();
+                }
+                // Any other two-byte escape (ESC =, ESC >, ESC 7, ...)
+                _ => {}
+            },
+            '\r' => {
+                if chars.peek() != Some(&'\n') {
+                    out.push('\n');
+                }
+            }
+            '\n' | '\t' => out.push(c),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Length of `input` without an escape sequence cut off at its end (e.g. by
+/// a read boundary), so the sequence can be stripped whole once the rest
+/// arrives
+pub fn complete_len(input: &str) -> usize {
+    let Some(start) = input.rfind('\x1b') else {
+        return input.len();
+    };
+    let mut rest = input[start + 1..].chars();
+    let complete = match rest.next() {
+        None => false,
+        Some('[') => rest.any(|c| ('@'..='~').contains(&c)),
+        // An ST ends with its own ESC, found above as the last one
+        Some(']') => rest.any(|c| c == '\x07'),
+        Some('(') | Some(')') => rest.next().is_some(),
+        Some(_) => true,
+    };
+    if complete {
+        input.len()
+    } else {
+        start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_unchanged() {
+        assert_eq!(strip_ansi("hello world\n"), "hello world\n");
+    }
+
+    #[test]
+    fn test_strips_sgr_colors() {
+        assert_eq!(strip_ansi("\x1b[1;32mok\x1b[0m done"), "ok done");
+    }
+
+    #[test]
+    fn test_strips_cursor_movement() {
+        assert_eq!(strip_ansi("\x1b[2K\x1b[1Gfoo\x1b[?25l"), "foo");
+    }
+
+    #[test]
+    fn test_strips_osc_title_with_bel_and_st() {
+        assert_eq!(strip_ansi("\x1b]0;title\x07a\x1b]8;;http://x\x1b\\b"), "ab");
+    }
+
+    #[test]
+    fn test_charset_designation() {
+        assert_eq!(strip_ansi("\x1b(Bx"), "x");
+    }
+
+    #[test]
+    fn test_carriage_return_handling() {
+        assert_eq!(strip_ansi("a\r\nb"), "a\nb");
+        assert_eq!(strip_ansi("50%\r100%"), "50%\n100%");
+    }
+
+    #[test]
+    fn test_drops_other_control_chars() {
+        assert_eq!(strip_ansi("a\x07b\x08c\td"), "abc\td");
+    }
+
+    #[test]
+    fn test_complete_len_holds_back_cut_off_escapes() {
+        assert_eq!(complete_len("ok\x1b[0m"), 6);
+        assert_eq!(complete_len("ok\x1b"), 2);
+        assert_eq!(complete_len("ok\x1b[3"), 2);
+        assert_eq!(complete_len("ok\x1b]0;tit"), 2);
+        assert_eq!(complete_len("ok\x1b]0;title\x07"), 12);
+        assert_eq!(complete_len("ok\x1b("), 2);
+        assert_eq!(complete_len("ok\x1b7"), 4);
+    }
+
+    #[test]
+    fn test_unicode_preserved() {
+        assert_eq!(strip_ansi("\x1b[33m✔ 완료\x1b[0m"), "✔ 완료");
+    }
+}
@@ -0,0 +1,569 @@
+use crate::ansi::strip_ansi;
+use crate::config::{ApprovalDecision, ApprovalPrompt, ApprovalRule};
+use crate::pty_manager::{PtyManager, SessionKey};
+use crate::transcript;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use uuid::Uuid;
+
+/// Stripped output searched for a prompt
+const WINDOW_CHARS: usize = 4096;
+
+/// Most lines of an unframed request taken next to the question
+const REQUEST_LINES: usize = 10;
+
+/// Time after an answer in which the same prompt may still be redrawn, so
+/// it isn't taken for a new one
+const ANSWER_SETTLE: Duration = Duration::from_millis(1500);
+
+/// Frame characters TUIs draw around a prompt
+const FRAME_CHARS: &str = "│┃║╭╮╰╯─━═┌┐└┘";
+
+/// Corners opening and closing a prompt frame
+const FRAME_TOP: &str = "╭┌";
+const FRAME_BOTTOM: &str = "╰└";
+
+/// Payload of the `approval-required` event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PendingApproval {
+    pub id: String,
+    pub workspace_id: String,
+    /// What the agent asks to do, as shown above its question
+    pub request: String,
+    pub time_ms: u64,
+}
+
+/// A permission prompt answered by a rule or the user; payload of the
+/// `approval-decided` event and one line of the approval log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalRecord {
+    pub id: String,
+    pub workspace_id: String,
+    pub request: String,
+    /// `approve`, `deny`, or `manual` when answered in the terminal
+    pub decision: ApprovalDecision,
+    /// Rule that decided; `None` when the user answered
+    pub rule: Option<String>,
+    pub time_ms: u64,
+    /// Why the answer couldn't be sent; the prompt then still waits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A permission prompt found in the output
+#[derive(Debug, PartialEq)]
+pub enum Detected {
+    /// Decided by a rule; `keys` answer the prompt
+    Decided { record: ApprovalRecord, keys: String },
+    /// Left to the user
+    Undecided(PendingApproval),
+}
+
+struct CompiledRule {
+    rule: ApprovalRule,
+    pattern: Regex,
+}
+
+/// Finds a workspace's agent permission prompts and decides them by its
+/// approval rules. While a prompt waits for the user further prompts are
+/// ignored, and briefly after an answer a redraw of the same prompt is.
+pub struct ApprovalEngine {
+    workspace_id: String,
+    prompt: Regex,
+    approve_keys: String,
+    deny_keys: String,
+    rules: Vec<CompiledRule>,
+    window: String,
+    pending: Option<PendingApproval>,
+    /// Request of the latest answered prompt, until it has settled
+    answered: Option<(String, Instant)>,
+}
+
+impl ApprovalEngine {
+    /// Rules with invalid patterns are logged and left out
+    pub fn new(workspace_id: &str, prompt: &ApprovalPrompt, rules: &[ApprovalRule]) -> Result<Self, String> {
+        let pattern = Regex::new(&prompt.pattern).map_err(|e| format!("invalid approval prompt pattern: {e}"))?;
+        let rules = rules
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(pattern) => Some(CompiledRule {
+                    rule: rule.clone(),
+                    pattern,
+                }),
+                Err(e) => {
+                    eprintln!("invalid approval rule pattern in {}: {e}", rule.name);
+                    None
+                }
+            })
+            .collect();
+        Ok(Self {
+            workspace_id: workspace_id.to_string(),
+            prompt: pattern,
+            approve_keys: prompt.approve.clone(),
+            deny_keys: prompt.deny.clone(),
+            rules,
+            window: String::new(),
+            pending: None,
+            answered: None,
+        })
+    }
+
+    /// Check a chunk of raw output for a permission prompt
+    pub fn on_output(&mut self, text: &str, now: Instant) -> Option<Detected> {
+        if self.pending.is_some() {
+            self.window.clear();
+            return None;
+        }
+
+        self.window.push_str(&strip_ansi(text));
+        if self.window.len() > WINDOW_CHARS {
+            let cut = self.window.len() - WINDOW_CHARS;
+            let cut = (cut..self.window.len())
+                .find(|&i| self.window.is_char_boundary(i))
+                .unwrap_or(self.window.len());
+            self.window.drain(..cut);
+        }
+
+        let end = self.prompt.find(&self.window)?.end();
+        let request = request_text(&self.window, end);
+        self.window.clear();
+        let redrawn = self
+            .answered
+            .as_ref()
+            .is_some_and(|(answered, until)| now < *until && *answered == request);
+        if redrawn {
+            return None;
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let time_ms = transcript::now_ms();
+        let (decision, rule) = self.decide(&request);
+        match decision {
+            ApprovalDecision::Ask | ApprovalDecision::Manual => {
+                let pending = PendingApproval {
+                    id,
+                    workspace_id: self.workspace_id.clone(),
+                    request,
+                    time_ms,
+                };
+                self.pending = Some(pending.clone());
+                Some(Detected::Undecided(pending))
+            }
+            decision => {
+                self.answered = Some((request.clone(), now + ANSWER_SETTLE));
+                Some(Detected::Decided {
+                    keys: self.keys(decision == ApprovalDecision::Approve).to_string(),
+                    record: ApprovalRecord {
+                        id,
+                        workspace_id: self.workspace_id.clone(),
+                        request,
+                        decision,
+                        rule,
+                        time_ms,
+                        error: None,
+                    },
+                })
+            }
+        }
+    }
+
+    /// Decision of the first rule matching `request`, or ask
+    fn decide(&self, request: &str) -> (ApprovalDecision, Option<String>) {
+        self.rules
+            .iter()
+            .find(|r| r.pattern.is_match(request))
+            .map(|r| (r.rule.decision, Some(r.rule.name.clone())))
+            .unwrap_or((ApprovalDecision::Ask, None))
+    }
+
+    fn keys(&self, approve: bool) -> &str {
+        if approve {
+            &self.approve_keys
+        } else {
+            &self.deny_keys
+        }
+    }
+
+    pub fn pending(&self) -> Option<&PendingApproval> {
+        self.pending.as_ref()
+    }
+
+    /// The user typed into the agent, which answers a waiting prompt in the
+    /// terminal; returns the prompt no longer waiting
+    pub fn on_input(&mut self) -> Option<PendingApproval> {
+        self.pending.take()
+    }
+
+    /// Check the visible screen; a waiting prompt that is no longer shown
+    /// was answered some other way and is returned
+    pub fn on_screen(&mut self, screen: &str) -> Option<PendingApproval> {
+        if self.pending.is_none() || self.prompt.is_match(screen) {
+            return None;
+        }
+        self.pending.take()
+    }
+
+    /// Settle the waiting prompt `id`; returns the record and the keys
+    /// answering it
+    pub fn answer(&mut self, id: &str, approve: bool, now: Instant) -> Result<(ApprovalRecord, String), String> {
+        let pending = match self.pending.take() {
+            Some(pending) if pending.id == id => pending,
+            other => {
+                self.pending = other;
+                return Err(format!("no pending approval: {id}"));
+            }
+        };
+        self.answered = Some((pending.request.clone(), now + ANSWER_SETTLE));
+        let decision = if approve {
+            ApprovalDecision::Approve
+        } else {
+            ApprovalDecision::Deny
+        };
+        let record = ApprovalRecord {
+            id: pending.id,
+            workspace_id: pending.workspace_id,
+            request: pending.request,
+            decision,
+            rule: None,
+            time_ms: transcript::now_ms(),
+            error: None,
+        };
+        Ok((record, self.keys(approve).to_string()))
+    }
+}
+
+/// What the agent asks to do, without TUI frames: the inside of the frame
+/// around the question ending at `question_end`, or else the paragraph
+/// ending with it. A question alone in its block ("run the following
+/// command?") takes the lines after it instead.
+fn request_text(window: &str, question_end: usize) -> String {
+    let line_start = window[..question_end].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = window[question_end..]
+        .find('\n')
+        .map_or(window.len(), |i| question_end + i);
+    let above: Vec<&str> = window[..line_start].lines().collect();
+    let below = window.get(line_end + 1..).unwrap_or_default().lines();
+
+    let top = above.iter().rposition(|line| is_frame_edge(line, FRAME_TOP));
+    let bottom = above.iter().rposition(|line| is_frame_edge(line, FRAME_BOTTOM));
+    let mut block: Vec<&str> = match top {
+        Some(top) if Some(top) > bottom => above[top + 1..].to_vec(),
+        _ => {
+            let para = above
+                .iter()
+                .rev()
+                .take_while(|line| !line_content(line).is_empty())
+                .take(REQUEST_LINES - 1)
+                .count();
+            above[above.len() - para..].to_vec()
+        }
+    };
+    block.push(&window[line_start..line_end]);
+
+    if block.iter().filter(|line| !line_content(line).is_empty()).count() == 1 {
+        block.extend(
+            below
+                .skip_while(|line| line_content(line).is_empty())
+                .take_while(|line| !line_content(line).is_empty() && !is_frame_edge(line, FRAME_BOTTOM))
+                .take(REQUEST_LINES),
+        );
+    }
+    block
+        .into_iter()
+        .map(line_content)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn line_content(line: &str) -> &str {
+    line.trim_matches(|c: char| c.is_whitespace() || FRAME_CHARS.contains(c))
+}
+
+fn is_frame_edge(line: &str, corners: &str) -> bool {
+    line.trim_start().starts_with(|c: char| corners.contains(c))
+}
+
+/// Tell the UI a waiting prompt is no longer shown
+pub fn dispatch_cleared(app: &AppHandle, pending: PendingApproval) {
+    let _ = app.emit("approval-cleared", pending);
+}
+
+/// The user answered a waiting prompt by typing into the terminal: clear it
+/// in the UI and log a manual decision off the input thread
+pub fn dispatch_answered(app: &AppHandle, pending: PendingApproval) {
+    dispatch_cleared(app, pending.clone());
+    let record = ApprovalRecord {
+        id: pending.id,
+        workspace_id: pending.workspace_id,
+        request: pending.request,
+        decision: ApprovalDecision::Manual,
+        rule: None,
+        time_ms: transcript::now_ms(),
+        error: None,
+    };
+    let app = app.clone();
+    std::thread::spawn(move || record_decision(&app, record));
+}
+
+/// Ask the UI about an undecided prompt, or answer a decided one off the
+/// output thread
+pub fn dispatch(app: &AppHandle, detected: Detected) {
+    let (record, keys) = match detected {
+        Detected::Undecided(pending) => {
+            let _ = app.emit("approval-required", pending);
+            return;
+        }
+        Detected::Decided { record, keys } => (record, keys),
+    };
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut record = record;
+        let written = app
+            .try_state::<Mutex<PtyManager>>()
+            .ok_or_else(|| "pty manager unavailable".to_string())
+            .and_then(|pty| {
+                pty.lock()
+                    .map_err(|e| e.to_string())?
+                    .write_raw(&SessionKey::agent(&record.workspace_id), keys.as_bytes())
+            });
+        if let Err(e) = written {
+            eprintln!("approval {} not answered: {e}", record.id);
+            record.error = Some(e);
+        }
+        record_decision(&app, record);
+    });
+}
+
+/// Log a decision and emit `approval-decided`
+pub fn record_decision(app: &AppHandle, record: ApprovalRecord) {
+    if let Err(e) = append_log(&log_path(), &record) {
+        eprintln!("approval {} not logged: {e}", record.id);
+    }
+    let _ = app.emit("approval-decided", record);
+}
+
+/// ~/.ocestrater/approvals.jsonl
+pub fn log_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".ocestrater")
+        .join("approvals.jsonl")
+}
+
+/// Append a decision. Requests hold command text, so the log is kept
+/// private like transcripts.
+pub fn append_log(path: &Path, record: &ApprovalRecord) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        let mut dir = std::fs::DirBuilder::new();
+        dir.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut dir, 0o700);
+        dir.create(parent).map_err(|e| format!("mkdir error: {e}"))?;
+        transcript::restrict(parent, 0o700)?;
+    }
+    let line = serde_json::to_string(record).map_err(|e| format!("serialize error: {e}"))?;
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path).map_err(|e| format!("open error: {e}"))?;
+    transcript::restrict(path, 0o600)?;
+    writeln!(file, "{line}").map_err(|e| format!("write error: {e}"))
+}
+
+/// Newest decisions first, optionally of one workspace
+pub fn read_log(path: &Path, workspace_id: Option<&str>, limit: usize) -> Result<Vec<ApprovalRecord>, String> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("open error: {e}")),
+    };
+    let mut records: Vec<ApprovalRecord> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .filter(|r: &ApprovalRecord| match workspace_id {
+            Some(id) => r.workspace_id == id,
+            None => true,
+        })
+        .collect();
+    records.reverse();
+    records.truncate(limit);
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(rules: &[(&str, &str, ApprovalDecision)]) -> ApprovalEngine {
+        let prompt = ApprovalPrompt {
+            pattern: r"(?i)do you want to proceed\?".into(),
+            approve: "1".into(),
+            deny: "\x1b".into(),
+        };
+        let rules: Vec<ApprovalRule> = rules
+            .iter()
+            .map(|(name, pattern, decision)| ApprovalRule {
+                name: name.to_string(),
+                pattern: pattern.to_string(),
+                decision: *decision,
+            })
+            .collect();
+        ApprovalEngine::new("ws1", &prompt, &rules).unwrap()
+    }
+
+    const CARGO_TEST: &str = "\x1b[1m╭────╮\r\n│ Bash command\r\n│   cargo test --workspace\r\n│ Do you want to proceed?\r\n│ ❯ 1. Yes";
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let mut engine = engine(&[
+            ("tests", r"cargo test", ApprovalDecision::Approve),
+            ("net", r"\b(curl|wget)\b", ApprovalDecision::Deny),
+        ]);
+        let now = Instant::now();
+        match engine.on_output(CARGO_TEST, now) {
+            Some(Detected::Decided { record, keys }) => {
+                assert_eq!(record.decision, ApprovalDecision::Approve);
+                assert_eq!(record.rule.as_deref(), Some("tests"));
+                assert_eq!(record.request, "Bash command\ncargo test --workspace\nDo you want to proceed?");
+                assert_eq!(keys, "1");
+            }
+            other => panic!("expected a decision, got {other:?}"),
+        }
+
+        // A redraw right after the answer is not a new prompt
+        assert!(engine.on_output(CARGO_TEST, now + Duration::from_millis(100)).is_none());
+        let later = now + ANSWER_SETTLE;
+        let split = engine.on_output("curl https://example.com\r\nDo you want to pro", later);
+        assert!(split.is_none());
+        match engine.on_output("ceed?", later) {
+            Some(Detected::Decided { record, keys }) => {
+                assert_eq!(record.decision, ApprovalDecision::Deny);
+                assert_eq!(keys, "\x1b");
+            }
+            other => panic!("expected a denial, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unmatched_prompt_waits_for_the_user() {
+        let mut engine = engine(&[("tests", r"cargo test", ApprovalDecision::Approve)]);
+        let now = Instant::now();
+        let pending = match engine.on_output("rm -rf target\nDo you want to proceed?", now) {
+            Some(Detected::Undecided(pending)) => pending,
+            other => panic!("expected a pending approval, got {other:?}"),
+        };
+        assert!(engine.on_output(CARGO_TEST, now).is_none());
+        assert!(engine.answer("other", true, now).is_err());
+
+        let (record, keys) = engine.answer(&pending.id, false, now).unwrap();
+        assert_eq!(record.decision, ApprovalDecision::Deny);
+        assert!(record.rule.is_none());
+        assert_eq!(keys, "\x1b");
+        assert!(engine.pending().is_none());
+    }
+
+    #[test]
+    fn test_prompt_answered_in_terminal_is_cleared() {
+        let mut engine = engine(&[]);
+        let now = Instant::now();
+        assert!(matches!(engine.on_output(CARGO_TEST, now), Some(Detected::Undecided(_))));
+        assert!(engine.on_screen("│ Do you want to proceed?").is_none());
+        let cleared = engine.on_screen("Running cargo test…").unwrap();
+        assert!(engine.answer(&cleared.id, true, now).is_err());
+
+        // The next prompt is raised again
+        assert!(matches!(engine.on_output(CARGO_TEST, now), Some(Detected::Undecided(_))));
+        assert!(engine.on_input().is_some());
+        assert!(engine.pending().is_none());
+    }
+
+    #[test]
+    fn test_rules_match_only_the_framed_request() {
+        let mut engine = engine(&[("tests", r"cargo test", ApprovalDecision::Approve)]);
+        let output = "I'll run cargo test after cleaning up.\r\n╭────╮\r\n│ Bash command\r\n│   rm -rf target\r\n│ Do you want to proceed?";
+        match engine.on_output(output, Instant::now()) {
+            Some(Detected::Undecided(pending)) => {
+                assert_eq!(pending.request, "Bash command\nrm -rf target\nDo you want to proceed?");
+            }
+            other => panic!("expected a pending approval, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unframed_request_is_the_paragraph_around_the_question() {
+        let chat = "Let me run cargo test.\n\n";
+        let shell = format!("{chat}$ rm -rf target\nAllow command?");
+        assert_eq!(request_text(&shell, shell.len()), "$ rm -rf target\nAllow command?");
+        let codex = format!("{chat}Would you like to run the following command?\n\n$ rm -rf target\n\n▌ Yes (y)");
+        let end = codex.find('?').unwrap() + 1;
+        assert_eq!(request_text(&codex, end), "Would you like to run the following command?\n$ rm -rf target");
+    }
+
+    #[test]
+    fn test_new_prompt_right_after_an_answer_is_detected() {
+        let mut engine = engine(&[
+            ("tests", r"cargo test", ApprovalDecision::Approve),
+            ("clean", r"cargo clean", ApprovalDecision::Deny),
+        ]);
+        let now = Instant::now();
+        assert!(engine.on_output(CARGO_TEST, now).is_some());
+        let clean = CARGO_TEST.replace("cargo test --workspace", "cargo clean");
+        match engine.on_output(&clean, now + Duration::from_millis(100)) {
+            Some(Detected::Decided { record, .. }) => assert_eq!(record.rule.as_deref(), Some("clean")),
+            other => panic!("expected a decision, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_log_reads_newest_first_per_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("approvals.jsonl");
+        assert!(read_log(&path, None, 10).unwrap().is_empty());
+        for (id, ws) in [("a", "ws1"), ("b", "ws2"), ("c", "ws1")] {
+            let record = ApprovalRecord {
+                id: id.into(),
+                workspace_id: ws.into(),
+                request: "ls".into(),
+                decision: ApprovalDecision::Approve,
+                rule: None,
+                time_ms: 0,
+                error: None,
+            };
+            append_log(&path, &record).unwrap();
+        }
+        let ids = |records: Vec<ApprovalRecord>| records.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(read_log(&path, Some("ws1"), 10).unwrap()), vec!["c", "a"]);
+        assert_eq!(ids(read_log(&path, None, 2).unwrap()), vec!["c", "b"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_log_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ocestrater").join("approvals.jsonl");
+        let record = ApprovalRecord {
+            id: "a".into(),
+            workspace_id: "ws1".into(),
+            request: "rm -rf target".into(),
+            decision: ApprovalDecision::Manual,
+            rule: None,
+            time_ms: 0,
+            error: Some("session not found".into()),
+        };
+        append_log(&path, &record).unwrap();
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(read_log(&path, None, 10).unwrap(), vec![record]);
+    }
+}
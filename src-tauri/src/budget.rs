@@ -0,0 +1,380 @@
+use crate::commands;
+use crate::config::{BudgetAction, BudgetLimits};
+use crate::pty_manager::{PtyManager, SessionKey};
+use crate::usage::{UsageCounts, WorkspaceUsage};
+use crate::workspace::{WorkspaceManager, WorkspaceState};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+const DEFAULT_WARN_PERCENT: u8 = 80;
+
+/// Time after an interrupt in which output is taken as the agent winding
+/// down rather than working on
+const INTERRUPT_SETTLE: Duration = Duration::from_secs(10);
+
+/// How much runtime a session gathers before it is stored on the workspace
+pub const RUNTIME_RECORD_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetKind {
+    Cost,
+    Runtime,
+    Tokens,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLevel {
+    Warning,
+    Exceeded,
+}
+
+/// Payload of the `budget-alert-*` event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BudgetAlert {
+    pub kind: BudgetKind,
+    pub level: BudgetLevel,
+    /// Configured limit (USD, seconds or tokens, matching the config field)
+    pub limit: f64,
+    pub used: f64,
+    /// Set when the limit was exceeded: what is done about it
+    pub action: Option<BudgetAction>,
+}
+
+/// What a workspace has used that counts against its budget
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BudgetUsage {
+    pub counts: UsageCounts,
+    /// Agent runtime stored on the workspace
+    pub runtime_secs: f64,
+}
+
+impl BudgetUsage {
+    pub fn of(usage: &WorkspaceUsage) -> Self {
+        Self {
+            counts: usage.total(),
+            runtime_secs: usage.runtime_secs,
+        }
+    }
+}
+
+/// Which hard limit, if any, `used` is already over
+pub fn exceeded_by(limits: &BudgetLimits, used: &BudgetUsage) -> Option<BudgetKind> {
+    if limits.max_cost_usd.is_some_and(|max| used.counts.cost_usd >= max) {
+        return Some(BudgetKind::Cost);
+    }
+    if limits
+        .max_runtime_secs
+        .is_some_and(|max| used.runtime_secs >= max as f64)
+    {
+        return Some(BudgetKind::Runtime);
+    }
+    if limits
+        .max_tokens
+        .is_some_and(|max| used.counts.input_tokens + used.counts.output_tokens >= max)
+    {
+        return Some(BudgetKind::Tokens);
+    }
+    None
+}
+
+/// Checks an agent session against its workspace's budget and counts its
+/// runtime. Each limit warns once when crossing `warn_percent`; the first
+/// hard limit reached stops the agent, or interrupts it and stops it if it
+/// goes on working anyway.
+pub struct BudgetWatcher {
+    limits: BudgetLimits,
+    /// Workspace usage, including what earlier sessions used
+    used: BudgetUsage,
+    /// Start of the runtime not yet in `used`
+    since: Instant,
+    warned: Vec<BudgetKind>,
+    /// The hard limit reached, once it was
+    exceeded: Option<BudgetAlert>,
+    /// When the agent was interrupted at a hard limit
+    interrupted_at: Option<Instant>,
+    /// The interrupted agent went on working
+    worked_after_interrupt: bool,
+}
+
+impl BudgetWatcher {
+    pub fn new(limits: BudgetLimits, used: BudgetUsage, now: Instant) -> Self {
+        Self {
+            limits,
+            used,
+            since: now,
+            warned: Vec::new(),
+            exceeded: None,
+            interrupted_at: None,
+            worked_after_interrupt: false,
+        }
+    }
+
+    pub fn on_usage(&mut self, delta: &UsageCounts) {
+        self.used.counts.add(delta);
+        if self.interrupted_at.is_some() && !delta.is_zero() {
+            self.worked_after_interrupt = true;
+        }
+    }
+
+    /// The agent's output shows it working; after an interrupt, output
+    /// within `INTERRUPT_SETTLE` is the agent winding down
+    pub fn on_working(&mut self, now: Instant) {
+        if self
+            .interrupted_at
+            .is_some_and(|at| now.duration_since(at) >= INTERRUPT_SETTLE)
+        {
+            self.worked_after_interrupt = true;
+        }
+    }
+
+    /// Runtime gathered since the last call, once it is at least `min`; it
+    /// is then counted as stored and is for the caller to store
+    pub fn take_runtime(&mut self, now: Instant, min: Duration) -> Option<f64> {
+        let elapsed = now.duration_since(self.since);
+        if elapsed.is_zero() || elapsed < min {
+            return None;
+        }
+        self.since = now;
+        let secs = elapsed.as_secs_f64();
+        self.used.runtime_secs += secs;
+        Some(secs)
+    }
+
+    pub fn check(&mut self, now: Instant) -> Vec<BudgetAlert> {
+        if let Some(exceeded) = &self.exceeded {
+            // An interrupted agent that keeps going is stopped after all
+            if self.interrupted_at.is_some() && self.worked_after_interrupt {
+                self.interrupted_at = None;
+                return vec![BudgetAlert {
+                    action: Some(BudgetAction::Stop),
+                    ..exceeded.clone()
+                }];
+            }
+            return Vec::new();
+        }
+
+        let runtime = self.used.runtime_secs + now.duration_since(self.since).as_secs_f64();
+        let counts = &self.used.counts;
+        let measures = [
+            (BudgetKind::Cost, self.limits.max_cost_usd, counts.cost_usd),
+            (BudgetKind::Runtime, self.limits.max_runtime_secs.map(|s| s as f64), runtime),
+            (
+                BudgetKind::Tokens,
+                self.limits.max_tokens.map(|t| t as f64),
+                (counts.input_tokens + counts.output_tokens) as f64,
+            ),
+        ];
+        let warn_at = f64::from(self.limits.warn_percent.unwrap_or(DEFAULT_WARN_PERCENT)) / 100.0;
+
+        let mut alerts = Vec::new();
+        for (kind, limit, used) in measures {
+            let limit = match limit {
+                Some(limit) => limit,
+                None => continue,
+            };
+            if used >= limit {
+                let action = self.limits.on_exceed.unwrap_or(BudgetAction::Stop);
+                if action == BudgetAction::Interrupt {
+                    self.interrupted_at = Some(now);
+                }
+                let alert = BudgetAlert {
+                    kind,
+                    level: BudgetLevel::Exceeded,
+                    limit,
+                    used,
+                    action: Some(action),
+                };
+                self.exceeded = Some(alert.clone());
+                return vec![alert];
+            }
+            if used >= limit * warn_at && !self.warned.contains(&kind) {
+                self.warned.push(kind);
+                alerts.push(BudgetAlert {
+                    kind,
+                    level: BudgetLevel::Warning,
+                    limit,
+                    used,
+                    action: None,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+/// Add runtime to a workspace's stored usage off the calling thread
+pub fn record_runtime(app: &AppHandle, workspace_id: &str, secs: f64) {
+    let app = app.clone();
+    let workspace_id = workspace_id.to_string();
+    std::thread::spawn(move || {
+        let recorded = match app.try_state::<Mutex<WorkspaceManager>>() {
+            Some(ws_mgr) => ws_mgr
+                .lock()
+                .map_err(|e| e.to_string())
+                .and_then(|mut ws| ws.record_runtime(&workspace_id, secs)),
+            None => return,
+        };
+        if let Err(e) = recorded {
+            eprintln!("runtime not recorded for {workspace_id}: {e}");
+        }
+    });
+}
+
+/// Emit an alert and, at a hard limit, interrupt or stop the agent off the
+/// output thread and mark the workspace `BudgetExceeded`
+pub fn dispatch(app: &AppHandle, workspace_id: &str, alert: BudgetAlert) {
+    let _ = app.emit(&format!("budget-alert-{workspace_id}"), alert.clone());
+    let action = match alert.action {
+        Some(action) => action,
+        None => return,
+    };
+
+    let app = app.clone();
+    let workspace_id = workspace_id.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = enforce(&app, &workspace_id, action) {
+            eprintln!("budget not enforced for {workspace_id}: {e}");
+        }
+    });
+}
+
+fn enforce(app: &AppHandle, workspace_id: &str, action: BudgetAction) -> Result<(), String> {
+    let (ws_mgr, pty_mgr) = match (
+        app.try_state::<Mutex<WorkspaceManager>>(),
+        app.try_state::<Mutex<PtyManager>>(),
+    ) {
+        (Some(ws), Some(pty)) => (ws, pty),
+        _ => return Err("app state unavailable".into()),
+    };
+    match action {
+        BudgetAction::Interrupt => {
+            let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+            pty.write_raw(&SessionKey::agent(workspace_id), b"\x03")?;
+        }
+        BudgetAction::Stop => commands::stop_workspace_sessions(&ws_mgr, &pty_mgr, workspace_id)?,
+    }
+    let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    ws.set_state(workspace_id, WorkspaceState::BudgetExceeded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(tokens: u64, cost_usd: f64) -> UsageCounts {
+        UsageCounts {
+            input_tokens: tokens,
+            output_tokens: 0,
+            cost_usd,
+        }
+    }
+
+    fn used(tokens: u64, cost_usd: f64) -> BudgetUsage {
+        BudgetUsage {
+            counts: counts(tokens, cost_usd),
+            runtime_secs: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_warns_once_then_exceeds_once() {
+        let limits = BudgetLimits {
+            max_cost_usd: Some(1.0),
+            ..Default::default()
+        };
+        let now = Instant::now();
+        let mut watcher = BudgetWatcher::new(limits, used(0, 0.5), now);
+        assert!(watcher.check(now).is_empty());
+
+        watcher.on_usage(&counts(0, 0.35));
+        let alerts = watcher.check(now);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!((alerts[0].kind, alerts[0].level), (BudgetKind::Cost, BudgetLevel::Warning));
+        assert!(watcher.check(now).is_empty());
+
+        watcher.on_usage(&counts(0, 0.2));
+        let alerts = watcher.check(now);
+        assert_eq!(alerts[0].level, BudgetLevel::Exceeded);
+        assert_eq!(alerts[0].action, Some(BudgetAction::Stop));
+        watcher.on_usage(&counts(0, 1.0));
+        assert!(watcher.check(now).is_empty());
+    }
+
+    #[test]
+    fn test_runtime_limit_uses_configured_action() {
+        let limits = BudgetLimits {
+            max_runtime_secs: Some(100),
+            warn_percent: Some(50),
+            on_exceed: Some(BudgetAction::Interrupt),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let mut watcher = BudgetWatcher::new(limits, BudgetUsage::default(), start);
+        assert_eq!(watcher.check(start + Duration::from_secs(60))[0].level, BudgetLevel::Warning);
+        let alerts = watcher.check(start + Duration::from_secs(100));
+        assert_eq!(alerts[0].kind, BudgetKind::Runtime);
+        assert_eq!(alerts[0].action, Some(BudgetAction::Interrupt));
+    }
+
+    #[test]
+    fn test_runtime_counts_earlier_sessions() {
+        let limits = BudgetLimits {
+            max_runtime_secs: Some(100),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let earlier = BudgetUsage {
+            runtime_secs: 90.0,
+            ..Default::default()
+        };
+        let mut watcher = BudgetWatcher::new(limits, earlier, start);
+        assert!(watcher.take_runtime(start + Duration::from_secs(5), RUNTIME_RECORD_INTERVAL).is_none());
+        assert_eq!(watcher.take_runtime(start + Duration::from_secs(5), Duration::ZERO), Some(5.0));
+        assert_eq!(watcher.check(start + Duration::from_secs(9))[0].level, BudgetLevel::Warning);
+        let alerts = watcher.check(start + Duration::from_secs(10));
+        assert_eq!((alerts[0].kind, alerts[0].level), (BudgetKind::Runtime, BudgetLevel::Exceeded));
+    }
+
+    #[test]
+    fn test_interrupted_agent_that_keeps_working_is_stopped() {
+        let limits = BudgetLimits {
+            max_tokens: Some(100),
+            on_exceed: Some(BudgetAction::Interrupt),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let mut watcher = BudgetWatcher::new(limits, used(100, 0.0), start);
+        assert_eq!(watcher.check(start)[0].action, Some(BudgetAction::Interrupt));
+
+        // Winding down right after the interrupt is fine
+        watcher.on_working(start + Duration::from_secs(1));
+        assert!(watcher.check(start + Duration::from_secs(1)).is_empty());
+
+        watcher.on_working(start + INTERRUPT_SETTLE);
+        let alerts = watcher.check(start + INTERRUPT_SETTLE);
+        assert_eq!(alerts[0].level, BudgetLevel::Exceeded);
+        assert_eq!(alerts[0].action, Some(BudgetAction::Stop));
+        watcher.on_usage(&counts(10, 0.0));
+        assert!(watcher.check(start + INTERRUPT_SETTLE * 2).is_empty());
+    }
+
+    #[test]
+    fn test_exceeded_by_checks_stored_usage() {
+        let limits = BudgetLimits {
+            max_tokens: Some(1000),
+            max_runtime_secs: Some(60),
+            ..Default::default()
+        };
+        assert_eq!(exceeded_by(&limits, &used(999, 9.0)), None);
+        assert_eq!(exceeded_by(&limits, &used(1000, 0.0)), Some(BudgetKind::Tokens));
+        let ran = BudgetUsage {
+            runtime_secs: 60.0,
+            ..Default::default()
+        };
+        assert_eq!(exceeded_by(&limits, &ran), Some(BudgetKind::Runtime));
+    }
+}
@@ -1,15 +1,28 @@
 use crate::agent::AgentAdapter;
-use crate::config::{ConfigStore, RepoRef};
+use crate::agent_health::{self, AgentHealth};
+use crate::approvals::{self, ApprovalRecord, PendingApproval};
+use crate::budget::{self, BudgetUsage};
+use crate::config::{BudgetLimits, ConfigStore, PermissionMode, RepoRef};
 use crate::git_ops::{self, FileVersion, MergeStrategy};
+use crate::handoff::Handoff;
+use crate::headless::HeadlessRequest;
 use crate::keychain::KeychainState;
-use crate::pty_manager::PtyManager;
+use crate::keys::{self, KeyInput};
+use crate::pty_manager::{PtyManager, SessionKey, SpawnOutcome, SpawnRequest, TerminalInfo};
+use crate::screen::ScreenSnapshot;
+use crate::scrollback::ScrollbackSnapshot;
+use crate::search::{self, HitSource, SearchFilters, SearchHit, SearchQuery, SessionMeta};
 use crate::shortcuts::{ShortcutConfig, ShortcutState};
 use crate::snippets::{self, Snippet};
+use crate::spawn_queue::QueuedSpawnInfo;
+use crate::transcript;
+use crate::triggers::WorkspaceTrigger;
 use crate::trust::{self, TrustStatus};
+use crate::usage::UsageReport;
 use crate::workspace::{WorkspaceInfo, WorkspaceManager, WorkspaceState};
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
-use tauri::{AppHandle, Emitter, State};
+use tauri::{AppHandle, Emitter, Manager, State};
 
 /// Payload emitted when a repo requires trust verification before running setup
 #[derive(Debug, Clone, Serialize)]
@@ -27,6 +40,13 @@ struct WorkspaceContext {
     agent_name: String,
     repo_path: String,
     worktree_path: String,
+    disabled_triggers: Vec<String>,
+    /// Workspace usage so far, counted against its budget
+    usage: BudgetUsage,
+    /// Prompt for the agent to start on; only the first spawn gets one
+    initial_prompt: Option<String>,
+    /// Conversation the agent resumes
+    resume_session: Option<String>,
 }
 
 /// Extract workspace context (agent, repo_path, worktree_path) from the workspace manager.
@@ -42,36 +62,198 @@ fn get_workspace_context(
         agent_name: workspace.agent.clone(),
         repo_path: workspace.repo_path.clone(),
         worktree_path: workspace.worktree_path.clone(),
+        disabled_triggers: workspace.disabled_triggers.clone(),
+        usage: BudgetUsage::of(&workspace.usage),
+        initial_prompt: None,
+        resume_session: workspace.session_id.clone(),
     })
 }
 
-/// Resolve agent config, fetch secrets, and spawn a PTY for the workspace.
+/// Context for a workspace's first agent start, which carries the task
+/// prompt it was created with. An agent resuming its conversation already
+/// has the task.
+fn get_first_start_context(
+    ws_mgr: &Mutex<WorkspaceManager>,
+    workspace_id: &str,
+) -> Result<WorkspaceContext, String> {
+    let mut ctx = get_workspace_context(ws_mgr, workspace_id)?;
+    if ctx.resume_session.is_none() {
+        let ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+        ctx.initial_prompt = ws.get(workspace_id).and_then(|w| w.prompt.clone());
+    }
+    Ok(ctx)
+}
+
+/// An agent spawn, started or queued, and the permission mode it runs in
+struct AgentSpawn {
+    outcome: SpawnOutcome,
+    permission_mode: PermissionMode,
+}
+
+/// Resolve agent config, fetch secrets, and spawn a PTY for the workspace,
+/// queueing the spawn if every agent slot is taken.
 fn resolve_and_spawn_agent(
     config: &Mutex<ConfigStore>,
     pty_mgr: &Mutex<PtyManager>,
     keychain: &KeychainState,
     workspace_id: &str,
-    agent_name: String,
-    repo_path: &str,
-    worktree_path: &str,
+    ctx: &WorkspaceContext,
     model_override: Option<&str>,
-) -> Result<(), String> {
+    priority: i32,
+) -> Result<AgentSpawn, String> {
+    let agent_name = ctx.agent_name.clone();
     let store = config.lock().map_err(|e| e.to_string())?;
     let agent_config = store
-        .resolve_agent(repo_path, &agent_name)
+        .resolve_agent(&ctx.repo_path, &agent_name)
         .ok_or_else(|| format!("unknown agent: {agent_name}"))?;
+    if let Some(model) = model_override {
+        agent_config.check_model(&agent_name, model)?;
+    }
     let default_model = agent_config.default_model.clone();
+    let triggers = store.resolve_triggers(&ctx.repo_path);
+    let budget = store.resolve_budget(&ctx.repo_path);
+    let permission_mode = store.resolve_permission_mode(&ctx.repo_path);
+    let approval_rules = store.resolve_approval_rules(&ctx.repo_path);
     drop(store);
 
+    ensure_within_budget(&budget, &ctx.usage)?;
+    let mut adapter = AgentAdapter::new(agent_name, agent_config).with_permission_mode(permission_mode)?;
+    adapter.resume_session = ctx.resume_session.clone();
+
     let secret_env = {
         let kc = keychain.lock().map_err(|e| e.to_string())?;
         kc.env_vars().clone()
     };
 
-    let model = model_override.or(default_model.as_deref());
-    let adapter = AgentAdapter::new(agent_name, agent_config);
+    let request = SpawnRequest {
+        adapter,
+        working_dir: ctx.worktree_path.clone(),
+        model: model_override.map(str::to_string).or(default_model),
+        secret_env,
+        triggers,
+        disabled_triggers: ctx.disabled_triggers.clone(),
+        budget,
+        usage: ctx.usage,
+        prompt: ctx.initial_prompt.clone(),
+        approval_rules,
+    };
     let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
-    pty.spawn(workspace_id, &adapter, worktree_path, model, Some(&secret_env))
+    let outcome = pty.spawn_or_queue(workspace_id, request, priority)?;
+    Ok(AgentSpawn {
+        outcome,
+        permission_mode,
+    })
+}
+
+/// Refuse to start an agent for a workspace that already used up its budget
+fn ensure_within_budget(budget: &BudgetLimits, usage: &BudgetUsage) -> Result<(), String> {
+    if let Some(kind) = budget::exceeded_by(budget, usage) {
+        let limit = match kind {
+            budget::BudgetKind::Cost => "cost",
+            budget::BudgetKind::Runtime => "runtime",
+            budget::BudgetKind::Tokens => "token",
+        };
+        return Err(format!("workspace {limit} budget exceeded; raise the limit to restart it"));
+    }
+    Ok(())
+}
+
+/// Record the spawn's permission mode and move the workspace to `Queued`
+/// if its spawn is still waiting. The queue is re-checked under both locks
+/// because an exiting session may already have started it.
+fn record_spawn_outcome(
+    ws_mgr: &Mutex<WorkspaceManager>,
+    pty_mgr: &Mutex<PtyManager>,
+    workspace_id: &str,
+    spawn: &AgentSpawn,
+) -> Result<(), String> {
+    let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    ws.set_permission_mode(workspace_id, spawn.permission_mode)?;
+    if spawn.outcome == SpawnOutcome::Started {
+        return Ok(());
+    }
+    let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    if pty.queue_position(workspace_id).is_some() {
+        ws.set_state(workspace_id, WorkspaceState::Queued)?;
+    }
+    Ok(())
+}
+
+/// Reconnect to agent sessions that outlived the previous app run (session
+/// daemon only) and mark workspaces whose agent is gone as stopped.
+/// Sessions of unknown workspaces, and sessions that already exited, are
+/// released from the daemon.
+pub fn restore_sessions(
+    config: &Mutex<ConfigStore>,
+    ws_mgr: &Mutex<WorkspaceManager>,
+    pty_mgr: &Mutex<PtyManager>,
+) -> Result<(), String> {
+    #[cfg(unix)]
+    let live_agents = {
+        let sessions = pty_mgr.lock().map_err(|e| e.to_string())?.daemon_sessions();
+        let mut live_agents = Vec::new();
+        for session in sessions {
+            let workspace = ws_mgr
+                .lock()
+                .map_err(|e| e.to_string())?
+                .get(&session.key.workspace_id)
+                .cloned();
+            let workspace = match workspace {
+                Some(ws) if session.alive => ws,
+                _ => {
+                    pty_mgr
+                        .lock()
+                        .map_err(|e| e.to_string())?
+                        .release_daemon_session(&session.key);
+                    continue;
+                }
+            };
+
+            let (adapter, triggers, budget, approval_rules) = if session.key.is_agent() {
+                let store = config.lock().map_err(|e| e.to_string())?;
+                // The agent keeps the permission mode it was started with
+                let adapter = store
+                    .resolve_agent(&workspace.repo_path, &workspace.agent)
+                    .map(|cfg| {
+                        let mut adapter = AgentAdapter::new(workspace.agent.clone(), cfg);
+                        adapter.permission_mode = workspace.permission_mode.unwrap_or_default();
+                        adapter
+                    });
+                (
+                    adapter,
+                    store.resolve_triggers(&workspace.repo_path),
+                    store.resolve_budget(&workspace.repo_path),
+                    store.resolve_approval_rules(&workspace.repo_path),
+                )
+            } else {
+                (None, Vec::new(), Default::default(), Vec::new())
+            };
+            let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+            if let Some(adapter) = &adapter {
+                pty.set_triggers(&workspace.id, &triggers, &workspace.disabled_triggers);
+                pty.set_budget(&workspace.id, &budget, BudgetUsage::of(&workspace.usage));
+                pty.set_approvals(&workspace.id, adapter, &approval_rules);
+            }
+            match pty.reattach(&session, adapter.as_ref(), &workspace.worktree_path) {
+                Ok(()) if session.key.is_agent() => live_agents.push(workspace.id),
+                Ok(()) => {}
+                Err(e) => {
+                    eprintln!("failed to reattach {}: {e}", session.key.event_id());
+                    pty.release_daemon_session(&session.key);
+                }
+            }
+        }
+        live_agents
+    };
+    #[cfg(not(unix))]
+    let live_agents: Vec<String> = {
+        let _ = (config, pty_mgr);
+        Vec::new()
+    };
+
+    let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    ws.reconcile_sessions(&live_agents);
+    Ok(())
 }
 
 /// Canonicalize a repo path and verify it is a registered repository.
@@ -163,6 +345,13 @@ pub struct CreateWorkspaceArgs {
     pub repo_alias: String,
     pub branch: String,
     pub agent: Option<String>,
+    /// Initial terminal rows/cols of the pane the agent will render into
+    pub rows: Option<u16>,
+    pub cols: Option<u16>,
+    /// Spawn queue priority used when all agent slots are taken (higher first)
+    pub priority: Option<i32>,
+    /// Task the agent starts on
+    pub prompt: Option<String>,
 }
 
 #[tauri::command]
@@ -199,19 +388,37 @@ pub fn create_workspace(
         .get(&repo_path)
         .and_then(|rc| rc.setup_script.clone());
 
+    let agent_config = store
+        .resolve_agent(&repo_path, &agent_name)
+        .ok_or_else(|| format!("unknown agent: {agent_name}"))?;
+    let permission_mode = store.resolve_permission_mode(&repo_path);
+
     drop(store); // Release config lock
 
+    // Fail before creating anything if the agent can't be started
+    let adapter = AgentAdapter::new(agent_name.clone(), agent_config).with_permission_mode(permission_mode)?;
+    let secrets = keychain.lock().map_err(|e| e.to_string())?.env_vars().clone();
+    agent_health::check(&adapter, &secrets, false).ensure_ready()?;
+
     // Create workspace (git worktree) — workspace.rs now canonicalizes internally
     let mut ws_manager = ws_mgr.lock().map_err(|e| e.to_string())?;
+    let prompt = args.prompt.filter(|p| !p.trim().is_empty());
     let ws = ws_manager.create(
         &repo_path,
         &args.repo_alias,
         &args.branch,
         &agent_name,
         &worktree_dir,
+        prompt.as_deref(),
     )?;
     drop(ws_manager);
 
+    // Record the initial terminal size so the PTY opens at the pane's size
+    if let (Some(rows), Some(cols)) = (args.rows, args.cols) {
+        let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+        pty.resize(&SessionKey::agent(&ws.id), rows, cols)?;
+    }
+
     // Trust check for setup scripts
     if let Some(ref script) = setup_script {
         let trust_status = trust::check_trust(&repo_path).unwrap_or(TrustStatus::Untrusted);
@@ -240,13 +447,32 @@ pub fn create_workspace(
         }
     }
 
-    // Spawn agent PTY
-    resolve_and_spawn_agent(
+    // Spawn agent PTY (or queue it when at max_concurrent_agents)
+    let ctx = WorkspaceContext {
+        agent_name,
+        repo_path,
+        worktree_path: ws.worktree_path.clone(),
+        disabled_triggers: vec![],
+        usage: BudgetUsage::default(),
+        initial_prompt: prompt,
+        resume_session: None,
+    };
+    let spawn = resolve_and_spawn_agent(
         &config, &pty_mgr, &keychain,
-        &ws.id, agent_name, &repo_path, &ws.worktree_path, None,
+        &ws.id, &ctx, None,
+        args.priority.unwrap_or(0),
     )?;
+    record_spawn_outcome(&ws_mgr, &pty_mgr, &ws.id, &spawn)?;
 
-    Ok(ws)
+    let ws_manager = ws_mgr.lock().map_err(|e| e.to_string())?;
+    let mut info = ws_manager
+        .get(&ws.id)
+        .cloned()
+        .ok_or_else(|| format!("workspace not found: {}", ws.id))?;
+    if let SpawnOutcome::Queued { position } = spawn.outcome {
+        info.queue_position = Some(position);
+    }
+    Ok(info)
 }
 
 /// Helper: run a setup script in a worktree directory
@@ -273,7 +499,7 @@ pub fn run_setup_and_start_agent(
     keychain: State<'_, KeychainState>,
     workspace_id: String,
 ) -> Result<(), String> {
-    let ctx = get_workspace_context(&ws_mgr, &workspace_id)?;
+    let ctx = get_first_start_context(&ws_mgr, &workspace_id)?;
 
     // Get setup script
     let setup_script = {
@@ -288,10 +514,11 @@ pub fn run_setup_and_start_agent(
         run_setup_script(&script, &ctx.worktree_path);
     }
 
-    resolve_and_spawn_agent(
+    let spawn = resolve_and_spawn_agent(
         &config, &pty_mgr, &keychain,
-        &workspace_id, ctx.agent_name, &ctx.repo_path, &ctx.worktree_path, None,
-    )
+        &workspace_id, &ctx, None, 0,
+    )?;
+    record_spawn_outcome(&ws_mgr, &pty_mgr, &workspace_id, &spawn)
 }
 
 /// Start agent without running setup script (called when user denies trust)
@@ -303,12 +530,70 @@ pub fn start_agent_no_setup(
     keychain: State<'_, KeychainState>,
     workspace_id: String,
 ) -> Result<(), String> {
-    let ctx = get_workspace_context(&ws_mgr, &workspace_id)?;
+    let ctx = get_first_start_context(&ws_mgr, &workspace_id)?;
 
-    resolve_and_spawn_agent(
+    let spawn = resolve_and_spawn_agent(
         &config, &pty_mgr, &keychain,
-        &workspace_id, ctx.agent_name, &ctx.repo_path, &ctx.worktree_path, None,
-    )
+        &workspace_id, &ctx, None, 0,
+    )?;
+    record_spawn_outcome(&ws_mgr, &pty_mgr, &workspace_id, &spawn)
+}
+
+/// Run the workspace's agent once on `prompt` without a terminal. Its
+/// events stream as `agent-event-{id}` and the final result is stored on
+/// the workspace; `stop_workspace` kills the run.
+#[tauri::command]
+pub fn run_headless(
+    config: State<'_, Mutex<ConfigStore>>,
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    keychain: State<'_, KeychainState>,
+    workspace_id: String,
+    prompt: String,
+    model: Option<String>,
+) -> Result<(), String> {
+    if prompt.trim().is_empty() {
+        return Err("prompt is empty".into());
+    }
+    let ctx = get_workspace_context(&ws_mgr, &workspace_id)?;
+
+    let store = config.lock().map_err(|e| e.to_string())?;
+    let agent_config = store
+        .resolve_agent(&ctx.repo_path, &ctx.agent_name)
+        .ok_or_else(|| format!("unknown agent: {}", ctx.agent_name))?;
+    let budget = store.resolve_budget(&ctx.repo_path);
+    let permission_mode = store.resolve_permission_mode(&ctx.repo_path);
+    drop(store);
+    if let Some(model) = &model {
+        agent_config.check_model(&ctx.agent_name, model)?;
+    }
+    ensure_within_budget(&budget, &ctx.usage)?;
+
+    let secret_env = keychain.lock().map_err(|e| e.to_string())?.env_vars().clone();
+    // Headless mode args are checked when its command is built
+    let mut adapter = AgentAdapter::new(ctx.agent_name.clone(), agent_config);
+    adapter.permission_mode = permission_mode;
+    agent_health::check(&adapter, &secret_env, false).ensure_ready()?;
+
+    let request = HeadlessRequest {
+        model: model.or_else(|| adapter.config.default_model.clone()),
+        adapter,
+        working_dir: ctx.worktree_path.clone(),
+        prompt,
+        secret_env,
+        budget,
+        usage: ctx.usage,
+    };
+
+    // Hold the workspace lock so a run that ends at once can't be marked
+    // running after its result is stored
+    let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    pty_mgr
+        .lock()
+        .map_err(|e| e.to_string())?
+        .run_headless(&workspace_id, request)?;
+    ws.set_permission_mode(&workspace_id, permission_mode)?;
+    ws.set_state(&workspace_id, WorkspaceState::Running)
 }
 
 #[tauri::command]
@@ -317,14 +602,30 @@ pub fn stop_workspace(
     pty_mgr: State<'_, Mutex<PtyManager>>,
     workspace_id: String,
 ) -> Result<(), String> {
-    // Kill PTY first
+    stop_workspace_sessions(&ws_mgr, &pty_mgr, &workspace_id)
+}
+
+/// Kill a workspace's agent and extra terminals and mark it stopped
+pub fn stop_workspace_sessions(
+    ws_mgr: &Mutex<WorkspaceManager>,
+    pty_mgr: &Mutex<PtyManager>,
+    workspace_id: &str,
+) -> Result<(), String> {
+    // Kill the agent and any extra terminals first. A stopped agent doesn't
+    // report its conversation on exit, so it is taken here.
     let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
-    pty.kill(&workspace_id)?;
+    let session_id = pty.session_id(workspace_id);
+    pty.kill(workspace_id)?;
+    pty.close_terminals(workspace_id);
     drop(pty);
 
     // Update workspace state
     let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
-    ws.stop(&workspace_id)
+    if let Some(id) = session_id {
+        let agent = ws.get(workspace_id).map(|w| w.agent.clone()).unwrap_or_default();
+        ws.set_session_id(workspace_id, &agent, &id)?;
+    }
+    ws.stop(workspace_id)
 }
 
 #[tauri::command]
@@ -336,6 +637,7 @@ pub fn remove_workspace(
     // Ensure PTY is killed
     let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
     let _ = pty.kill(&workspace_id);
+    pty.forget(&workspace_id);
     drop(pty);
 
     let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
@@ -345,10 +647,57 @@ pub fn remove_workspace(
 #[tauri::command]
 pub fn list_workspaces(
     ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
     repo_path: Option<String>,
 ) -> Result<Vec<WorkspaceInfo>, String> {
     let ws = ws_mgr.lock().map_err(|e| e.to_string())?;
-    Ok(ws.list(repo_path.as_deref()))
+    let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    Ok(ws
+        .list(repo_path.as_deref())
+        .into_iter()
+        .map(|mut info| {
+            info.activity = pty.activity(&info.id);
+            info.queue_position = pty.queue_position(&info.id);
+            info
+        })
+        .collect())
+}
+
+// ── Spawn Queue Commands ──
+
+#[tauri::command]
+pub fn list_spawn_queue(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+) -> Result<Vec<QueuedSpawnInfo>, String> {
+    let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    Ok(pty.queued_spawns())
+}
+
+#[tauri::command]
+pub fn cancel_queued_spawn(
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+) -> Result<(), String> {
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    if !pty.cancel_queued(&workspace_id) {
+        return Err(format!("spawn not queued: {workspace_id}"));
+    }
+    drop(pty);
+
+    let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    ws.set_state(&workspace_id, WorkspaceState::Stopped)
+}
+
+/// Move a queued spawn to a 1-based position; returns the position it landed at
+#[tauri::command]
+pub fn move_queued_spawn(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    position: usize,
+) -> Result<usize, String> {
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.move_queued(&workspace_id, position)
 }
 
 // ── Agent Commands ──
@@ -363,6 +712,344 @@ pub fn send_to_agent(
     pty.write(&workspace_id, &message)
 }
 
+/// Send named keys, literal text or bracketed pastes without the newline
+/// `send_to_agent` appends (e.g. ctrl-c to interrupt, arrows in menus).
+/// Targets the agent unless `terminal_id` names an extra terminal.
+#[tauri::command]
+pub fn send_keys(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    terminal_id: Option<String>,
+    keys: Vec<KeyInput>,
+) -> Result<(), String> {
+    let data = keys::encode(&keys)?;
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.write_raw(&SessionKey::new(&workspace_id, terminal_id.as_deref()), &data)
+}
+
+#[tauri::command]
+pub fn write_raw(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    terminal_id: Option<String>,
+    data: Vec<u8>,
+) -> Result<(), String> {
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.write_raw(&SessionKey::new(&workspace_id, terminal_id.as_deref()), &data)
+}
+
+#[tauri::command]
+pub fn resize_pty(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    terminal_id: Option<String>,
+    rows: u16,
+    cols: u16,
+) -> Result<(), String> {
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.resize(&SessionKey::new(&workspace_id, terminal_id.as_deref()), rows, cols)
+}
+
+/// Retained output for rebuilding a terminal after a reload or remount.
+/// Pass the next sequence number already seen to resume without duplicates.
+#[tauri::command]
+pub fn get_pty_scrollback(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    terminal_id: Option<String>,
+    from_seq: Option<u64>,
+) -> Result<ScrollbackSnapshot, String> {
+    let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.scrollback(&SessionKey::new(&workspace_id, terminal_id.as_deref()), from_seq)
+}
+
+/// Visible screen of a session as emulated on the Rust side: text lines
+/// plus, unless `include_cells` is false, the cell grid with attributes
+#[tauri::command]
+pub fn get_screen_snapshot(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    terminal_id: Option<String>,
+    include_cells: Option<bool>,
+) -> Result<ScreenSnapshot, String> {
+    let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.screen_snapshot(
+        &SessionKey::new(&workspace_id, terminal_id.as_deref()),
+        include_cells.unwrap_or(true),
+    )
+}
+
+// ── Terminal Commands ──
+
+/// Open a shell (or run `command` through it) in the workspace's worktree,
+/// with the same Keychain secrets as the agent
+#[tauri::command]
+pub fn open_terminal(
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    keychain: State<'_, KeychainState>,
+    workspace_id: String,
+    command: Option<String>,
+    rows: Option<u16>,
+    cols: Option<u16>,
+) -> Result<TerminalInfo, String> {
+    let ctx = get_workspace_context(&ws_mgr, &workspace_id)?;
+    let secret_env = {
+        let kc = keychain.lock().map_err(|e| e.to_string())?;
+        kc.env_vars().clone()
+    };
+
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    let info = pty.open_terminal(
+        &workspace_id,
+        &ctx.worktree_path,
+        command.as_deref(),
+        Some(&secret_env),
+    )?;
+    if let (Some(rows), Some(cols)) = (rows, cols) {
+        pty.resize(&SessionKey::new(&workspace_id, Some(&info.terminal_id)), rows, cols)?;
+    }
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn list_terminals(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+) -> Result<Vec<TerminalInfo>, String> {
+    let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    Ok(pty.list_terminals(&workspace_id))
+}
+
+#[tauri::command]
+pub fn close_terminal(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    terminal_id: String,
+) -> Result<(), String> {
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.close_terminal(&workspace_id, &terminal_id)
+}
+
+// ── Transcript Commands ──
+
+/// Everything a workspace's agents printed, as plain text with ANSI stripped.
+/// Transcripts are kept after the workspace is removed.
+#[tauri::command]
+pub fn get_transcript(workspace_id: String) -> Result<String, String> {
+    let path = transcript::transcript_path(&transcript::transcripts_dir(), &workspace_id)?;
+    let entries = transcript::read_entries(&path)?;
+    Ok(transcript::to_plain_text(&entries))
+}
+
+/// Write a workspace's transcript to `dest_path` as an asciicast v2 file
+#[tauri::command]
+pub fn export_transcript_asciicast(workspace_id: String, dest_path: String) -> Result<(), String> {
+    let path = transcript::transcript_path(&transcript::transcripts_dir(), &workspace_id)?;
+    let cast = transcript::to_asciicast(&transcript::read_entries(&path)?)?;
+    std::fs::write(&dest_path, cast).map_err(|e| format!("write error: {e}"))
+}
+
+// ── Trigger Commands ──
+
+/// Output triggers that apply to a workspace, with whether each is active
+#[tauri::command]
+pub fn list_triggers(
+    config: State<'_, Mutex<ConfigStore>>,
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    workspace_id: String,
+) -> Result<Vec<WorkspaceTrigger>, String> {
+    let ctx = get_workspace_context(&ws_mgr, &workspace_id)?;
+    let store = config.lock().map_err(|e| e.to_string())?;
+    Ok(store
+        .resolve_triggers(&ctx.repo_path)
+        .into_iter()
+        .map(|rule| WorkspaceTrigger {
+            active: rule.enabled && !ctx.disabled_triggers.contains(&rule.name),
+            rule,
+        })
+        .collect())
+}
+
+/// Switch a trigger on or off for one workspace, including its running agent
+#[tauri::command]
+pub fn set_trigger_enabled(
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    trigger: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    ws.set_trigger_enabled(&workspace_id, &trigger, enabled)?;
+    let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    pty.set_trigger_enabled(&workspace_id, &trigger, enabled);
+    Ok(())
+}
+
+// ── Usage Commands ──
+
+/// Tokens and cost of one workspace, by model and by day
+#[tauri::command]
+pub fn get_workspace_usage(
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    workspace_id: String,
+) -> Result<UsageReport, String> {
+    let ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    let info = ws
+        .get(&workspace_id)
+        .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+    Ok(UsageReport::collect([(info.id.as_str(), &info.usage)], None, None))
+}
+
+/// Usage totals across workspaces, optionally for one repo and a day range
+/// (`YYYY-MM-DD`, inclusive)
+#[tauri::command]
+pub fn get_usage_summary(
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    repo_path: Option<String>,
+    from_day: Option<String>,
+    to_day: Option<String>,
+) -> Result<UsageReport, String> {
+    let ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+    let workspaces = ws.list(repo_path.as_deref());
+    Ok(UsageReport::collect(
+        workspaces.iter().map(|info| (info.id.as_str(), &info.usage)),
+        from_day.as_deref(),
+        to_day.as_deref(),
+    ))
+}
+
+// ── Approval Commands ──
+
+/// Decisions returned by `get_approval_log` unless a limit is given
+const APPROVAL_LOG_LIMIT: usize = 200;
+
+/// Permission prompts waiting for the user
+#[tauri::command]
+pub fn list_pending_approvals(
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+) -> Result<Vec<PendingApproval>, String> {
+    let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+    Ok(pty.pending_approvals())
+}
+
+/// Answer a permission prompt the rules left to the user
+#[tauri::command]
+pub fn answer_approval(
+    app: AppHandle,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    workspace_id: String,
+    approval_id: String,
+    approve: bool,
+) -> Result<(), String> {
+    let record = pty_mgr
+        .lock()
+        .map_err(|e| e.to_string())?
+        .answer_approval(&workspace_id, &approval_id, approve)?;
+    approvals::record_decision(&app, record);
+    Ok(())
+}
+
+/// Logged approval decisions, newest first
+#[tauri::command]
+pub fn get_approval_log(
+    workspace_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<ApprovalRecord>, String> {
+    approvals::read_log(
+        &approvals::log_path(),
+        workspace_id.as_deref(),
+        limit.unwrap_or(APPROVAL_LOG_LIMIT),
+    )
+}
+
+// ── Search Commands ──
+
+/// Search agent transcripts (live and archived) and the output of running
+/// sessions that aren't recorded. Hits are returned newest first.
+#[tauri::command]
+pub fn search_sessions(
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    query: String,
+    filters: Option<SearchFilters>,
+) -> Result<Vec<SearchHit>, String> {
+    let query = SearchQuery::new(&query, filters.unwrap_or_default())?;
+
+    let workspaces: std::collections::HashMap<String, WorkspaceInfo> = {
+        let ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+        ws.list(None).into_iter().map(|w| (w.id.clone(), w)).collect()
+    };
+    let live = {
+        let pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+        pty.indexed_lines()
+    };
+
+    let mut hits = Vec::new();
+    for (key, lines) in live {
+        let workspace = workspaces.get(&key.workspace_id);
+        let meta = SessionMeta {
+            workspace_id: key.workspace_id.clone(),
+            terminal_id: (!key.is_agent()).then(|| key.terminal_id.clone()),
+            agent: workspace.filter(|_| key.is_agent()).map(|w| w.agent.clone()),
+            repo_path: workspace.map(|w| w.repo_path.clone()),
+            working_dir: workspace.map(|w| w.worktree_path.clone()),
+        };
+        if query.accepts(&meta) {
+            query.search_lines(&meta, &lines, HitSource::Live, &mut hits);
+        }
+    }
+
+    query.search_transcripts(
+        &transcript::transcripts_dir(),
+        |id| {
+            workspaces.get(id).map(|w| SessionMeta {
+                workspace_id: w.id.clone(),
+                repo_path: Some(w.repo_path.clone()),
+                ..Default::default()
+            })
+        },
+        &mut hits,
+    );
+
+    Ok(search::finish_hits(hits, query.limit()))
+}
+
+/// Report for every configured agent whether its CLI is on PATH, its
+/// version, and which required credentials are missing
+#[tauri::command]
+pub async fn check_agents(
+    config: State<'_, Mutex<ConfigStore>>,
+    keychain: State<'_, KeychainState>,
+    repo_path: Option<String>,
+) -> Result<Vec<AgentHealth>, String> {
+    let store = config.lock().map_err(|e| e.to_string())?;
+    let mut names: Vec<String> = store.global.agents.keys().cloned().collect();
+    names.sort();
+    let adapters: Vec<AgentAdapter> = names
+        .into_iter()
+        .filter_map(|name| {
+            let cfg = store.resolve_agent(repo_path.as_deref().unwrap_or_default(), &name)?;
+            Some(AgentAdapter::new(name, cfg))
+        })
+        .collect();
+    drop(store);
+    let secrets = keychain.lock().map_err(|e| e.to_string())?.env_vars().clone();
+
+    // Version probes run side by side so one slow CLI doesn't stall the rest
+    Ok(std::thread::scope(|scope| {
+        let checks: Vec<_> = adapters
+            .iter()
+            .map(|adapter| scope.spawn(|| agent_health::check(adapter, &secrets, true)))
+            .collect();
+        checks
+            .into_iter()
+            .filter_map(|check| check.join().ok())
+            .collect()
+    }))
+}
+
 #[tauri::command]
 pub fn get_agents(
     config: State<'_, Mutex<ConfigStore>>,
@@ -467,7 +1154,7 @@ pub fn merge_workspace(
         .get(&workspace_id)
         .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
 
-    if workspace.state == WorkspaceState::Running {
+    if matches!(workspace.state, WorkspaceState::Running | WorkspaceState::Queued) {
         return Err("cannot merge a running workspace — stop it first".into());
     }
 
@@ -511,6 +1198,7 @@ pub fn discard_workspace(
     {
         let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
         let _ = pty.kill(&workspace_id);
+        pty.forget(&workspace_id);
     }
 
     // Stop workspace and extract context
@@ -552,7 +1240,19 @@ pub async fn run_snippet_v2(
     workspace_id: String,
     name: String,
 ) -> Result<(), String> {
-    let ctx = get_workspace_context(&ws_mgr, &workspace_id)?;
+    start_snippet(&app, &ws_mgr, &workspace_id, &name)
+}
+
+/// Run a snippet in a workspace's worktree, streaming its output as
+/// `snippet-output-*` events
+pub fn start_snippet(
+    app: &AppHandle,
+    ws_mgr: &Mutex<WorkspaceManager>,
+    workspace_id: &str,
+    name: &str,
+) -> Result<(), String> {
+    let name = name.to_string();
+    let ctx = get_workspace_context(ws_mgr, workspace_id)?;
     let worktree_path = ctx.worktree_path;
     let repo_path = ctx.repo_path;
 
@@ -576,7 +1276,7 @@ pub async fn run_snippet_v2(
 
     // Capture the command and re-resolve right before execution to prevent TOCTOU
     let command = snippet.command.clone();
-    let ws_id = workspace_id.clone();
+    let ws_id = workspace_id.to_string();
     let repo_path_clone = repo_path.clone();
 
     // Emit separator header
@@ -770,6 +1470,85 @@ pub fn save_shortcuts(
 
 // ── Model Switch Command ──
 
+/// Check an agent exists and, when a model is picked, that it may run it.
+/// Done before the running agent is killed, so a bad pick leaves it be.
+fn ensure_known_model(
+    config: &Mutex<ConfigStore>,
+    repo_path: &str,
+    agent: &str,
+    model: &str,
+) -> Result<(), String> {
+    config
+        .lock()
+        .map_err(|e| e.to_string())?
+        .resolve_agent(repo_path, agent)
+        .ok_or_else(|| format!("unknown agent: {agent}"))?
+        .check_model(agent, model)
+}
+
+/// Kill a workspace's agent and start it again, resuming its conversation
+/// when the agent can
+fn respawn_agent(
+    config: &Mutex<ConfigStore>,
+    ws_mgr: &Mutex<WorkspaceManager>,
+    pty_mgr: &Mutex<PtyManager>,
+    keychain: &KeychainState,
+    workspace_id: &str,
+    model: Option<&str>,
+) -> Result<(), String> {
+    let mut ctx = get_workspace_context(ws_mgr, workspace_id)?;
+    if let Some(model) = model {
+        ensure_known_model(config, &ctx.repo_path, &ctx.agent_name, model)?;
+    }
+
+    // Kill existing PTY before respawning; its conversation id is taken
+    // first so the new session can pick up where it left off
+    let session_id = {
+        let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+        let session_id = pty.session_id(workspace_id);
+        pty.kill(workspace_id)?;
+        session_id
+    };
+    if let Some(id) = session_id {
+        ws_mgr
+            .lock()
+            .map_err(|e| e.to_string())?
+            .set_session_id(workspace_id, &ctx.agent_name, &id)?;
+        ctx.resume_session = Some(id);
+    }
+
+    let spawn = resolve_and_spawn_agent(
+        config, pty_mgr, keychain,
+        workspace_id, &ctx, model, 0,
+    )?;
+    record_spawn_outcome(ws_mgr, pty_mgr, workspace_id, &spawn)
+}
+
+/// Start a workspace's agent afresh, with its task, after it failed to
+/// resume the stored conversation
+pub fn restart_without_resume(app: &AppHandle, workspace_id: &str) -> Result<(), String> {
+    let (config, ws_mgr, pty_mgr, keychain) = match (
+        app.try_state::<Mutex<ConfigStore>>(),
+        app.try_state::<Mutex<WorkspaceManager>>(),
+        app.try_state::<Mutex<PtyManager>>(),
+        app.try_state::<KeychainState>(),
+    ) {
+        (Some(config), Some(ws), Some(pty), Some(keychain)) => (config, ws, pty, keychain),
+        _ => return Err("app state unavailable".into()),
+    };
+    ws_mgr
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clear_session_id(workspace_id)?;
+
+    let ctx = get_first_start_context(&ws_mgr, workspace_id)?;
+    let spawn = resolve_and_spawn_agent(
+        &config, &pty_mgr, &keychain,
+        workspace_id, &ctx, None, 0,
+    )?;
+    record_spawn_outcome(&ws_mgr, &pty_mgr, workspace_id, &spawn)
+}
+
 #[tauri::command]
 pub fn switch_agent_model(
     config: State<'_, Mutex<ConfigStore>>,
@@ -779,16 +1558,116 @@ pub fn switch_agent_model(
     workspace_id: String,
     model: String,
 ) -> Result<(), String> {
-    let ctx = get_workspace_context(&ws_mgr, &workspace_id)?;
+    respawn_agent(&config, &ws_mgr, &pty_mgr, &keychain, &workspace_id, Some(&model))
+}
 
-    // Kill existing PTY before respawning
-    {
-        let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
-        pty.kill(&workspace_id)?;
+/// Restart a workspace's agent with its default model
+#[tauri::command]
+pub fn restart_agent(
+    config: State<'_, Mutex<ConfigStore>>,
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    keychain: State<'_, KeychainState>,
+    workspace_id: String,
+) -> Result<(), String> {
+    respawn_agent(&config, &ws_mgr, &pty_mgr, &keychain, &workspace_id, None)
+}
+
+// ── Agent Switch Command ──
+
+#[derive(Deserialize)]
+pub struct SwitchAgentArgs {
+    pub workspace_id: String,
+    pub agent: String,
+    pub model: Option<String>,
+    /// Leave a summary of the work so far (see `handoff`) for the new agent
+    #[serde(default)]
+    pub handoff: bool,
+}
+
+/// Hand a workspace to another agent: the current one is stopped and the
+/// new one started in the same worktree, resuming its own conversation if
+/// it worked here before
+#[tauri::command]
+pub fn switch_agent(
+    config: State<'_, Mutex<ConfigStore>>,
+    ws_mgr: State<'_, Mutex<WorkspaceManager>>,
+    pty_mgr: State<'_, Mutex<PtyManager>>,
+    keychain: State<'_, KeychainState>,
+    args: SwitchAgentArgs,
+) -> Result<(), String> {
+    let workspace_id = args.workspace_id.as_str();
+    let mut ctx = get_workspace_context(&ws_mgr, workspace_id)?;
+    if ctx.agent_name == args.agent {
+        return Err(format!("workspace already runs {}", args.agent));
+    }
+
+    // Fail before touching the running agent if the new one can't be started
+    let store = config.lock().map_err(|e| e.to_string())?;
+    let agent_config = store
+        .resolve_agent(&ctx.repo_path, &args.agent)
+        .ok_or_else(|| format!("unknown agent: {}", args.agent))?;
+    if let Some(model) = &args.model {
+        agent_config.check_model(&args.agent, model)?;
     }
+    let permission_mode = store.resolve_permission_mode(&ctx.repo_path);
+    let budget = store.resolve_budget(&ctx.repo_path);
+    drop(store);
+    ensure_within_budget(&budget, &ctx.usage)?;
+    let adapter = AgentAdapter::new(args.agent.clone(), agent_config).with_permission_mode(permission_mode)?;
+    let secrets = keychain.lock().map_err(|e| e.to_string())?.env_vars().clone();
+    agent_health::check(&adapter, &secrets, false).ensure_ready()?;
+
+    // Written while the old agent is still around, so a failure leaves
+    // the workspace as it was
+    let handoff = if args.handoff {
+        let (branch, task) = {
+            let ws = ws_mgr.lock().map_err(|e| e.to_string())?;
+            let workspace = ws
+                .get(workspace_id)
+                .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+            (workspace.branch.clone(), workspace.prompt.clone())
+        };
+        let handoff = Handoff::collect(
+            workspace_id,
+            &ctx.agent_name,
+            &branch,
+            task.as_deref(),
+            &ctx.repo_path,
+            &ctx.worktree_path,
+        )?;
+        handoff.write(&ctx.worktree_path)?;
+        Some(handoff)
+    } else {
+        None
+    };
 
-    resolve_and_spawn_agent(
+    let session_id = {
+        let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+        let session_id = pty.session_id(workspace_id);
+        pty.kill(workspace_id)?;
+        session_id
+    };
+    ctx.resume_session = ws_mgr
+        .lock()
+        .map_err(|e| e.to_string())?
+        .switch_agent(workspace_id, &args.agent, session_id, transcript::now_ms())?;
+    ctx.agent_name = args.agent.clone();
+    ctx.initial_prompt = handoff.map(|h| h.prompt());
+
+    let spawn = match resolve_and_spawn_agent(
         &config, &pty_mgr, &keychain,
-        &workspace_id, ctx.agent_name, &ctx.repo_path, &ctx.worktree_path, Some(&model),
-    )
+        workspace_id, &ctx, args.model.as_deref(), 0,
+    ) {
+        Ok(spawn) => spawn,
+        Err(e) => {
+            // Keep the previous agent on record so it can be restarted
+            ws_mgr
+                .lock()
+                .map_err(|e| e.to_string())?
+                .revert_switch_agent(workspace_id)?;
+            return Err(e);
+        }
+    };
+    record_spawn_outcome(&ws_mgr, &pty_mgr, workspace_id, &spawn)
 }
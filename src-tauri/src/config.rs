@@ -1,3 +1,5 @@
+use crate::agent;
+use crate::usage::UsagePattern;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -10,6 +12,14 @@ pub struct GlobalConfig {
     pub agents: HashMap<String, AgentConfig>,
     pub defaults: Defaults,
     pub repositories: Vec<RepoRef>,
+    /// Output triggers for every repo; a repo trigger with the same name
+    /// replaces the global one
+    #[serde(default)]
+    pub triggers: Vec<TriggerRule>,
+    /// Rules answering agent permission prompts in `ask` mode, checked
+    /// after the repo's own rules
+    #[serde(default)]
+    pub approval_rules: Vec<ApprovalRule>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,12 +29,458 @@ pub struct AgentConfig {
     pub args: Vec<String>,
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Models the agent can be switched to, as bare ids or full entries
     #[serde(default)]
-    pub models: Vec<String>,
+    pub models: Vec<ModelEntry>,
     #[serde(default)]
     pub default_model: Option<String>,
     #[serde(default)]
     pub model_flag: Option<String>,
+    /// Accept models that aren't listed in `models`
+    #[serde(default)]
+    pub free_form_models: bool,
+    /// Regexes matching the agent's idle input prompt (ANSI-stripped output)
+    #[serde(default)]
+    pub prompt_patterns: Vec<String>,
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// Patterns reading token usage and cost from the agent's output;
+    /// built-in ones are used for claude, codex and gemini when unset
+    #[serde(default)]
+    pub usage_patterns: Vec<UsagePattern>,
+    /// Structured output whose events carry usage
+    #[serde(default)]
+    pub usage_format: Option<EventFormat>,
+    /// How the agent's CLI is driven; unset parts fall back to the built-in
+    /// descriptor of claude, codex and gemini
+    #[serde(flatten)]
+    pub descriptor: AgentDescriptor,
+}
+
+impl AgentConfig {
+    pub fn model(&self, id: &str) -> Option<&ModelEntry> {
+        self.models.iter().find(|m| m.id == id)
+    }
+
+    /// Refuse a model the agent doesn't list (its default counts as listed)
+    /// unless it takes free-form models
+    pub fn check_model(&self, agent: &str, id: &str) -> Result<(), String> {
+        if self.free_form_models || self.model(id).is_some() || self.default_model.as_deref() == Some(id) {
+            return Ok(());
+        }
+        let known: Vec<&str> = self.models.iter().map(|m| m.id.as_str()).collect();
+        Err(match known.is_empty() {
+            true => format!("unknown model {id}: agent {agent} lists no models"),
+            false => format!("unknown model {id} for agent {agent}; known: {}", known.join(", ")),
+        })
+    }
+}
+
+/// A model an agent runs, with what it needs beyond the model flag. In
+/// config a bare id stands for an entry with only the id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(from = "ModelSpec")]
+pub struct ModelEntry {
+    pub id: String,
+    /// Name shown instead of the id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Args added after the model flag, e.g. a reasoning effort
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Prices charging the model's tokens when the agent reports no cost
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<ModelCost>,
+}
+
+/// USD per million tokens
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelCost {
+    #[serde(default)]
+    pub input_per_mtok: f64,
+    #[serde(default)]
+    pub output_per_mtok: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ModelSpec {
+    Id(String),
+    Entry {
+        id: String,
+        #[serde(default)]
+        display_name: Option<String>,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+        #[serde(default)]
+        cost: Option<ModelCost>,
+    },
+}
+
+impl From<ModelSpec> for ModelEntry {
+    fn from(spec: ModelSpec) -> Self {
+        match spec {
+            ModelSpec::Id(id) => id.into(),
+            ModelSpec::Entry {
+                id,
+                display_name,
+                args,
+                env,
+                cost,
+            } => ModelEntry {
+                id,
+                display_name,
+                args,
+                env,
+                cost,
+            },
+        }
+    }
+}
+
+impl From<String> for ModelEntry {
+    fn from(id: String) -> Self {
+        ModelEntry {
+            id,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for ModelEntry {
+    fn from(id: &str) -> Self {
+        id.to_string().into()
+    }
+}
+
+/// Agent-specific ways of driving a CLI, kept as data so that any agent can
+/// be adapted from config
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AgentDescriptor {
+    /// Args for each permission mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<PermissionArgs>,
+    /// How a task prompt is handed to an interactive session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_prompt: Option<PromptDelivery>,
+    /// Args resuming an earlier conversation; `{session_id}` is replaced
+    /// with the captured id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_args: Option<Vec<String>>,
+    /// Where the id of the agent's conversation can be found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<SessionIdSource>,
+    /// Non-interactive mode with a structured event stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headless: Option<HeadlessMode>,
+    /// How the agent asks for permission in `ask` mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_prompt: Option<ApprovalPrompt>,
+    /// Args printing the agent's version; `--version` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_args: Option<Vec<String>>,
+    /// Env vars the agent can't run without, set in `env`, the app's
+    /// environment or as secrets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub required_env: Option<Vec<String>>,
+}
+
+impl AgentDescriptor {
+    /// Field-wise merge where parts set in `overrides` win
+    pub fn overridden_by(&self, overrides: &AgentDescriptor) -> AgentDescriptor {
+        AgentDescriptor {
+            permissions: match (&self.permissions, &overrides.permissions) {
+                (Some(base), Some(overrides)) => Some(base.overridden_by(overrides)),
+                (base, overrides) => overrides.clone().or_else(|| base.clone()),
+            },
+            initial_prompt: overrides.initial_prompt.clone().or_else(|| self.initial_prompt.clone()),
+            resume_args: overrides.resume_args.clone().or_else(|| self.resume_args.clone()),
+            session_id: overrides.session_id.clone().or_else(|| self.session_id.clone()),
+            headless: overrides.headless.clone().or_else(|| self.headless.clone()),
+            approval_prompt: overrides.approval_prompt.clone().or_else(|| self.approval_prompt.clone()),
+            version_args: overrides.version_args.clone().or_else(|| self.version_args.clone()),
+            required_env: overrides.required_env.clone().or_else(|| self.required_env.clone()),
+        }
+    }
+}
+
+/// How much an agent may do without asking
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionMode {
+    /// Act without asking for approval
+    #[default]
+    Auto,
+    /// Ask before acting, the agent's own default
+    Ask,
+    /// Read and plan only, no edits or commands
+    ReadOnly,
+}
+
+/// Args an agent takes for each permission mode. An agent without args
+/// for `auto` or `ask` runs as it is; one without `read_only` args can't
+/// be run read-only.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PermissionArgs {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto: Option<ModeArgs>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ask: Option<ModeArgs>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<ModeArgs>,
+}
+
+impl PermissionArgs {
+    pub fn overridden_by(&self, overrides: &PermissionArgs) -> PermissionArgs {
+        PermissionArgs {
+            auto: overrides.auto.clone().or_else(|| self.auto.clone()),
+            ask: overrides.ask.clone().or_else(|| self.ask.clone()),
+            read_only: overrides.read_only.clone().or_else(|| self.read_only.clone()),
+        }
+    }
+
+    pub fn get(&self, mode: PermissionMode) -> Option<&ModeArgs> {
+        match mode {
+            PermissionMode::Auto => self.auto.as_ref(),
+            PermissionMode::Ask => self.ask.as_ref(),
+            PermissionMode::ReadOnly => self.read_only.as_ref(),
+        }
+    }
+}
+
+/// Args putting the agent in one permission mode
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModeArgs {
+    pub args: Vec<String>,
+    /// Args that already select this mode (e.g. `-y` for `--yolo`); nothing
+    /// is added when one of these or `args` is present. When `args` is a
+    /// flag and its value, the flag given with another value is replaced.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Only add `args` when the agent has no other args, for modes that are
+    /// a subcommand (e.g. `exec --full-auto`)
+    #[serde(default)]
+    pub only_without_args: bool,
+    /// Flags that would override this mode (e.g. `--yolo` for a read-only
+    /// mode); they are dropped from the agent's args
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+}
+
+/// An agent's permission prompt and the keys answering it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalPrompt {
+    /// Regex matching the question (ANSI-stripped output)
+    pub pattern: String,
+    /// Keys approving the request once
+    pub approve: String,
+    /// Keys declining it
+    pub deny: String,
+}
+
+/// Where an agent's conversation id shows up: in its output, or as the
+/// name of the file it keeps the conversation in
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionIdSource {
+    /// Regex over the output whose first group is the id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Directory holding one file per conversation, searched a few levels
+    /// deep. `~` is the home dir and `{project}` the worktree path with
+    /// every other char than letters and digits replaced by `-`. Without
+    /// `{project}` only files mentioning the worktree path count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    /// Regex over file names in `dir` whose first group is the id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_pattern: Option<String>,
+}
+
+/// Answers permission prompts whose request (the command, file or tool
+/// shown above the question) matches `pattern`. The first matching rule
+/// decides; prompts no rule matches are asked.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ApprovalRule {
+    pub name: String,
+    pub pattern: String,
+    pub decision: ApprovalDecision,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approve,
+    Deny,
+    /// Leave it to the user
+    Ask,
+    /// Answered by the user in the terminal; only logged, never a rule's
+    Manual,
+}
+
+/// How a prompt is passed on the agent's command line
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PromptDelivery {
+    /// As the last positional argument, after `--` so a prompt starting
+    /// with `-` isn't read as a flag
+    Positional,
+    /// As the value of a flag, e.g. `-p <prompt>`
+    Flag { flag: String },
+    /// After a subcommand, e.g. `exec -- <prompt>`
+    Subcommand { name: String },
+    /// Typed into the session once it has started
+    Stdin,
+}
+
+/// JSON event stream an agent writes in its structured output mode
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventFormat {
+    /// `claude --output-format stream-json`
+    ClaudeJson,
+    /// `codex exec --json`
+    CodexJson,
+    /// `gemini --output-format json|stream-json`
+    GeminiJson,
+}
+
+/// How to run the agent without a TUI for one task
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HeadlessMode {
+    /// Args used instead of the interactive ones
+    pub args: Vec<String>,
+    pub prompt: PromptDelivery,
+    pub format: EventFormat,
+    /// Permission args used in place of the interactive ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<PermissionArgs>,
+}
+
+/// Resource limits applied to an agent's process tree (Linux only).
+/// Unset fields leave the inherited limit in place.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ResourceLimits {
+    /// Address space per process, in MiB
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// CPU time per process, in seconds
+    #[serde(default)]
+    pub cpu_seconds: Option<u64>,
+    /// Processes for the agent's user (RLIMIT_NPROC counts per user)
+    #[serde(default)]
+    pub max_processes: Option<u64>,
+    #[serde(default)]
+    pub max_open_files: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Field-wise merge where values set in `overrides` win
+    pub fn overridden_by(&self, overrides: &ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            memory_mb: overrides.memory_mb.or(self.memory_mb),
+            cpu_seconds: overrides.cpu_seconds.or(self.cpu_seconds),
+            max_processes: overrides.max_processes.or(self.max_processes),
+            max_open_files: overrides.max_open_files.or(self.max_open_files),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ResourceLimits::default()
+    }
+}
+
+/// Spending guardrails for one workspace. Unset limits are unlimited.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BudgetLimits {
+    /// Cost as reported by the agent, in USD
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    /// Wall-clock time of the workspace's agents, summed over their sessions
+    #[serde(default)]
+    pub max_runtime_secs: Option<u64>,
+    /// Input plus output tokens
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    /// Share of a limit (in percent) at which a warning is emitted; 80 when unset
+    #[serde(default)]
+    pub warn_percent: Option<u8>,
+    /// What happens at a hard limit; the agent is stopped when unset
+    #[serde(default)]
+    pub on_exceed: Option<BudgetAction>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// Send Ctrl-C so the agent abandons its current turn
+    Interrupt,
+    /// Stop the agent's PTY
+    Stop,
+}
+
+impl BudgetLimits {
+    /// Field-wise merge where values set in `overrides` win
+    pub fn overridden_by(&self, overrides: &BudgetLimits) -> BudgetLimits {
+        BudgetLimits {
+            max_cost_usd: overrides.max_cost_usd.or(self.max_cost_usd),
+            max_runtime_secs: overrides.max_runtime_secs.or(self.max_runtime_secs),
+            max_tokens: overrides.max_tokens.or(self.max_tokens),
+            warn_percent: overrides.warn_percent.or(self.warn_percent),
+            on_exceed: overrides.on_exceed.or(self.on_exceed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_cost_usd.is_none() && self.max_runtime_secs.is_none() && self.max_tokens.is_none()
+    }
+}
+
+/// Runs an action when agent output (ANSI-stripped) matches `pattern`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TriggerRule {
+    pub name: String,
+    pub pattern: String,
+    pub action: TriggerAction,
+    /// Minimum time between two firings in the same workspace
+    #[serde(default = "default_trigger_cooldown_ms")]
+    pub cooldown_ms: u64,
+    /// A repo can switch off a global trigger by redefining it disabled
+    #[serde(default = "default_trigger_enabled")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerAction {
+    /// Emit a notification (the `trigger-fired` event only)
+    Notify {
+        #[serde(default)]
+        message: Option<String>,
+    },
+    /// Type `text` into the agent, followed by Enter unless `submit` is false
+    AutoReply {
+        text: String,
+        #[serde(default = "default_submit")]
+        submit: bool,
+    },
+    RunSnippet { snippet: String },
+    StopWorkspace,
+}
+
+fn default_trigger_cooldown_ms() -> u64 {
+    30_000
+}
+
+fn default_trigger_enabled() -> bool {
+    true
+}
+
+fn default_submit() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,12 +490,70 @@ pub struct Defaults {
     pub theme: String,
     #[serde(default = "default_max_concurrent_agents")]
     pub max_concurrent_agents: usize,
+    /// Encoding of `pty-output-*` event payloads
+    #[serde(default)]
+    pub output_encoding: OutputEncoding,
+    /// Bytes of PTY output retained per workspace for replay on reattach
+    #[serde(default = "default_scrollback_bytes")]
+    pub scrollback_bytes: usize,
+    /// Grace period after SIGINT before a stopping agent gets SIGTERM
+    #[serde(default = "default_interrupt_grace_ms")]
+    pub interrupt_grace_ms: u64,
+    /// Grace period after SIGTERM before the process group is SIGKILLed
+    #[serde(default = "default_terminate_grace_ms")]
+    pub terminate_grace_ms: u64,
+    /// Quiet time after which an agent without a visible prompt is idle
+    #[serde(default = "default_idle_after_ms")]
+    pub idle_after_ms: u64,
+    /// Host agent PTYs in a background daemon so they survive app restarts
+    #[serde(default)]
+    pub session_daemon: bool,
+    /// Record agent sessions under ~/.ocestrater/transcripts/
+    #[serde(default = "default_record_transcripts")]
+    pub record_transcripts: bool,
+    /// Spending limits for every workspace unless a repo overrides them
+    #[serde(default)]
+    pub budget: BudgetLimits,
+    /// What agents may do without asking, unless a repo overrides it
+    #[serde(default)]
+    pub permission_mode: PermissionMode,
+}
+
+/// How raw PTY bytes are carried over IPC
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputEncoding {
+    /// UTF-8 text; multi-byte sequences split across reads are reassembled
+    #[default]
+    Utf8,
+    /// Base64 of the exact bytes, for clients that decode binary themselves
+    Base64,
 }
 
 fn default_max_concurrent_agents() -> usize {
     8
 }
 
+fn default_scrollback_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_interrupt_grace_ms() -> u64 {
+    3000
+}
+
+fn default_terminate_grace_ms() -> u64 {
+    2000
+}
+
+fn default_idle_after_ms() -> u64 {
+    5000
+}
+
+fn default_record_transcripts() -> bool {
+    true
+}
+
 fn default_theme() -> String {
     "system".into()
 }
@@ -66,6 +580,18 @@ pub struct RepoConfig {
     pub snippets: HashMap<String, String>,
     #[serde(default)]
     pub agent_overrides: HashMap<String, AgentOverride>,
+    /// Limits for every agent in this repo, overriding per-agent limits
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    #[serde(default)]
+    pub triggers: Vec<TriggerRule>,
+    /// Budget for workspaces of this repo, overriding the defaults field-wise
+    #[serde(default)]
+    pub budget: BudgetLimits,
+    #[serde(default)]
+    pub permission_mode: Option<PermissionMode>,
+    #[serde(default)]
+    pub approval_rules: Vec<ApprovalRule>,
 }
 
 fn default_version() -> u32 {
@@ -142,6 +668,12 @@ impl ConfigStore {
                 models: vec!["opus".into(), "sonnet".into(), "haiku".into()],
                 default_model: Some("sonnet".into()),
                 model_flag: Some("--model".into()),
+                free_form_models: false,
+                prompt_patterns: agent::builtin_prompt_patterns("claude"),
+                limits: ResourceLimits::default(),
+                usage_patterns: vec![],
+                usage_format: None,
+                descriptor: agent::builtin_descriptor("claude"),
             },
         );
         agents.insert(
@@ -153,6 +685,12 @@ impl ConfigStore {
                 models: vec!["o3".into(), "o4-mini".into(), "gpt-4.1".into()],
                 default_model: Some("o4-mini".into()),
                 model_flag: Some("--model".into()),
+                free_form_models: false,
+                prompt_patterns: agent::builtin_prompt_patterns("codex"),
+                limits: ResourceLimits::default(),
+                usage_patterns: vec![],
+                usage_format: None,
+                descriptor: agent::builtin_descriptor("codex"),
             },
         );
         agents.insert(
@@ -164,6 +702,12 @@ impl ConfigStore {
                 models: vec!["gemini-2.5-pro".into(), "gemini-2.5-flash".into()],
                 default_model: Some("gemini-2.5-flash".into()),
                 model_flag: Some("--model".into()),
+                free_form_models: false,
+                prompt_patterns: agent::builtin_prompt_patterns("gemini"),
+                limits: ResourceLimits::default(),
+                usage_patterns: vec![],
+                usage_format: None,
+                descriptor: agent::builtin_descriptor("gemini"),
             },
         );
 
@@ -174,8 +718,19 @@ impl ConfigStore {
                 agent: "claude".into(),
                 theme: "system".into(),
                 max_concurrent_agents: default_max_concurrent_agents(),
+                output_encoding: OutputEncoding::Utf8,
+                scrollback_bytes: default_scrollback_bytes(),
+                interrupt_grace_ms: default_interrupt_grace_ms(),
+                terminate_grace_ms: default_terminate_grace_ms(),
+                idle_after_ms: default_idle_after_ms(),
+                session_daemon: false,
+                record_transcripts: default_record_transcripts(),
+                budget: BudgetLimits::default(),
+                permission_mode: PermissionMode::Auto,
             },
             repositories: vec![],
+            triggers: vec![],
+            approval_rules: vec![],
         }
     }
 
@@ -188,6 +743,11 @@ impl ConfigStore {
             worktree_dir: ".worktrees".into(),
             snippets: HashMap::new(),
             agent_overrides: HashMap::new(),
+            limits: ResourceLimits::default(),
+            triggers: vec![],
+            budget: BudgetLimits::default(),
+            permission_mode: None,
+            approval_rules: vec![],
         }
     }
 
@@ -206,9 +766,11 @@ impl ConfigStore {
 
     /// Resolve agent config: repo overrides > global agent config
     pub fn resolve_agent(&self, repo_path: &str, agent_name: &str) -> Option<AgentConfig> {
-        let base = self.global.agents.get(agent_name)?.clone();
+        let mut base = self.global.agents.get(agent_name)?.clone();
 
         if let Some(repo_cfg) = self.repo_configs.get(repo_path) {
+            base.limits = base.limits.overridden_by(&repo_cfg.limits);
+
             if let Some(overrides) = repo_cfg.agent_overrides.get(agent_name) {
                 let args = if overrides.args.is_empty() {
                     base.args
@@ -217,20 +779,57 @@ impl ConfigStore {
                 };
                 let mut env = base.env;
                 env.extend(overrides.env.clone());
-                return Some(AgentConfig {
-                    command: base.command,
-                    args,
-                    env,
-                    models: base.models,
-                    default_model: base.default_model,
-                    model_flag: base.model_flag,
-                });
+                return Some(AgentConfig { args, env, ..base });
             }
         }
 
         Some(base)
     }
 
+    /// Triggers for a repo: global ones, with repo triggers replacing
+    /// same-named global triggers
+    pub fn resolve_triggers(&self, repo_path: &str) -> Vec<TriggerRule> {
+        let mut triggers = self.global.triggers.clone();
+        if let Some(repo_cfg) = self.repo_configs.get(repo_path) {
+            for rule in &repo_cfg.triggers {
+                match triggers.iter_mut().find(|t| t.name == rule.name) {
+                    Some(existing) => *existing = rule.clone(),
+                    None => triggers.push(rule.clone()),
+                }
+            }
+        }
+        triggers
+    }
+
+    /// Approval rules for a repo: its own first, then the global ones
+    pub fn resolve_approval_rules(&self, repo_path: &str) -> Vec<ApprovalRule> {
+        let mut rules = self
+            .repo_configs
+            .get(repo_path)
+            .map(|rc| rc.approval_rules.clone())
+            .unwrap_or_default();
+        rules.extend(self.global.approval_rules.iter().cloned());
+        rules
+    }
+
+    /// Budget for a repo's workspaces: the defaults with repo overrides
+    pub fn resolve_budget(&self, repo_path: &str) -> BudgetLimits {
+        let defaults = &self.global.defaults.budget;
+        match self.repo_configs.get(repo_path) {
+            Some(repo_cfg) => defaults.overridden_by(&repo_cfg.budget),
+            None => defaults.clone(),
+        }
+    }
+
+    /// Permission mode for a repo's agents: the default unless the repo
+    /// sets one
+    pub fn resolve_permission_mode(&self, repo_path: &str) -> PermissionMode {
+        self.repo_configs
+            .get(repo_path)
+            .and_then(|rc| rc.permission_mode)
+            .unwrap_or(self.global.defaults.permission_mode)
+    }
+
     pub fn save_global(&self) -> Result<(), String> {
         let _ = std::fs::create_dir_all(&self.config_dir);
         let path = self.config_dir.join("config.json");
@@ -259,6 +858,10 @@ impl ConfigStore {
 mod tests {
     use super::*;
 
+    fn ids(models: &[ModelEntry]) -> Vec<&str> {
+        models.iter().map(|m| m.id.as_str()).collect()
+    }
+
     fn make_store() -> ConfigStore {
         ConfigStore {
             global: ConfigStore::default_global(),
@@ -321,6 +924,144 @@ mod tests {
         assert!(agent.env.is_empty());
     }
 
+    #[test]
+    fn test_resolve_agent_repo_limits_override_agent_limits() {
+        let mut store = make_store();
+        if let Some(claude) = store.global.agents.get_mut("claude") {
+            claude.limits = ResourceLimits {
+                memory_mb: Some(4096),
+                max_open_files: Some(1024),
+                ..Default::default()
+            };
+        }
+
+        let mut rc = ConfigStore::default_repo_config();
+        rc.limits = ResourceLimits {
+            memory_mb: Some(2048),
+            max_processes: Some(256),
+            ..Default::default()
+        };
+        store.repo_configs.insert("/my/repo".to_string(), rc);
+
+        let agent = store.resolve_agent("/my/repo", "claude").unwrap();
+        assert_eq!(agent.limits.memory_mb, Some(2048));
+        assert_eq!(agent.limits.max_processes, Some(256));
+        assert_eq!(agent.limits.max_open_files, Some(1024));
+        assert_eq!(agent.limits.cpu_seconds, None);
+    }
+
+    #[test]
+    fn test_trigger_rule_serde_defaults() {
+        let json = r#"{"name": "proceed", "pattern": "Do you want to proceed\\?", "action": {"type": "auto_reply", "text": "y"}}"#;
+        let rule: TriggerRule = serde_json::from_str(json).unwrap();
+        assert_eq!(rule.cooldown_ms, 30_000);
+        assert!(rule.enabled);
+        assert_eq!(
+            rule.action,
+            TriggerAction::AutoReply {
+                text: "y".into(),
+                submit: true
+            }
+        );
+
+        let stop: TriggerAction = serde_json::from_str(r#"{"type": "stop_workspace"}"#).unwrap();
+        assert_eq!(stop, TriggerAction::StopWorkspace);
+    }
+
+    #[test]
+    fn test_resolve_triggers_repo_replaces_global_by_name() {
+        let rule = |name: &str, pattern: &str| TriggerRule {
+            name: name.into(),
+            pattern: pattern.into(),
+            action: TriggerAction::Notify { message: None },
+            cooldown_ms: 1000,
+            enabled: true,
+        };
+        let mut store = make_store();
+        store.global.triggers = vec![rule("rate-limit", "rate limit"), rule("tests", "FAILED")];
+        let mut rc = ConfigStore::default_repo_config();
+        rc.triggers = vec![
+            TriggerRule {
+                enabled: false,
+                ..rule("rate-limit", "rate limit")
+            },
+            rule("deploy", "deployed"),
+        ];
+        store.repo_configs.insert("/my/repo".to_string(), rc);
+
+        let triggers = store.resolve_triggers("/my/repo");
+        let names: Vec<&str> = triggers.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["rate-limit", "tests", "deploy"]);
+        assert!(!triggers[0].enabled);
+        assert_eq!(store.resolve_triggers("/other").len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_budget_repo_overrides_defaults_fieldwise() {
+        let mut store = make_store();
+        store.global.defaults.budget = BudgetLimits {
+            max_cost_usd: Some(5.0),
+            max_runtime_secs: Some(3600),
+            ..Default::default()
+        };
+        let mut rc = ConfigStore::default_repo_config();
+        rc.budget = serde_json::from_str(r#"{"max_cost_usd": 1.5, "on_exceed": "interrupt"}"#).unwrap();
+        store.repo_configs.insert("/my/repo".to_string(), rc);
+
+        let budget = store.resolve_budget("/my/repo");
+        assert_eq!(budget.max_cost_usd, Some(1.5));
+        assert_eq!(budget.max_runtime_secs, Some(3600));
+        assert_eq!(budget.on_exceed, Some(BudgetAction::Interrupt));
+        assert_eq!(store.resolve_budget("/other").max_cost_usd, Some(5.0));
+        assert!(BudgetLimits::default().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_approval_rules_puts_repo_rules_first() {
+        let mut store = make_store();
+        store.global.approval_rules = serde_json::from_str(
+            r#"[{"name": "tests", "pattern": "cargo test", "decision": "approve"}]"#,
+        )
+        .unwrap();
+        let mut rc = ConfigStore::default_repo_config();
+        rc.approval_rules = vec![ApprovalRule {
+            name: "all".into(),
+            pattern: ".".into(),
+            decision: ApprovalDecision::Ask,
+        }];
+        store.repo_configs.insert("/my/repo".to_string(), rc);
+
+        let names: Vec<String> = store
+            .resolve_approval_rules("/my/repo")
+            .into_iter()
+            .map(|r| r.name)
+            .collect();
+        assert_eq!(names, vec!["all", "tests"]);
+        assert_eq!(store.resolve_approval_rules("/other")[0].decision, ApprovalDecision::Approve);
+    }
+
+    #[test]
+    fn test_resolve_permission_mode_repo_overrides_default() {
+        let mut store = make_store();
+        assert_eq!(store.resolve_permission_mode("/my/repo"), PermissionMode::Auto);
+        store.global.defaults.permission_mode = PermissionMode::Ask;
+        let mut rc = ConfigStore::default_repo_config();
+        rc.permission_mode = serde_json::from_str(r#""read_only""#).unwrap();
+        store.repo_configs.insert("/my/repo".to_string(), rc);
+
+        assert_eq!(store.resolve_permission_mode("/my/repo"), PermissionMode::ReadOnly);
+        assert_eq!(store.resolve_permission_mode("/other"), PermissionMode::Ask);
+    }
+
+    #[test]
+    fn test_resource_limits_serde_defaults() {
+        let limits: ResourceLimits = serde_json::from_str(r#"{"cpu_seconds": 600}"#).unwrap();
+        assert_eq!(limits.cpu_seconds, Some(600));
+        assert!(limits.memory_mb.is_none());
+        assert!(!limits.is_empty());
+        assert!(ResourceLimits::default().is_empty());
+    }
+
     #[test]
     fn test_resolve_agent_unknown_agent_returns_none() {
         let store = make_store();
@@ -350,6 +1091,11 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
@@ -384,6 +1130,11 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
@@ -429,6 +1180,11 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
@@ -516,6 +1272,11 @@ mod tests {
             worktree_dir: ".wt".into(),
             snippets,
             agent_overrides: HashMap::new(),
+            limits: ResourceLimits::default(),
+            triggers: vec![],
+            budget: BudgetLimits::default(),
+            permission_mode: None,
+            approval_rules: vec![],
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -538,9 +1299,15 @@ mod tests {
             command: "claude".to_string(),
             args: vec!["--flag".to_string()],
             env,
-            models: vec!["opus".to_string(), "sonnet".to_string()],
+            models: vec!["opus".into(), "sonnet".into()],
             default_model: Some("sonnet".to_string()),
             model_flag: Some("--model".to_string()),
+            free_form_models: false,
+            prompt_patterns: vec![],
+            limits: ResourceLimits::default(),
+            usage_patterns: vec![],
+            usage_format: None,
+            descriptor: AgentDescriptor::default(),
         };
 
         let json = serde_json::to_string(&original).unwrap();
@@ -560,6 +1327,24 @@ mod tests {
         assert_eq!(defaults.agent, "codex");
         assert_eq!(defaults.theme, "system");
         assert_eq!(defaults.max_concurrent_agents, 8);
+        assert_eq!(defaults.output_encoding, OutputEncoding::Utf8);
+        assert_eq!(defaults.scrollback_bytes, 1024 * 1024);
+        assert_eq!(defaults.interrupt_grace_ms, 3000);
+        assert_eq!(defaults.terminate_grace_ms, 2000);
+        assert_eq!(defaults.idle_after_ms, 5000);
+        assert!(!defaults.session_daemon);
+        assert!(defaults.record_transcripts);
+    }
+
+    #[test]
+    fn test_output_encoding_serde() {
+        let json = r#"{"agent": "claude", "output_encoding": "base64"}"#;
+        let defaults: Defaults = serde_json::from_str(json).unwrap();
+        assert_eq!(defaults.output_encoding, OutputEncoding::Base64);
+        assert_eq!(
+            serde_json::to_string(&OutputEncoding::Utf8).unwrap(),
+            "\"utf8\""
+        );
     }
 
     #[test]
@@ -578,7 +1363,7 @@ mod tests {
     fn test_agent_config_models_field() {
         let config = ConfigStore::default_global();
         let claude = config.agents.get("claude").unwrap();
-        assert_eq!(claude.models, vec!["opus", "sonnet", "haiku"]);
+        assert_eq!(ids(&claude.models), vec!["opus", "sonnet", "haiku"]);
     }
 
     #[test]
@@ -614,6 +1399,7 @@ mod tests {
         assert!(config.models.is_empty());
         assert!(config.default_model.is_none());
         assert!(config.model_flag.is_none());
+        assert!(config.prompt_patterns.is_empty());
     }
 
     #[test]
@@ -622,13 +1408,19 @@ mod tests {
             command: "my-agent".to_string(),
             args: vec!["--verbose".to_string()],
             env: HashMap::new(),
-            models: vec!["model-a".to_string(), "model-b".to_string()],
+            models: vec!["model-a".into(), "model-b".into()],
             default_model: Some("model-a".to_string()),
             model_flag: Some("--model".to_string()),
+            free_form_models: false,
+            prompt_patterns: vec![],
+            limits: ResourceLimits::default(),
+            usage_patterns: vec![],
+            usage_format: None,
+            descriptor: AgentDescriptor::default(),
         };
         let json = serde_json::to_string(&config).unwrap();
         let parsed: AgentConfig = serde_json::from_str(&json).unwrap();
-        assert_eq!(parsed.models, vec!["model-a", "model-b"]);
+        assert_eq!(ids(&parsed.models), vec!["model-a", "model-b"]);
         assert_eq!(parsed.default_model, Some("model-a".to_string()));
         assert_eq!(parsed.model_flag, Some("--model".to_string()));
     }
@@ -637,7 +1429,7 @@ mod tests {
     fn test_resolve_agent_preserves_model_fields() {
         let store = make_store();
         let agent = store.resolve_agent("/some/repo", "claude").unwrap();
-        assert_eq!(agent.models, vec!["opus", "sonnet", "haiku"]);
+        assert_eq!(ids(&agent.models), vec!["opus", "sonnet", "haiku"]);
         assert_eq!(agent.default_model, Some("sonnet".to_string()));
         assert_eq!(agent.model_flag, Some("--model".to_string()));
     }
@@ -665,13 +1457,18 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
         let agent = store.resolve_agent("/model/repo", "claude").unwrap();
         // Override changes args but model fields come from base
         assert_eq!(agent.args, vec!["--custom"]);
-        assert_eq!(agent.models, vec!["opus", "sonnet", "haiku"]);
+        assert_eq!(ids(&agent.models), vec!["opus", "sonnet", "haiku"]);
         assert_eq!(agent.default_model, Some("sonnet".to_string()));
         assert_eq!(agent.model_flag, Some("--model".to_string()));
     }
@@ -680,16 +1477,58 @@ mod tests {
     fn test_codex_agent_models() {
         let config = ConfigStore::default_global();
         let codex = config.agents.get("codex").unwrap();
-        assert_eq!(codex.models, vec!["o3", "o4-mini", "gpt-4.1"]);
+        assert_eq!(ids(&codex.models), vec!["o3", "o4-mini", "gpt-4.1"]);
         assert_eq!(codex.command, "codex");
     }
 
+    #[test]
+    fn test_models_accept_bare_ids_and_full_entries() {
+        let json = r#"{
+            "command": "codex",
+            "models": [
+                "o3",
+                {
+                    "id": "gpt-5",
+                    "display_name": "GPT-5 (high)",
+                    "args": ["-c", "model_reasoning_effort=high"],
+                    "env": {"CODEX_CONTEXT": "large"},
+                    "cost": {"input_per_mtok": 1.25, "output_per_mtok": 10.0}
+                }
+            ]
+        }"#;
+        let config: AgentConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.models[0], ModelEntry::from("o3"));
+        let gpt5 = config.model("gpt-5").unwrap();
+        assert_eq!(gpt5.display_name.as_deref(), Some("GPT-5 (high)"));
+        assert_eq!(gpt5.args, vec!["-c", "model_reasoning_effort=high"]);
+        assert_eq!(gpt5.cost.unwrap().output_per_mtok, 10.0);
+
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: AgentConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.models, config.models);
+    }
+
+    #[test]
+    fn test_check_model_rejects_unlisted_models() {
+        let mut codex = ConfigStore::default_global().agents.remove("codex").unwrap();
+        assert!(codex.check_model("codex", "o3").is_ok());
+        let err = codex.check_model("codex", "o5").unwrap_err();
+        assert!(err.contains("unknown model o5") && err.contains("o4-mini"));
+
+        codex.free_form_models = true;
+        assert!(codex.check_model("codex", "o5").is_ok());
+
+        let custom: AgentConfig = serde_json::from_str(r#"{"command": "x", "default_model": "m1"}"#).unwrap();
+        assert!(custom.check_model("x", "m1").is_ok());
+        assert!(custom.check_model("x", "m2").unwrap_err().contains("lists no models"));
+    }
+
     #[test]
     fn test_gemini_agent_models() {
         let config = ConfigStore::default_global();
         let gemini = config.agents.get("gemini").unwrap();
         assert_eq!(
-            gemini.models,
+            ids(&gemini.models),
             vec!["gemini-2.5-pro", "gemini-2.5-flash"]
         );
         assert_eq!(gemini.command, "gemini");
@@ -778,6 +1617,11 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides_a,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
@@ -791,6 +1635,11 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides_b,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
@@ -832,6 +1681,11 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
@@ -866,6 +1720,11 @@ mod tests {
                 worktree_dir: ".worktrees".into(),
                 snippets: HashMap::new(),
                 agent_overrides: overrides,
+                limits: ResourceLimits::default(),
+                triggers: vec![],
+                budget: BudgetLimits::default(),
+                permission_mode: None,
+                approval_rules: vec![],
             },
         );
 
@@ -940,6 +1799,11 @@ mod tests {
             worktree_dir: ".wt".to_string(),
             snippets,
             agent_overrides,
+            limits: ResourceLimits::default(),
+            triggers: vec![],
+            budget: BudgetLimits::default(),
+            permission_mode: None,
+            approval_rules: vec![],
         };
 
         let json = serde_json::to_string(&config).unwrap();
@@ -764,6 +764,37 @@ fn collect_conflicts(repo_path: &str) -> Vec<String> {
     }
 }
 
+/// `git diff --stat` of the worktree, uncommitted changes included, against
+/// where it branched off, followed by the untracked files
+pub fn diff_stat(worktree_path: &str, base_branch: &str) -> Result<String, String> {
+    let base_sha = resolve_merge_base(worktree_path, base_branch)?;
+    let mut stat = git_allow_empty(worktree_path, &["diff", "--stat", "-M", &base_sha])?;
+
+    let untracked = git_allow_empty(worktree_path, &["ls-files", "--others", "--exclude-standard"])?;
+    for path in untracked.lines().filter(|l| !l.is_empty()) {
+        stat.push_str(&format!(" {path} (untracked)\n"));
+    }
+    Ok(stat)
+}
+
+/// Keep `pattern` out of `git status` without touching tracked files, by
+/// adding it to the repository's `info/exclude`
+pub fn exclude_locally(worktree_path: &str, pattern: &str) -> Result<(), String> {
+    let path = git(worktree_path, &["rev-parse", "--git-path", "info/exclude"])?;
+    let path = std::path::Path::new(worktree_path).join(path.trim());
+
+    let current = std::fs::read_to_string(&path).unwrap_or_default();
+    if current.lines().any(|line| line.trim() == pattern) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("mkdir error: {e}"))?;
+    }
+    let separator = if current.is_empty() || current.ends_with('\n') { "" } else { "\n" };
+    std::fs::write(&path, format!("{current}{separator}{pattern}\n"))
+        .map_err(|e| format!("write error: {e}"))
+}
+
 pub fn discard_worktree(
     repo_path: &str,
     worktree_path: &str,
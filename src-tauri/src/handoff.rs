@@ -0,0 +1,165 @@
+use crate::git_ops;
+use crate::transcript::{self, TranscriptEntry};
+use std::path::PathBuf;
+
+/// Where the summary is written, relative to the worktree
+pub const HANDOFF_FILE: &str = ".ocestrater/handoff.md";
+
+/// Lines of the previous agent's output included in the summary
+const EXCERPT_LINES: usize = 80;
+
+/// What the agent being switched away from leaves for the next one
+pub struct Handoff {
+    pub from_agent: String,
+    pub branch: String,
+    /// Task the workspace was created with
+    pub task: Option<String>,
+    pub diff_stat: String,
+    /// End of the previous agent's output; empty without a transcript
+    pub excerpt: String,
+}
+
+impl Handoff {
+    /// Gather the diff stat and the transcript excerpt of a workspace
+    pub fn collect(
+        workspace_id: &str,
+        from_agent: &str,
+        branch: &str,
+        task: Option<&str>,
+        repo_path: &str,
+        worktree_path: &str,
+    ) -> Result<Self, String> {
+        let base_branch = git_ops::detect_base_branch(repo_path);
+        let diff_stat = git_ops::diff_stat(worktree_path, &base_branch)?;
+        let excerpt = transcript::transcript_path(&transcript::transcripts_dir(), workspace_id)
+            .and_then(|path| transcript::read_entries(&path))
+            .map(|entries| last_run_excerpt(&entries, EXCERPT_LINES))
+            .unwrap_or_default();
+        Ok(Self {
+            from_agent: from_agent.to_string(),
+            branch: branch.to_string(),
+            task: task.map(str::to_string),
+            diff_stat,
+            excerpt,
+        })
+    }
+
+    pub fn render(&self) -> String {
+        let mut doc = format!(
+            "# Handoff from {}\n\nYou are taking over branch `{}` from {}, which worked in this worktree before you.\n",
+            self.from_agent, self.branch, self.from_agent
+        );
+        if let Some(task) = &self.task {
+            doc.push_str(&format!("\n## Task\n\n{}\n", task.trim()));
+        }
+        let diff_stat = match self.diff_stat.trim() {
+            "" => "No changes yet.",
+            stat => stat,
+        };
+        doc.push_str(&format!("\n## Changes so far\n\n```\n{diff_stat}\n```\n"));
+        if !self.excerpt.trim().is_empty() {
+            doc.push_str(&format!(
+                "\n## End of {}'s session\n\n```\n{}\n```\n",
+                self.from_agent,
+                self.excerpt.trim_end()
+            ));
+        }
+        doc
+    }
+
+    /// Write the summary into the worktree, kept out of `git status`
+    pub fn write(&self, worktree_path: &str) -> Result<PathBuf, String> {
+        let path = PathBuf::from(worktree_path).join(HANDOFF_FILE);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("mkdir error: {e}"))?;
+        }
+        std::fs::write(&path, self.render()).map_err(|e| format!("handoff write error: {e}"))?;
+        git_ops::exclude_locally(worktree_path, &format!("/{HANDOFF_FILE}"))?;
+        Ok(path)
+    }
+
+    /// Prompt pointing the next agent at the summary
+    pub fn prompt(&self) -> String {
+        format!(
+            "You are taking over from {}. Read {HANDOFF_FILE} for the task and what has been done so far, then continue the work.",
+            self.from_agent
+        )
+    }
+}
+
+/// Last `lines` non-blank lines of output of the latest run in a transcript
+fn last_run_excerpt(entries: &[TranscriptEntry], lines: usize) -> String {
+    let start = entries
+        .iter()
+        .rposition(|e| matches!(e, TranscriptEntry::Start { .. }))
+        .unwrap_or(0);
+    let text = transcript::to_plain_text(&entries[start..]);
+    let kept: Vec<&str> = text
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    kept[kept.len().saturating_sub(lines)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(agent: &str) -> TranscriptEntry {
+        TranscriptEntry::Start {
+            time_ms: 0,
+            agent: agent.into(),
+            label: agent.into(),
+            working_dir: "/wt".into(),
+            cols: 80,
+            rows: 24,
+            reattached: false,
+        }
+    }
+
+    fn output(data: &str) -> TranscriptEntry {
+        TranscriptEntry::Output {
+            time_ms: 0,
+            data: data.into(),
+        }
+    }
+
+    #[test]
+    fn test_excerpt_takes_the_end_of_the_last_run() {
+        let entries = vec![
+            start("claude"),
+            output("earlier run\n"),
+            start("claude"),
+            output("\x1b[1mone\x1b[0m\n\n   \ntwo\nthree  \n"),
+        ];
+        assert_eq!(last_run_excerpt(&entries, 2), "two\nthree");
+        assert_eq!(last_run_excerpt(&entries, 10), "one\ntwo\nthree");
+    }
+
+    #[test]
+    fn test_render_includes_task_changes_and_excerpt() {
+        let handoff = Handoff {
+            from_agent: "claude".into(),
+            branch: "fix-login".into(),
+            task: Some("Fix the login redirect".into()),
+            diff_stat: " src/login.rs | 4 ++--\n 1 file changed\n".into(),
+            excerpt: "Running tests...".into(),
+        };
+        let doc = handoff.render();
+        assert!(doc.starts_with("# Handoff from claude"));
+        assert!(doc.contains("## Task\n\nFix the login redirect"));
+        assert!(doc.contains("src/login.rs | 4 ++--"));
+        assert!(doc.contains("## End of claude's session\n\n```\nRunning tests..."));
+
+        let empty = Handoff {
+            task: None,
+            diff_stat: String::new(),
+            excerpt: String::new(),
+            ..handoff
+        };
+        let doc = empty.render();
+        assert!(doc.contains("No changes yet."));
+        assert!(!doc.contains("## Task") && !doc.contains("## End of"));
+    }
+}
@@ -0,0 +1,629 @@
+use crate::agent::AgentAdapter;
+use crate::budget::{self, BudgetUsage, BudgetWatcher};
+use crate::config::{BudgetAction, BudgetLimits, EventFormat};
+use crate::pty_manager::PtyManager;
+use crate::resource_limits;
+use crate::transcript;
+use crate::usage::{self, UsageCounts, UsageTracker};
+use crate::workspace::WorkspaceManager;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Lines of stderr kept to explain a run that fails without a result
+const STDERR_TAIL_LINES: usize = 20;
+
+/// How often a run without output is checked against its runtime budget
+const BUDGET_TICK_MS: u64 = 1000;
+
+/// Poll interval while waiting for a killed run to exit
+const EXIT_POLL_INTERVAL_MS: u64 = 50;
+
+/// Tools that write files, with the input field holding the path
+const FILE_EDIT_TOOLS: &[(&str, &str)] = &[
+    // claude
+    ("Edit", "file_path"),
+    ("MultiEdit", "file_path"),
+    ("Write", "file_path"),
+    ("NotebookEdit", "notebook_path"),
+    // gemini
+    ("write_file", "file_path"),
+    ("replace", "file_path"),
+];
+
+/// Payload of the `agent-event-*` event of a headless run
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    AssistantMessage { text: String },
+    ToolCall { name: String, input: Value },
+    FileEdit { path: String },
+    /// Always the last event of a run
+    Result { text: String, is_error: bool },
+    Usage { model: String, counts: UsageCounts },
+}
+
+/// Outcome of a workspace's latest headless run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HeadlessResult {
+    pub text: String,
+    pub is_error: bool,
+    /// Unix epoch millis
+    pub finished_at: u64,
+}
+
+/// Turns the JSON lines an agent writes in its structured mode into events.
+/// Agents that finish without a result text get their last assistant
+/// message as the result.
+pub struct EventParser {
+    format: EventFormat,
+    last_message: String,
+}
+
+impl EventParser {
+    pub fn new(format: EventFormat) -> Self {
+        Self {
+            format,
+            last_message: String::new(),
+        }
+    }
+
+    /// Events in one line of output; anything but a JSON event yields none
+    pub fn parse_line(&mut self, line: &str) -> Vec<AgentEvent> {
+        let event: Value = match serde_json::from_str(line.trim()) {
+            Ok(event) => event,
+            Err(_) => return Vec::new(),
+        };
+        let kind = event.get("type").and_then(Value::as_str).unwrap_or_default();
+        match self.format {
+            EventFormat::ClaudeJson => self.parse_claude(kind, &event),
+            EventFormat::CodexJson => self.parse_codex(kind, &event),
+            EventFormat::GeminiJson => self.parse_gemini(kind, &event),
+        }
+    }
+
+    fn parse_claude(&mut self, kind: &str, event: &Value) -> Vec<AgentEvent> {
+        match kind {
+            "assistant" => {
+                let blocks = event
+                    .pointer("/message/content")
+                    .and_then(Value::as_array)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                let mut events = Vec::new();
+                for block in blocks {
+                    match block.get("type").and_then(Value::as_str) {
+                        Some("text") => {
+                            if let Some(text) = str_at(block, "text").filter(|t| !t.is_empty()) {
+                                events.push(self.message(text.to_string()));
+                            }
+                        }
+                        Some("tool_use") => {
+                            let name = str_at(block, "name").unwrap_or_default();
+                            events.extend(tool_call(name, block.get("input").cloned()));
+                        }
+                        _ => {}
+                    }
+                }
+                events
+            }
+            "result" => {
+                let is_error = event.get("is_error").and_then(Value::as_bool).unwrap_or(false)
+                    || str_at(event, "subtype").is_some_and(|s| s != "success");
+                let text = match str_at(event, "result") {
+                    Some(text) => text.to_string(),
+                    None => str_at(event, "subtype").unwrap_or_default().to_string(),
+                };
+                vec![AgentEvent::Result { text, is_error }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn parse_codex(&mut self, kind: &str, event: &Value) -> Vec<AgentEvent> {
+        match kind {
+            "item.completed" => {
+                let item = match event.get("item") {
+                    Some(item) => item,
+                    None => return Vec::new(),
+                };
+                match str_at(item, "type").unwrap_or_default() {
+                    "agent_message" => match str_at(item, "text") {
+                        Some(text) => vec![self.message(text.to_string())],
+                        None => Vec::new(),
+                    },
+                    "command_execution" => tool_call(
+                        "shell",
+                        Some(serde_json::json!({ "command": item.get("command") })),
+                    ),
+                    "mcp_tool_call" => tool_call(
+                        str_at(item, "tool").unwrap_or_default(),
+                        item.get("arguments").cloned(),
+                    ),
+                    "web_search" => tool_call(
+                        "web_search",
+                        Some(serde_json::json!({ "query": item.get("query") })),
+                    ),
+                    "file_change" => item
+                        .get("changes")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|change| str_at(change, "path"))
+                        .map(|path| AgentEvent::FileEdit { path: path.to_string() })
+                        .collect(),
+                    _ => Vec::new(),
+                }
+            }
+            "turn.completed" => vec![AgentEvent::Result {
+                text: self.last_message.clone(),
+                is_error: false,
+            }],
+            "turn.failed" => vec![AgentEvent::Result {
+                text: event
+                    .pointer("/error/message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("turn failed")
+                    .to_string(),
+                is_error: true,
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn parse_gemini(&mut self, kind: &str, event: &Value) -> Vec<AgentEvent> {
+        match kind {
+            "message" if str_at(event, "role") == Some("assistant") => {
+                let text = str_at(event, "content").unwrap_or_default();
+                // Streamed messages arrive in pieces that make up one reply
+                if event.get("delta").and_then(Value::as_bool).unwrap_or(false) {
+                    self.last_message.push_str(text);
+                    vec![AgentEvent::AssistantMessage { text: text.to_string() }]
+                } else {
+                    vec![self.message(text.to_string())]
+                }
+            }
+            "tool_use" => {
+                // Whatever streams after a tool call is a new reply
+                self.last_message.clear();
+                tool_call(
+                    str_at(event, "tool_name").unwrap_or_default(),
+                    event.get("parameters").cloned(),
+                )
+            }
+            "result" => {
+                let is_error = str_at(event, "status").is_some_and(|s| s != "success");
+                let text = match event.pointer("/error/message").and_then(Value::as_str) {
+                    Some(error) if is_error => error.to_string(),
+                    _ => self.last_message.clone(),
+                };
+                vec![AgentEvent::Result { text, is_error }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn message(&mut self, text: String) -> AgentEvent {
+        self.last_message = text.clone();
+        AgentEvent::AssistantMessage { text }
+    }
+}
+
+fn str_at<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+/// A tool call, followed by a file edit for tools that write files
+fn tool_call(name: &str, input: Option<Value>) -> Vec<AgentEvent> {
+    let input = input.unwrap_or(Value::Null);
+    let edited = FILE_EDIT_TOOLS
+        .iter()
+        .find(|(tool, _)| *tool == name)
+        .and_then(|(_, field)| str_at(&input, field))
+        .map(|path| AgentEvent::FileEdit { path: path.to_string() });
+
+    let mut events = vec![AgentEvent::ToolCall {
+        name: name.to_string(),
+        input,
+    }];
+    events.extend(edited);
+    events
+}
+
+/// Everything needed to start a headless run
+pub struct HeadlessRequest {
+    pub adapter: AgentAdapter,
+    pub working_dir: String,
+    pub model: Option<String>,
+    pub prompt: String,
+    /// Keychain secrets passed to the agent as env vars
+    pub secret_env: HashMap<String, String>,
+    pub budget: BudgetLimits,
+    /// Usage the workspace had before this run
+    pub usage: BudgetUsage,
+}
+
+/// A running headless agent process
+pub struct HeadlessRun {
+    child: Arc<Mutex<Child>>,
+    /// The agent leads its own process group, so this is also its pgid
+    pid: u32,
+    /// Set once the agent has been waited for
+    exited: Arc<AtomicBool>,
+    /// Set by `kill`; the reader thread then stops emitting
+    killed: Arc<AtomicBool>,
+}
+
+impl HeadlessRun {
+    /// Terminate the agent's process group in the background: SIGTERM, then
+    /// SIGKILL once `grace` has passed, so tool subprocesses go too
+    pub fn kill(&self, grace: Duration) {
+        self.killed.store(true, Ordering::SeqCst);
+
+        #[cfg(unix)]
+        {
+            let pgid = self.pid as libc::pid_t;
+            let exited = self.exited.clone();
+            std::thread::spawn(move || {
+                // SAFETY: killpg has no memory-safety preconditions; ESRCH once
+                // the group is gone is expected and ignored
+                unsafe {
+                    libc::killpg(pgid, libc::SIGTERM);
+                }
+                let deadline = Instant::now() + grace;
+                while Instant::now() < deadline && !exited.load(Ordering::SeqCst) {
+                    std::thread::sleep(Duration::from_millis(EXIT_POLL_INTERVAL_MS));
+                }
+                // Also reaps grandchildren left behind in the group
+                // SAFETY: as above
+                unsafe {
+                    libc::killpg(pgid, libc::SIGKILL);
+                }
+            });
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = (self.pid, &self.exited, grace);
+            if let Ok(mut child) = self.child.lock() {
+                let _ = child.kill();
+            }
+        }
+    }
+}
+
+/// Start the agent in its structured mode and stream its events from a
+/// reader thread. When the process ends, the result is stored on the
+/// workspace and the run is cleared from the PTY manager.
+pub fn start(app: &AppHandle, workspace_id: &str, request: HeadlessRequest) -> Result<HeadlessRun, String> {
+    let adapter = &request.adapter;
+    let (command, args, stdin_prompt, format) =
+        adapter.build_headless_command(request.model.as_deref(), &request.prompt)?;
+
+    let mut cmd = Command::new(&command);
+    cmd.args(&args)
+        .current_dir(&request.working_dir)
+        .envs(adapter.env_vars(request.model.as_deref()))
+        .envs(&request.secret_env)
+        .stdin(if stdin_prompt.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Its own process group, so killing the run reaches tool subprocesses
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    if !adapter.config.limits.is_empty() {
+        if let Err(e) = resource_limits::apply_before_exec(&mut cmd, &adapter.config.limits) {
+            eprintln!("resource limits not applied for {workspace_id}: {e}");
+        }
+    }
+    let mut child = cmd.spawn().map_err(|e| format!("spawn error: {e}"))?;
+
+    let stdin = child.stdin.take();
+    let stdout = child.stdout.take().ok_or("stdout not captured")?;
+    let stderr = child.stderr.take().ok_or("stderr not captured")?;
+    let stderr_tail = std::thread::spawn(move || tail_lines(stderr));
+
+    // Usage the output doesn't attribute goes to the run's model
+    let extractor = adapter.usage_extractor();
+    let tracker = (!extractor.is_empty()).then(|| {
+        let model = request
+            .model
+            .as_deref()
+            .or(adapter.config.default_model.as_deref())
+            .unwrap_or(&adapter.name);
+        UsageTracker::new(extractor, model.to_string()).with_costs(adapter.model_costs())
+    });
+    // A one-shot run can't be interrupted and resumed, so a hard limit
+    // always stops it
+    let limits = BudgetLimits {
+        on_exceed: Some(BudgetAction::Stop),
+        ..request.budget.clone()
+    };
+    let watcher = BudgetWatcher::new(limits, request.usage, Instant::now());
+
+    let pid = child.id();
+    let child = Arc::new(Mutex::new(child));
+    let run = HeadlessRun {
+        child: child.clone(),
+        pid,
+        exited: Arc::new(AtomicBool::new(false)),
+        killed: Arc::new(AtomicBool::new(false)),
+    };
+    let exited = run.exited.clone();
+    let killed = run.killed.clone();
+    let app = app.clone();
+    let workspace_id = workspace_id.to_string();
+    std::thread::spawn(move || {
+        let lines = read_lines(stdout);
+        if let (Some(prompt), Some(stdin)) = (stdin_prompt, stdin) {
+            write_prompt(stdin, &prompt);
+        }
+
+        let mut stream = EventStream {
+            app: &app,
+            workspace_id: &workspace_id,
+            parser: EventParser::new(format),
+            tracker,
+            watcher,
+            killed,
+            result: None,
+        };
+        stream.pump(lines);
+        stream.record_runtime(Duration::ZERO);
+
+        let status = child.lock().map_err(|e| e.to_string()).and_then(|mut c| {
+            c.wait().map_err(|e| format!("wait error: {e}"))
+        });
+        exited.store(true, Ordering::SeqCst);
+        let stderr_tail = stderr_tail.join().unwrap_or_default();
+        let (text, is_error) = match (stream.result.take(), status) {
+            (Some(result), _) => result,
+            (None, Ok(status)) if status.success() => (stream.parser.last_message.clone(), false),
+            (None, Ok(status)) => {
+                let text = format!("agent exited with {status}\n{}", stderr_tail.join("\n"));
+                (text.trim().to_string(), true)
+            }
+            (None, Err(e)) => (e, true),
+        };
+        stream.finish(text, is_error);
+    });
+    Ok(run)
+}
+
+/// Closing stdin after the prompt tells the agent there is nothing more
+fn write_prompt(mut stdin: ChildStdin, prompt: &str) {
+    let _ = stdin.write_all(prompt.as_bytes());
+}
+
+/// Forward stdout lines from their own thread, so the pump can also act on
+/// time passing while the agent is silent
+fn read_lines(stdout: ChildStdout) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Last lines a stream writes before it closes
+fn tail_lines(stream: impl Read) -> Vec<String> {
+    let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+    tail.into()
+}
+
+/// Per-run state of the reader thread
+struct EventStream<'a> {
+    app: &'a AppHandle,
+    workspace_id: &'a str,
+    parser: EventParser,
+    tracker: Option<UsageTracker>,
+    watcher: BudgetWatcher,
+    /// Set once the run was killed and removed from the PTY manager
+    killed: Arc<AtomicBool>,
+    /// Text and error flag of the run's result event, once seen
+    result: Option<(String, bool)>,
+}
+
+impl EventStream<'_> {
+    fn pump(&mut self, lines: mpsc::Receiver<String>) {
+        let tick = Duration::from_millis(BUDGET_TICK_MS);
+        loop {
+            let line = match lines.recv_timeout(tick) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.check_budget();
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            // Output still draining from a killed run is dropped
+            if self.is_killed() {
+                continue;
+            }
+            for event in self.parser.parse_line(&line) {
+                if let AgentEvent::Result { text, is_error } = &event {
+                    self.result = Some((text.clone(), *is_error));
+                    continue;
+                }
+                self.emit(event);
+            }
+            self.record_usage(&line);
+        }
+    }
+
+    fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+    fn record_usage(&mut self, line: &str) {
+        let now_ms = transcript::now_ms();
+        let deltas = match self.tracker.as_mut() {
+            Some(tracker) => tracker.on_output(&format!("{line}\n"), now_ms),
+            None => return,
+        };
+        for (model, counts) in &deltas {
+            self.watcher.on_usage(counts);
+            self.emit(AgentEvent::Usage {
+                model: model.clone(),
+                counts: *counts,
+            });
+        }
+        usage::dispatch(self.app, self.workspace_id, now_ms, deltas);
+        self.check_budget();
+    }
+
+    fn check_budget(&mut self) {
+        if self.is_killed() {
+            return;
+        }
+        let now = Instant::now();
+        for alert in self.watcher.check(now) {
+            budget::dispatch(self.app, self.workspace_id, alert);
+        }
+        self.record_runtime(budget::RUNTIME_RECORD_INTERVAL);
+    }
+
+    /// Store the run's runtime once `min` of it has gathered
+    fn record_runtime(&mut self, min: Duration) {
+        if let Some(secs) = self.watcher.take_runtime(Instant::now(), min) {
+            budget::record_runtime(self.app, self.workspace_id, secs);
+        }
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        let _ = self
+            .app
+            .emit(&format!("agent-event-{}", self.workspace_id), event);
+    }
+
+    /// Emit the result last, store it and release the run. A killed run was
+    /// already released and its workspace updated by whoever killed it.
+    fn finish(&self, text: String, is_error: bool) {
+        self.emit(AgentEvent::Result {
+            text: text.clone(),
+            is_error,
+        });
+        if self.is_killed() {
+            return;
+        }
+        let result = HeadlessResult {
+            text,
+            is_error,
+            finished_at: transcript::now_ms(),
+        };
+        if let Some(ws_mgr) = self.app.try_state::<Mutex<WorkspaceManager>>() {
+            let stored = ws_mgr
+                .lock()
+                .map_err(|e| e.to_string())
+                .and_then(|mut ws| ws.finish_headless_run(self.workspace_id, result));
+            if let Err(e) = stored {
+                eprintln!("headless result not stored for {}: {e}", self.workspace_id);
+            }
+        }
+        if let Some(pty_mgr) = self.app.try_state::<Mutex<PtyManager>>() {
+            if let Ok(mut pty) = pty_mgr.lock() {
+                pty.headless_finished(self.workspace_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(format: EventFormat, lines: &[&str]) -> Vec<AgentEvent> {
+        let mut parser = EventParser::new(format);
+        lines.iter().flat_map(|line| parser.parse_line(line)).collect()
+    }
+
+    #[test]
+    fn test_claude_stream_json_events() {
+        let events = parse_all(
+            EventFormat::ClaudeJson,
+            &[
+                r#"{"type":"system","subtype":"init","session_id":"s1"}"#,
+                r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Fixing it"},{"type":"tool_use","id":"t1","name":"Edit","input":{"file_path":"src/main.rs"}}]}}"#,
+                "not json",
+                r#"{"type":"result","subtype":"success","is_error":false,"result":"Done","total_cost_usd":0.1}"#,
+            ],
+        );
+        assert_eq!(
+            events,
+            vec![
+                AgentEvent::AssistantMessage { text: "Fixing it".into() },
+                AgentEvent::ToolCall {
+                    name: "Edit".into(),
+                    input: serde_json::json!({"file_path": "src/main.rs"}),
+                },
+                AgentEvent::FileEdit { path: "src/main.rs".into() },
+                AgentEvent::Result { text: "Done".into(), is_error: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codex_result_is_last_agent_message() {
+        let events = parse_all(
+            EventFormat::CodexJson,
+            &[
+                r#"{"type":"item.started","item":{"id":"i0","type":"command_execution","command":"ls"}}"#,
+                r#"{"type":"item.completed","item":{"id":"i0","type":"command_execution","command":"ls"}}"#,
+                r#"{"type":"item.completed","item":{"id":"i1","type":"file_change","changes":[{"path":"a.rs","kind":"update"}]}}"#,
+                r#"{"type":"item.completed","item":{"id":"i2","type":"agent_message","text":"All set"}}"#,
+                r#"{"type":"turn.completed","usage":{"input_tokens":10,"output_tokens":2}}"#,
+            ],
+        );
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], AgentEvent::ToolCall {
+            name: "shell".into(),
+            input: serde_json::json!({"command": "ls"}),
+        });
+        assert_eq!(events[1], AgentEvent::FileEdit { path: "a.rs".into() });
+        assert_eq!(events[3], AgentEvent::Result { text: "All set".into(), is_error: false });
+
+        let failed = parse_all(
+            EventFormat::CodexJson,
+            &[r#"{"type":"turn.failed","error":{"message":"quota exceeded"}}"#],
+        );
+        assert_eq!(failed, vec![AgentEvent::Result { text: "quota exceeded".into(), is_error: true }]);
+    }
+
+    #[test]
+    fn test_gemini_joins_streamed_message_into_result() {
+        let events = parse_all(
+            EventFormat::GeminiJson,
+            &[
+                r#"{"type":"message","role":"user","content":"hi"}"#,
+                r#"{"type":"message","role":"assistant","content":"Writing it","delta":true}"#,
+                r#"{"type":"tool_use","tool_name":"write_file","tool_id":"t1","parameters":{"file_path":"b.txt"}}"#,
+                r#"{"type":"message","role":"assistant","content":"Hel","delta":true}"#,
+                r#"{"type":"message","role":"assistant","content":"lo","delta":true}"#,
+                r#"{"type":"result","status":"success","stats":{"input_tokens":5,"output_tokens":1}}"#,
+            ],
+        );
+        assert_eq!(events.len(), 6);
+        assert_eq!(events[2], AgentEvent::FileEdit { path: "b.txt".into() });
+        assert_eq!(events[5], AgentEvent::Result { text: "Hello".into(), is_error: false });
+    }
+}
@@ -0,0 +1,190 @@
+use serde::Deserialize;
+
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+/// One unit of input for `send_keys`
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyInput {
+    /// Literal text, written as-is (no newline appended)
+    Text { text: String },
+    /// Named key or chord, e.g. "ctrl-c", "esc", "up", "shift-tab"
+    Key { name: String },
+    /// Text wrapped in bracketed-paste markers so a multi-line prompt
+    /// arrives as one block instead of being submitted line by line
+    Paste { text: String },
+}
+
+/// Encode a sequence of inputs into the bytes a terminal would send
+pub fn encode(inputs: &[KeyInput]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    for input in inputs {
+        match input {
+            KeyInput::Text { text } => out.extend_from_slice(text.as_bytes()),
+            KeyInput::Key { name } => out.extend(encode_key(name)?),
+            KeyInput::Paste { text } => out.extend(bracketed_paste(text)),
+        }
+    }
+    Ok(out)
+}
+
+/// Wrap text in bracketed-paste markers. An end marker inside the text is
+/// stripped so pasted content can't break out of paste mode.
+pub fn bracketed_paste(text: &str) -> Vec<u8> {
+    let body = text.replace(PASTE_END, "");
+    format!("{PASTE_START}{body}{PASTE_END}").into_bytes()
+}
+
+/// Encode a named key (case-insensitive). Chords use `ctrl-` / `alt-`
+/// prefixes; `+` is accepted as a separator too ("ctrl+c"). A single
+/// character is sent as it is ("A", "+", " ").
+pub fn encode_key(name: &str) -> Result<Vec<u8>, String> {
+    if name.chars().count() == 1 {
+        return Ok(name.as_bytes().to_vec());
+    }
+    let trimmed = name.trim();
+    // Same length as `trimmed`, so its offsets index `trimmed` too
+    let normalized = trimmed.to_ascii_lowercase().replace('+', "-");
+
+    if let Some(rest) = normalized.strip_prefix("ctrl-") {
+        return ctrl_key(rest).ok_or_else(|| format!("unknown key: {name}"));
+    }
+    if normalized.starts_with("alt-") {
+        let mut seq = vec![0x1b];
+        seq.extend(encode_key(&trimmed["alt-".len()..])?);
+        return Ok(seq);
+    }
+
+    let seq: &[u8] = match normalized.as_str() {
+        "enter" | "return" => b"\r",
+        "tab" => b"\t",
+        "shift-tab" | "backtab" => b"\x1b[Z",
+        "esc" | "escape" => b"\x1b",
+        "backspace" => b"\x7f",
+        "delete" | "del" => b"\x1b[3~",
+        "insert" => b"\x1b[2~",
+        "space" => b" ",
+        "up" => b"\x1b[A",
+        "down" => b"\x1b[B",
+        "right" => b"\x1b[C",
+        "left" => b"\x1b[D",
+        "home" => b"\x1b[H",
+        "end" => b"\x1b[F",
+        "pageup" | "page-up" => b"\x1b[5~",
+        "pagedown" | "page-down" => b"\x1b[6~",
+        "f1" => b"\x1bOP",
+        "f2" => b"\x1bOQ",
+        "f3" => b"\x1bOR",
+        "f4" => b"\x1bOS",
+        _ if trimmed.chars().count() == 1 => return Ok(trimmed.as_bytes().to_vec()),
+        _ => return Err(format!("unknown key: {name}")),
+    };
+    Ok(seq.to_vec())
+}
+
+/// Control chord for a single key: ctrl-a..ctrl-z and the C0 punctuation chords
+fn ctrl_key(key: &str) -> Option<Vec<u8>> {
+    let byte = match key {
+        "space" | "@" => 0x00,
+        "[" => 0x1b,
+        "\\" => 0x1c,
+        "]" => 0x1d,
+        "^" => 0x1e,
+        "_" => 0x1f,
+        k if k.len() == 1 && k.as_bytes()[0].is_ascii_lowercase() => k.as_bytes()[0] & 0x1f,
+        _ => return None,
+    };
+    Some(vec![byte])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ctrl_c_is_etx() {
+        assert_eq!(encode_key("ctrl-c").unwrap(), vec![0x03]);
+        assert_eq!(encode_key("Ctrl+C").unwrap(), vec![0x03]);
+    }
+
+    #[test]
+    fn test_ctrl_letters_and_punctuation() {
+        assert_eq!(encode_key("ctrl-a").unwrap(), vec![0x01]);
+        assert_eq!(encode_key("ctrl-z").unwrap(), vec![0x1a]);
+        assert_eq!(encode_key("ctrl-[").unwrap(), vec![0x1b]);
+        assert_eq!(encode_key("ctrl-space").unwrap(), vec![0x00]);
+        assert!(encode_key("ctrl-1").is_err());
+    }
+
+    #[test]
+    fn test_named_keys() {
+        assert_eq!(encode_key("esc").unwrap(), b"\x1b");
+        assert_eq!(encode_key("enter").unwrap(), b"\r");
+        assert_eq!(encode_key("tab").unwrap(), b"\t");
+        assert_eq!(encode_key("shift-tab").unwrap(), b"\x1b[Z");
+        assert_eq!(encode_key("up").unwrap(), b"\x1b[A");
+        assert_eq!(encode_key("down").unwrap(), b"\x1b[B");
+        assert_eq!(encode_key("PageDown").unwrap(), b"\x1b[6~");
+    }
+
+    #[test]
+    fn test_alt_prefix_sends_escape_first() {
+        assert_eq!(encode_key("alt-b").unwrap(), b"\x1bb");
+        assert_eq!(encode_key("alt-enter").unwrap(), b"\x1b\r");
+        assert_eq!(encode_key("alt-B").unwrap(), b"\x1bB");
+        assert_eq!(encode_key("alt-+").unwrap(), b"\x1b+");
+    }
+
+    #[test]
+    fn test_single_character_passes_through() {
+        assert_eq!(encode_key("y").unwrap(), b"y");
+        assert_eq!(encode_key("1").unwrap(), b"1");
+        // Not lowercased or read as a separator
+        assert_eq!(encode_key("Y").unwrap(), b"Y");
+        assert_eq!(encode_key("+").unwrap(), b"+");
+        assert_eq!(encode_key(" ").unwrap(), b" ");
+    }
+
+    #[test]
+    fn test_unknown_key_is_error() {
+        let err = encode_key("hyper-x").unwrap_err();
+        assert!(err.contains("unknown key"));
+    }
+
+    #[test]
+    fn test_bracketed_paste_wraps_text() {
+        assert_eq!(
+            bracketed_paste("line 1\nline 2"),
+            b"\x1b[200~line 1\nline 2\x1b[201~".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_bracketed_paste_strips_embedded_end_marker() {
+        let pasted = bracketed_paste("a\x1b[201~rm -rf /\r");
+        assert_eq!(pasted, b"\x1b[200~arm -rf /\r\x1b[201~".to_vec());
+    }
+
+    #[test]
+    fn test_encode_sequence() {
+        let inputs = vec![
+            KeyInput::Key { name: "esc".into() },
+            KeyInput::Text { text: "hi".into() },
+            KeyInput::Paste { text: "x".into() },
+            KeyInput::Key { name: "enter".into() },
+        ];
+        assert_eq!(
+            encode(&inputs).unwrap(),
+            b"\x1bhi\x1b[200~x\x1b[201~\r".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_key_input_deserialize() {
+        let json = r#"[{"type": "key", "name": "ctrl-c"}, {"type": "paste", "text": "p"}]"#;
+        let inputs: Vec<KeyInput> = serde_json::from_str(json).unwrap();
+        assert_eq!(inputs[0], KeyInput::Key { name: "ctrl-c".into() });
+        assert_eq!(inputs[1], KeyInput::Paste { text: "p".into() });
+    }
+}
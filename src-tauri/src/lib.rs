@@ -1,16 +1,53 @@
 mod config;
+mod ansi;
+mod activity;
+mod approvals;
+mod budget;
 mod pty_manager;
+mod resource_limits;
+mod resume;
+mod screen;
+mod scrollback;
+mod search;
+#[cfg(unix)]
+mod session_daemon;
+mod spawn_queue;
+mod transcript;
+mod triggers;
+mod usage;
 mod agent;
+mod agent_health;
+mod headless;
 mod workspace;
 mod git_ops;
+mod handoff;
 mod snippets;
 mod trust;
 mod keychain;
+mod keys;
 mod shortcuts;
 mod commands;
 
 use tauri::Manager;
 
+/// Run as the session daemon when launched with `--session-daemon [socket]`.
+/// Returns false for a normal app launch.
+#[cfg(unix)]
+pub fn maybe_run_session_daemon() -> bool {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(session_daemon::DAEMON_FLAG) {
+        return false;
+    }
+    let socket = args
+        .next()
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(session_daemon::socket_path);
+    if let Err(e) = session_daemon::run(&socket) {
+        eprintln!("session daemon error: {e}");
+    }
+    true
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -22,17 +59,18 @@ pub fn run() {
             let config = config::ConfigStore::load_or_default();
             app.manage(std::sync::Mutex::new(config));
 
-            // Initialize PTY manager with max_sessions from config
-            let max_sessions = {
+            // Initialize PTY manager with session limits and output settings from config
+            let defaults = {
                 let cfg = app.state::<std::sync::Mutex<config::ConfigStore>>();
                 let store = cfg.lock().unwrap();
-                store.global.defaults.max_concurrent_agents
+                store.global.defaults.clone()
             };
-            let pty_mgr = pty_manager::PtyManager::new(app_handle, max_sessions);
+            let pty_mgr = pty_manager::PtyManager::new(app_handle, &defaults);
             app.manage(std::sync::Mutex::new(pty_mgr));
 
-            // Initialize workspace manager
-            let ws_mgr = workspace::WorkspaceManager::new();
+            // Workspace records (usage, budgets, conversations) always
+            // persist; the daemon only decides whether sessions are reattached
+            let ws_mgr = workspace::WorkspaceManager::load_or_default();
             app.manage(std::sync::Mutex::new(ws_mgr));
 
             // Initialize Keychain store (loads all secrets into memory cache)
@@ -43,6 +81,15 @@ pub fn run() {
             let shortcut_store = shortcuts::ShortcutStore::load_or_default();
             app.manage(std::sync::Mutex::new(shortcut_store));
 
+            // Reattach to sessions kept alive by the session daemon
+            if let Err(e) = commands::restore_sessions(
+                &app.state::<std::sync::Mutex<config::ConfigStore>>(),
+                &app.state::<std::sync::Mutex<workspace::WorkspaceManager>>(),
+                &app.state::<std::sync::Mutex<pty_manager::PtyManager>>(),
+            ) {
+                eprintln!("session restore failed: {e}");
+            }
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -55,8 +102,30 @@ pub fn run() {
             commands::stop_workspace,
             commands::remove_workspace,
             commands::list_workspaces,
+            commands::list_spawn_queue,
+            commands::cancel_queued_spawn,
+            commands::move_queued_spawn,
             commands::send_to_agent,
+            commands::send_keys,
+            commands::write_raw,
+            commands::resize_pty,
+            commands::get_pty_scrollback,
+            commands::get_screen_snapshot,
+            commands::open_terminal,
+            commands::list_terminals,
+            commands::close_terminal,
+            commands::get_transcript,
+            commands::export_transcript_asciicast,
+            commands::search_sessions,
+            commands::list_triggers,
+            commands::set_trigger_enabled,
+            commands::get_workspace_usage,
+            commands::get_usage_summary,
+            commands::list_pending_approvals,
+            commands::answer_approval,
+            commands::get_approval_log,
             commands::get_agents,
+            commands::check_agents,
             commands::run_snippet,
             commands::get_worktree_status,
             commands::get_diff,
@@ -77,8 +146,11 @@ pub fn run() {
             commands::list_shortcuts,
             commands::save_shortcuts,
             commands::switch_agent_model,
+            commands::restart_agent,
+            commands::switch_agent,
             commands::run_setup_and_start_agent,
             commands::start_agent_no_setup,
+            commands::run_headless,
         ])
         .run(tauri::generate_context!())
         .expect("error running ocestrater");
@@ -1,5 +1,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    #[cfg(unix)]
+    if ocestrater_lib::maybe_run_session_daemon() {
+        return;
+    }
     ocestrater_lib::run();
 }
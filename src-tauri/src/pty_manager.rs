@@ -1,47 +1,448 @@
+use crate::activity::{ActivityTracker, AgentActivity};
 use crate::agent::AgentAdapter;
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use crate::approvals::{self, ApprovalEngine, ApprovalRecord, PendingApproval};
+use crate::budget::{self, BudgetUsage, BudgetWatcher};
+use crate::config::{ApprovalRule, BudgetLimits, Defaults, OutputEncoding, TriggerRule};
+use crate::headless::{self, HeadlessRequest, HeadlessRun};
+use crate::resource_limits::{self, LimitHit, LimitWatcher};
+use crate::resume::{self, SessionIdCapture};
+use crate::screen::{ScreenSnapshot, TerminalScreen};
+use crate::scrollback::{Scrollback, ScrollbackSnapshot};
+use crate::search::{IndexedLine, LineIndex};
+#[cfg(unix)]
+use crate::session_daemon::{self, DaemonClient, DaemonRequest, DaemonSessionInfo, SpawnSpec};
+use crate::spawn_queue::{QueuedSpawnInfo, SpawnQueue};
+use crate::transcript::{self, TranscriptEntry, TranscriptWriter};
+use crate::triggers::{self, TriggerEngine, TriggerFired};
+use crate::usage::{self, UsageTracker};
+use crate::workspace::{WorkspaceManager, WorkspaceState};
+use base64::Engine;
+use portable_pty::{ChildKiller, CommandBuilder, ExitStatus, MasterPty, NativePtySystem, PtySize, PtySystem};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Emitter};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
 
 const BATCH_INTERVAL_MS: u64 = 16; // ~60fps batching
+const BATCH_MAX_BYTES: usize = 4096;
+const READ_BUF_SIZE: usize = 4096;
+/// How long the exit waiter lets the emitter drain final output before `pty-exit-*`
+const EXIT_FLUSH_TIMEOUT_MS: u64 = 500;
+const EXIT_POLL_INTERVAL_MS: u64 = 50;
+/// An agent resuming a conversation that exits with an error this soon
+/// couldn't resume it, and is started afresh
+const RESUME_FAILURE_WINDOW: Duration = Duration::from_secs(10);
+/// How often activity is re-evaluated while the output stream is quiet
+const ACTIVITY_TICK_MS: u64 = 250;
+const DEFAULT_ROWS: u16 = 40;
+const DEFAULT_COLS: u16 = 120;
+/// Terminal id of the workspace's agent session
+pub const AGENT_TERMINAL: &str = "agent";
 
 /// Represents a single PTY session for an agent
 pub struct PtySession {
+    backend: PtyBackend,
     writer: Box<dyn Write + Send>,
-    _child: Box<dyn portable_pty::Child + Send>,
+    pid: Option<u32>,
+    /// What the terminal runs, shown when listing terminals
+    label: String,
+    /// Agent sessions record their input here
+    transcript: Option<SharedTranscript>,
     pub alive: Arc<Mutex<bool>>,
+    /// Set when the session is stopped on purpose, so its exit isn't taken
+    /// for the agent dying
+    stopped: Arc<Mutex<bool>>,
+}
+
+type SharedTranscript = Arc<Mutex<TranscriptWriter>>;
+
+impl PtySession {
+    fn record_input(&self, data: String) {
+        record(
+            &self.transcript,
+            TranscriptEntry::Input {
+                time_ms: transcript::now_ms(),
+                data,
+            },
+        );
+    }
+}
+
+/// Append to a session's transcript, if it has one
+fn record(transcript: &Option<SharedTranscript>, entry: TranscriptEntry) {
+    if let Some(Ok(mut writer)) = transcript.as_ref().map(|t| t.lock()) {
+        writer.record(&entry);
+    }
+}
+
+/// Where a session's PTY lives: in this process, or in the session daemon
+enum PtyBackend {
+    Local {
+        master: Box<dyn MasterPty + Send>,
+        killer: Box<dyn ChildKiller + Send + Sync>,
+    },
+    #[cfg(unix)]
+    Daemon { client: DaemonClient, key: SessionKey },
+}
+
+impl PtyBackend {
+    fn resize(&self, size: PtySize) -> Result<(), String> {
+        match self {
+            PtyBackend::Local { master, .. } => {
+                master.resize(size).map_err(|e| format!("resize error: {e}"))
+            }
+            #[cfg(unix)]
+            PtyBackend::Daemon { client, key } => client
+                .request(&DaemonRequest::Resize {
+                    key: key.clone(),
+                    rows: size.rows,
+                    cols: size.cols,
+                })
+                .map(|_| ()),
+        }
+    }
+
+    /// Foreground process group of the terminal, when it can be queried
+    #[cfg(unix)]
+    fn foreground_group(&self) -> Option<libc::pid_t> {
+        match self {
+            PtyBackend::Local { master, .. } => master.process_group_leader(),
+            PtyBackend::Daemon { .. } => None,
+        }
+    }
+
+    fn force_kill(&mut self) {
+        match self {
+            PtyBackend::Local { killer, .. } => {
+                let _ = killer.kill();
+            }
+            #[cfg(unix)]
+            PtyBackend::Daemon { client, key } => {
+                let _ = client.request(&DaemonRequest::Kill { key: key.clone() });
+            }
+        }
+    }
+}
+
+/// A started process and the handles the session plumbing needs
+struct SessionIo {
+    backend: PtyBackend,
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+    pid: Option<u32>,
+    /// Leading output bytes that replay what an earlier attach already saw
+    replayed: usize,
+    /// Blocks until the process exits
+    wait: Box<dyn FnOnce() -> ExitInfo + Send>,
+}
+
+/// Identifies a PTY session: the agent (`AGENT_TERMINAL`) or one of the
+/// workspace's extra terminals
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SessionKey {
+    pub workspace_id: String,
+    pub terminal_id: String,
+}
+
+impl SessionKey {
+    pub fn new(workspace_id: &str, terminal_id: Option<&str>) -> Self {
+        Self {
+            workspace_id: workspace_id.to_string(),
+            terminal_id: terminal_id.unwrap_or(AGENT_TERMINAL).to_string(),
+        }
+    }
+
+    pub fn agent(workspace_id: &str) -> Self {
+        Self::new(workspace_id, None)
+    }
+
+    pub fn is_agent(&self) -> bool {
+        self.terminal_id == AGENT_TERMINAL
+    }
+
+    /// Suffix of the session's `pty-output-*` / `pty-exit-*` events: the
+    /// workspace id for the agent, `{workspace_id}:{terminal_id}` otherwise
+    pub fn event_id(&self) -> String {
+        if self.is_agent() {
+            self.workspace_id.clone()
+        } else {
+            format!("{}:{}", self.workspace_id, self.terminal_id)
+        }
+    }
+}
+
+/// A workspace terminal as reported to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TerminalInfo {
+    pub workspace_id: String,
+    pub terminal_id: String,
+    pub label: String,
+    pub alive: bool,
+}
+
+/// How the agent process ended, carried by the `pty-exit-*` event
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExitInfo {
+    /// Exit code when the process exited normally
+    pub code: Option<u32>,
+    /// Signal description when the process was terminated by a signal
+    pub signal: Option<String>,
+}
+
+impl ExitInfo {
+    pub fn from_status(status: &ExitStatus) -> Self {
+        // portable-pty only exposes the signal through its Display impl
+        let text = status.to_string();
+        match text.strip_prefix("Terminated by ") {
+            Some(signal) => Self {
+                code: None,
+                signal: Some(signal.to_string()),
+            },
+            None => Self {
+                code: Some(status.exit_code()),
+                signal: None,
+            },
+        }
+    }
+}
+
+/// Everything needed to start an agent, kept while its spawn is queued
+pub struct SpawnRequest {
+    pub adapter: AgentAdapter,
+    pub working_dir: String,
+    pub model: Option<String>,
+    pub secret_env: HashMap<String, String>,
+    pub triggers: Vec<TriggerRule>,
+    /// Triggers switched off for this workspace
+    pub disabled_triggers: Vec<String>,
+    pub budget: BudgetLimits,
+    /// What the workspace has used so far, counted against `budget`
+    pub usage: BudgetUsage,
+    /// Task the agent starts on
+    pub prompt: Option<String>,
+    /// Rules answering the agent's permission prompts in `ask` mode
+    pub approval_rules: Vec<ApprovalRule>,
+}
+
+/// Whether `spawn_or_queue` started the agent or had to queue it
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SpawnOutcome {
+    Started,
+    Queued { position: usize },
+}
+
+/// Consumers that only apply to agent sessions
+#[derive(Clone)]
+struct AgentHooks {
+    activity: Arc<Mutex<ActivityTracker>>,
+    limits: Arc<Mutex<LimitWatcher>>,
+    transcript: Option<SharedTranscript>,
+    triggers: Option<Arc<Mutex<TriggerEngine>>>,
+    usage: Option<Arc<Mutex<UsageTracker>>>,
+    budget: Option<Arc<Mutex<BudgetWatcher>>>,
+    approvals: Option<Arc<Mutex<ApprovalEngine>>>,
+    session_id: Option<Arc<Mutex<SessionIdCapture>>>,
+    /// Name of the agent, whose conversation a captured id belongs to
+    agent: String,
+    /// The agent was started to resume a stored conversation
+    resumed: bool,
+    /// Prompt typed in once the agent first waits for input, for agents
+    /// that take it on stdin
+    initial_input: Arc<Mutex<Option<String>>>,
+}
+
+/// Grace periods for staged termination: SIGINT, then SIGTERM, then SIGKILL
+#[derive(Debug, Clone, Copy)]
+struct TerminationGrace {
+    interrupt: Duration,
+    terminate: Duration,
 }
 
 /// Manages all PTY sessions
 pub struct PtyManager {
-    sessions: HashMap<String, PtySession>,
+    sessions: HashMap<SessionKey, PtySession>,
+    /// Last known terminal size per session, kept across respawns
+    sizes: HashMap<SessionKey, PtySize>,
+    /// Output history per session; outlives sessions so it can be replayed
+    scrollbacks: HashMap<SessionKey, Arc<Mutex<Scrollback>>>,
+    /// Emulated terminal screen per session, kept after exit
+    screens: HashMap<SessionKey, Arc<Mutex<TerminalScreen>>>,
+    /// Searchable text of sessions that aren't recorded to a transcript
+    line_indexes: HashMap<SessionKey, Arc<Mutex<LineIndex>>>,
+    /// Output triggers per workspace, shared with its agent's output thread
+    trigger_engines: HashMap<String, Arc<Mutex<TriggerEngine>>>,
+    /// Permission prompt answering per workspace, shared the same way
+    approval_engines: HashMap<String, Arc<Mutex<ApprovalEngine>>>,
+    /// Conversation id capture of each workspace's latest agent session
+    session_ids: HashMap<String, Arc<Mutex<SessionIdCapture>>>,
+    /// Prompts for the next agent spawn of a workspace
+    initial_prompts: HashMap<String, String>,
+    /// Budget per workspace with the usage it had when the budget was set
+    budgets: HashMap<String, (BudgetLimits, BudgetUsage)>,
+    /// Agents running without a terminal, by workspace
+    headless_runs: HashMap<String, HeadlessRun>,
+    /// Activity of each workspace's latest session, kept after exit
+    activities: HashMap<String, Arc<Mutex<ActivityTracker>>>,
+    /// Spawns waiting for a free slot under `max_sessions`
+    queue: SpawnQueue<SpawnRequest>,
     app_handle: AppHandle,
     max_sessions: usize,
+    output_encoding: OutputEncoding,
+    scrollback_bytes: usize,
+    grace: TerminationGrace,
+    idle_after: Duration,
+    /// Where agent transcripts are written; `None` disables recording
+    transcripts_dir: Option<PathBuf>,
+    /// Set when sessions are hosted by the session daemon
+    #[cfg(unix)]
+    daemon: Option<DaemonClient>,
 }
 
 impl PtyManager {
-    pub fn new(app_handle: AppHandle, max_sessions: usize) -> Self {
+    pub fn new(app_handle: AppHandle, defaults: &Defaults) -> Self {
         Self {
             sessions: HashMap::new(),
+            sizes: HashMap::new(),
+            scrollbacks: HashMap::new(),
+            screens: HashMap::new(),
+            line_indexes: HashMap::new(),
+            activities: HashMap::new(),
+            trigger_engines: HashMap::new(),
+            approval_engines: HashMap::new(),
+            session_ids: HashMap::new(),
+            budgets: HashMap::new(),
+            initial_prompts: HashMap::new(),
+            headless_runs: HashMap::new(),
+            queue: SpawnQueue::new(),
             app_handle,
-            max_sessions,
+            max_sessions: defaults.max_concurrent_agents,
+            output_encoding: defaults.output_encoding,
+            scrollback_bytes: defaults.scrollback_bytes,
+            grace: TerminationGrace {
+                interrupt: Duration::from_millis(defaults.interrupt_grace_ms),
+                terminate: Duration::from_millis(defaults.terminate_grace_ms),
+            },
+            idle_after: Duration::from_millis(defaults.idle_after_ms),
+            transcripts_dir: defaults.record_transcripts.then(transcript::transcripts_dir),
+            #[cfg(unix)]
+            daemon: defaults
+                .session_daemon
+                .then(|| DaemonClient::new(session_daemon::socket_path())),
         }
     }
 
-    /// Remove dead sessions (naturally exited processes) to free up slots
+    /// Remove dead agent sessions (naturally exited processes) to free up
+    /// slots. Exited extra terminals stay listed until they are closed.
     fn prune_dead_sessions(&mut self) {
-        let dead_ids: Vec<String> = self
+        let dead: Vec<SessionKey> = self
             .sessions
             .iter()
-            .filter(|(_, s)| s.alive.lock().map(|a| !*a).unwrap_or(true))
-            .map(|(id, _)| id.clone())
+            .filter(|(key, s)| key.is_agent() && s.alive.lock().map(|a| !*a).unwrap_or(true))
+            .map(|(key, _)| key.clone())
             .collect();
 
-        for id in dead_ids {
-            self.sessions.remove(&id);
+        for key in dead {
+            self.sessions.remove(&key);
+        }
+    }
+
+    /// Agent sessions count against `max_sessions`; extra terminals don't
+    fn agent_session_count(&self) -> usize {
+        self.sessions.keys().filter(|key| key.is_agent()).count()
+    }
+
+    fn has_free_slot(&mut self) -> bool {
+        self.prune_dead_sessions();
+        self.agent_session_count() < self.max_sessions
+    }
+
+    /// Spawn immediately if a slot is free, otherwise queue the spawn. Queued
+    /// spawns start automatically as sessions exit (see `start_queued_spawns`).
+    pub fn spawn_or_queue(
+        &mut self,
+        workspace_id: &str,
+        request: SpawnRequest,
+        priority: i32,
+    ) -> Result<SpawnOutcome, String> {
+        if self.queue.contains(workspace_id) {
+            return Err(format!("spawn already queued: {workspace_id}"));
+        }
+        if self.headless_runs.contains_key(workspace_id) {
+            return Err(format!("headless run in progress: {workspace_id}"));
+        }
+
+        if self.has_free_slot() {
+            self.spawn_request(workspace_id, &request)?;
+            return Ok(SpawnOutcome::Started);
+        }
+
+        let position = self.queue.push(workspace_id, priority, request)?;
+        self.emit_queue_changed();
+        Ok(SpawnOutcome::Queued { position })
+    }
+
+    /// Start queued spawns while slots are free, returning each started
+    /// workspace with the result of its spawn
+    pub fn start_queued(&mut self) -> Vec<(String, Result<(), String>)> {
+        let mut started = Vec::new();
+        while !self.queue.is_empty() && self.has_free_slot() {
+            if let Some((workspace_id, request)) = self.queue.pop() {
+                let result = self.spawn_request(&workspace_id, &request);
+                started.push((workspace_id, result));
+            }
+        }
+        if !started.is_empty() {
+            self.emit_queue_changed();
+        }
+        started
+    }
+
+    /// Remove a workspace's queued spawn; returns false if it wasn't queued
+    pub fn cancel_queued(&mut self, workspace_id: &str) -> bool {
+        let cancelled = self.queue.remove(workspace_id).is_some();
+        if cancelled {
+            self.emit_queue_changed();
         }
+        cancelled
+    }
+
+    /// Move a queued spawn to a 1-based position, returning where it landed
+    pub fn move_queued(&mut self, workspace_id: &str, position: usize) -> Result<usize, String> {
+        let position = self.queue.move_to(workspace_id, position)?;
+        self.emit_queue_changed();
+        Ok(position)
+    }
+
+    pub fn queue_position(&self, workspace_id: &str) -> Option<usize> {
+        self.queue.position(workspace_id)
+    }
+
+    pub fn queued_spawns(&self) -> Vec<QueuedSpawnInfo> {
+        self.queue.list()
+    }
+
+    fn emit_queue_changed(&self) {
+        let _ = self.app_handle.emit("spawn-queue-changed", self.queue.list());
+    }
+
+    fn spawn_request(&mut self, workspace_id: &str, request: &SpawnRequest) -> Result<(), String> {
+        self.set_triggers(workspace_id, &request.triggers, &request.disabled_triggers);
+        self.set_approvals(workspace_id, &request.adapter, &request.approval_rules);
+        self.set_budget(workspace_id, &request.budget, request.usage);
+        self.set_initial_prompt(workspace_id, request.prompt.as_deref());
+        self.spawn(
+            workspace_id,
+            &request.adapter,
+            &request.working_dir,
+            request.model.as_deref(),
+            Some(&request.secret_env),
+        )
     }
 
     /// Spawn a new PTY session for a workspace
@@ -56,123 +457,536 @@ impl PtyManager {
         // Clean up naturally-exited sessions before checking capacity
         self.prune_dead_sessions();
 
-        if self.sessions.len() >= self.max_sessions {
+        if self.agent_session_count() >= self.max_sessions {
             return Err(format!(
                 "maximum concurrent agents reached ({}). Stop an existing agent to create a new one.",
                 self.max_sessions
             ));
         }
 
-        if self.sessions.contains_key(workspace_id) {
+        let key = SessionKey::agent(workspace_id);
+        if self.sessions.contains_key(&key) {
             return Err(format!("session already exists: {workspace_id}"));
         }
 
-        let pty_system = NativePtySystem::default();
-        let pair = pty_system
-            .openpty(PtySize {
-                rows: 40,
-                cols: 120,
-                pixel_width: 0,
-                pixel_height: 0,
+        let prompt = self.initial_prompts.remove(workspace_id);
+        let (command, args, stdin_prompt) = adapter.build_command_with_prompt(model, prompt.as_deref());
+        let label = std::iter::once(&command).chain(&args).cloned().collect::<Vec<_>>().join(" ");
+        // Limits are set before the agent execs; everything it forks
+        // inherits them
+        let limits = &adapter.config.limits;
+        let (program, program_args) = if limits.is_empty() {
+            (command, args)
+        } else {
+            resource_limits::wrap(&command, &args, limits).unwrap_or_else(|e| {
+                eprintln!("resource limits not applied for {workspace_id}: {e}");
+                (command, args)
             })
-            .map_err(|e| format!("pty open error: {e}"))?;
-
-        let (command, args) = adapter.build_command(model);
-        let mut cmd = CommandBuilder::new(&command);
-        cmd.args(&args);
+        };
+        let mut cmd = CommandBuilder::new(&program);
+        cmd.args(&program_args);
         cmd.cwd(working_dir);
 
         // Set env vars from adapter
-        for (k, v) in adapter.env_vars() {
+        for (k, v) in adapter.env_vars(model) {
             cmd.env(k, v);
         }
+        apply_session_env(&mut cmd, secret_env);
+
+        let hooks = self.agent_hooks(&key, adapter, model, working_dir, &label, false);
+        if let Ok(mut input) = hooks.initial_input.lock() {
+            *input = stdin_prompt;
+        }
+        self.start_session(key, cmd, label, Some(hooks))
+    }
+
+    /// Open an extra terminal in a workspace: the user's shell, or `command`
+    /// run through it (e.g. a dev server). It shares the agent's secret env
+    /// but not its slot, activity tracking or limits.
+    pub fn open_terminal(
+        &mut self,
+        workspace_id: &str,
+        working_dir: &str,
+        command: Option<&str>,
+        secret_env: Option<&std::collections::HashMap<String, String>>,
+    ) -> Result<TerminalInfo, String> {
+        let terminal_id = format!("term-{}", &uuid::Uuid::new_v4().to_string()[..8]);
+        let key = SessionKey::new(workspace_id, Some(&terminal_id));
+
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
+        let mut cmd = CommandBuilder::new(&shell);
+        if let Some(command) = command {
+            cmd.args(["-c", command]);
+        }
+        cmd.cwd(working_dir);
+        apply_session_env(&mut cmd, secret_env);
 
-        // Inject Keychain secrets as environment variables
-        if let Some(secrets) = secret_env {
-            for (k, v) in secrets {
-                cmd.env(k, v);
+        let label = command.unwrap_or(&shell).to_string();
+        self.start_session(key.clone(), cmd, label.clone(), None)?;
+
+        Ok(TerminalInfo {
+            workspace_id: key.workspace_id,
+            terminal_id: key.terminal_id,
+            label,
+            alive: true,
+        })
+    }
+
+    /// Terminals of a workspace, agent first, extra terminals by id
+    pub fn list_terminals(&self, workspace_id: &str) -> Vec<TerminalInfo> {
+        let mut terminals: Vec<TerminalInfo> = self
+            .sessions
+            .iter()
+            .filter(|(key, _)| key.workspace_id == workspace_id)
+            .map(|(key, s)| TerminalInfo {
+                workspace_id: key.workspace_id.clone(),
+                terminal_id: key.terminal_id.clone(),
+                label: s.label.clone(),
+                alive: s.alive.lock().map(|a| *a).unwrap_or(false),
+            })
+            .collect();
+        terminals.sort_by(|a, b| {
+            (a.terminal_id != AGENT_TERMINAL, &a.terminal_id)
+                .cmp(&(b.terminal_id != AGENT_TERMINAL, &b.terminal_id))
+        });
+        terminals
+    }
+
+    /// Close an extra terminal and drop its scrollback
+    pub fn close_terminal(&mut self, workspace_id: &str, terminal_id: &str) -> Result<(), String> {
+        if terminal_id == AGENT_TERMINAL {
+            return Err("the agent terminal is closed by stopping the workspace".into());
+        }
+        let key = SessionKey::new(workspace_id, Some(terminal_id));
+        let session = self
+            .sessions
+            .remove(&key)
+            .ok_or_else(|| format!("no terminal: {terminal_id}"))?;
+        self.sizes.remove(&key);
+        self.scrollbacks.remove(&key);
+        self.screens.remove(&key);
+        self.line_indexes.remove(&key);
+
+        let grace = self.grace;
+        std::thread::spawn(move || terminate_session(session, grace));
+        Ok(())
+    }
+
+    /// Close every extra terminal of a workspace
+    pub fn close_terminals(&mut self, workspace_id: &str) {
+        let terminal_ids: Vec<String> = self
+            .sessions
+            .keys()
+            .filter(|key| key.workspace_id == workspace_id && !key.is_agent())
+            .map(|key| key.terminal_id.clone())
+            .collect();
+        for terminal_id in terminal_ids {
+            let _ = self.close_terminal(workspace_id, &terminal_id);
+        }
+    }
+
+    /// Fresh activity tracker, limit watcher, transcript and usage tracker
+    /// for an agent session
+    fn agent_hooks(
+        &mut self,
+        key: &SessionKey,
+        adapter: &AgentAdapter,
+        model: Option<&str>,
+        working_dir: &str,
+        label: &str,
+        reattached: bool,
+    ) -> AgentHooks {
+        let activity = Arc::new(Mutex::new(ActivityTracker::new(
+            &adapter.config.prompt_patterns,
+            self.idle_after,
+            Instant::now(),
+        )));
+        self.activities
+            .insert(key.workspace_id.clone(), activity.clone());
+
+        let size = self.size_for(key);
+        let transcript = self.open_transcript(
+            &key.workspace_id,
+            TranscriptEntry::Start {
+                time_ms: transcript::now_ms(),
+                agent: adapter.name.clone(),
+                label: label.to_string(),
+                working_dir: working_dir.to_string(),
+                cols: size.cols,
+                rows: size.rows,
+                reattached,
+            },
+        );
+
+        // Usage the output doesn't attribute goes to the session's model
+        let extractor = adapter.usage_extractor();
+        let usage = (!extractor.is_empty()).then(|| {
+            let model = model
+                .or(adapter.config.default_model.as_deref())
+                .unwrap_or(&adapter.name);
+            let tracker = UsageTracker::new(extractor, model.to_string()).with_costs(adapter.model_costs());
+            Arc::new(Mutex::new(tracker))
+        });
+
+        let budget = self
+            .budgets
+            .get(&key.workspace_id)
+            .map(|(limits, used)| Arc::new(Mutex::new(BudgetWatcher::new(limits.clone(), *used, Instant::now()))));
+
+        let session_id = adapter.config.descriptor.session_id.as_ref().and_then(|source| {
+            match SessionIdCapture::new(source, working_dir, SystemTime::now()) {
+                Ok(capture) => Some(Arc::new(Mutex::new(capture))),
+                Err(e) => {
+                    eprintln!("session id capture off for {}: {e}", key.workspace_id);
+                    None
+                }
+            }
+        });
+        match &session_id {
+            Some(capture) => {
+                self.session_ids
+                    .insert(key.workspace_id.clone(), capture.clone());
             }
+            None => {
+                self.session_ids.remove(&key.workspace_id);
+            }
+        }
+
+        AgentHooks {
+            activity,
+            limits: Arc::new(Mutex::new(LimitWatcher::new(adapter.config.limits.clone()))),
+            transcript,
+            triggers: self.trigger_engines.get(&key.workspace_id).cloned(),
+            approvals: self.approval_engines.get(&key.workspace_id).cloned(),
+            session_id,
+            agent: adapter.name.clone(),
+            resumed: adapter.resume_session.is_some() && !reattached,
+            usage,
+            budget,
+            initial_input: Arc::new(Mutex::new(None)),
         }
+    }
 
-        // Force color output
-        cmd.env("FORCE_COLOR", "1");
-        cmd.env("TERM", "xterm-256color");
+    /// Install the output triggers used by a workspace's next agent session
+    pub fn set_triggers(&mut self, workspace_id: &str, rules: &[TriggerRule], disabled: &[String]) {
+        let engine = TriggerEngine::new(rules, disabled);
+        if engine.is_empty() {
+            self.trigger_engines.remove(workspace_id);
+        } else {
+            self.trigger_engines
+                .insert(workspace_id.to_string(), Arc::new(Mutex::new(engine)));
+        }
+    }
 
-        let child = pair
-            .slave
-            .spawn_command(cmd)
-            .map_err(|e| format!("spawn error: {e}"))?;
+    /// Answer the permission prompts of a workspace's next agent session by
+    /// `rules`; agents that aren't in `ask` mode are left alone
+    pub fn set_approvals(&mut self, workspace_id: &str, adapter: &AgentAdapter, rules: &[ApprovalRule]) {
+        self.approval_engines.remove(workspace_id);
+        let prompt = match adapter.approval_prompt() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        match ApprovalEngine::new(workspace_id, prompt, rules) {
+            Ok(engine) => {
+                self.approval_engines
+                    .insert(workspace_id.to_string(), Arc::new(Mutex::new(engine)));
+            }
+            Err(e) => eprintln!("approvals off for {workspace_id}: {e}"),
+        }
+    }
 
-        let reader = pair
-            .master
-            .try_clone_reader()
-            .map_err(|e| format!("reader clone error: {e}"))?;
-        let writer = pair
-            .master
-            .take_writer()
-            .map_err(|e| format!("writer error: {e}"))?;
+    /// Prompts waiting for the user, across workspaces
+    pub fn pending_approvals(&self) -> Vec<PendingApproval> {
+        self.approval_engines
+            .values()
+            .filter_map(|engine| engine.lock().ok().and_then(|e| e.pending().cloned()))
+            .collect()
+    }
+
+    /// Answer a prompt that waits for the user
+    pub fn answer_approval(&mut self, workspace_id: &str, id: &str, approve: bool) -> Result<ApprovalRecord, String> {
+        let engine = self
+            .approval_engines
+            .get(workspace_id)
+            .cloned()
+            .ok_or_else(|| format!("no pending approval: {id}"))?;
+        let (record, keys) = engine
+            .lock()
+            .map_err(|e| e.to_string())?
+            .answer(id, approve, Instant::now())?;
+        self.write_raw(&SessionKey::agent(workspace_id), keys.as_bytes())?;
+        Ok(record)
+    }
+
+    /// Input to an agent answers a prompt it waits on in the terminal
+    fn input_written(&self, workspace_id: &str) {
+        let cleared = self
+            .approval_engines
+            .get(workspace_id)
+            .and_then(|engine| engine.lock().ok()?.on_input());
+        if let Some(cleared) = cleared {
+            approvals::dispatch_answered(&self.app_handle, cleared);
+        }
+    }
+
+    /// Id of the conversation the workspace's latest agent session had, as
+    /// far as it can be told yet
+    pub fn session_id(&self, workspace_id: &str) -> Option<String> {
+        self.session_ids
+            .get(workspace_id)
+            .and_then(|c| c.lock().ok().and_then(|c| c.session_id()))
+    }
+
+    /// Prompt the next agent spawn of a workspace starts on
+    pub fn set_initial_prompt(&mut self, workspace_id: &str, prompt: Option<&str>) {
+        match prompt {
+            Some(prompt) => {
+                self.initial_prompts
+                    .insert(workspace_id.to_string(), prompt.to_string());
+            }
+            None => {
+                self.initial_prompts.remove(workspace_id);
+            }
+        }
+    }
+
+    /// Install the budget checked by a workspace's next agent session; `used`
+    /// is what the workspace has used before that session. Kept without
+    /// limits too, so the session's runtime is still counted.
+    pub fn set_budget(&mut self, workspace_id: &str, limits: &BudgetLimits, used: BudgetUsage) {
+        self.budgets
+            .insert(workspace_id.to_string(), (limits.clone(), used));
+    }
+
+    /// Switch a trigger on or off for a workspace's running agent
+    pub fn set_trigger_enabled(&mut self, workspace_id: &str, trigger: &str, enabled: bool) {
+        if let Some(Ok(mut engine)) = self.trigger_engines.get(workspace_id).map(|e| e.lock()) {
+            engine.set_enabled(trigger, enabled);
+        }
+    }
+
+    fn open_transcript(&self, workspace_id: &str, start: TranscriptEntry) -> Option<SharedTranscript> {
+        let dir = self.transcripts_dir.as_ref()?;
+        let opened = transcript::transcript_path(dir, workspace_id)
+            .and_then(|path| TranscriptWriter::open(&path));
+        match opened {
+            Ok(mut writer) => {
+                writer.record(&start);
+                Some(Arc::new(Mutex::new(writer)))
+            }
+            Err(e) => {
+                eprintln!("transcript disabled for {workspace_id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Start `cmd` in a PTY (local or daemon-hosted) and wire up the session
+    fn start_session(
+        &mut self,
+        key: SessionKey,
+        cmd: CommandBuilder,
+        label: String,
+        agent: Option<AgentHooks>,
+    ) -> Result<(), String> {
+        let size = self.size_for(&key);
+        #[cfg(unix)]
+        let io = match &self.daemon {
+            Some(client) => spawn_in_daemon(client, &key, &cmd, size, &label)?,
+            None => spawn_local(cmd, size)?,
+        };
+        #[cfg(not(unix))]
+        let io = spawn_local(cmd, size)?;
+
+        self.run_session(key, io, label, agent);
+        Ok(())
+    }
+
+    /// Start the reader, emitter and exit waiter for a started process
+    fn run_session(&mut self, key: SessionKey, io: SessionIo, label: String, agent: Option<AgentHooks>) {
+        let SessionIo {
+            backend,
+            reader,
+            writer,
+            pid,
+            replayed,
+            wait,
+        } = io;
 
         let alive = Arc::new(Mutex::new(true));
         let alive_clone = alive.clone();
-        let ws_id = workspace_id.to_string();
+        let stopped = Arc::new(Mutex::new(false));
+        let stopped_clone = stopped.clone();
         let handle = self.app_handle.clone();
+        let scrollback_bytes = self.scrollback_bytes;
+        let scrollback = self
+            .scrollbacks
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(Mutex::new(Scrollback::new(scrollback_bytes))))
+            .clone();
+        // Each run starts from a blank screen
+        let size = self.size_for(&key);
+        let screen = Arc::new(Mutex::new(TerminalScreen::new(size.rows, size.cols)));
+        self.screens.insert(key.clone(), screen.clone());
+        let transcript = agent.as_ref().and_then(|hooks| hooks.transcript.clone());
+        let line_index = transcript.is_none().then(|| {
+            self.line_indexes
+                .entry(key.clone())
+                .or_insert_with(|| Arc::new(Mutex::new(LineIndex::new(scrollback_bytes))))
+                .clone()
+        });
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let (pump_done_tx, pump_done_rx) = mpsc::channel::<()>();
 
-        // Spawn reader thread with batched IPC
+        // Reader thread: forward raw byte chunks exactly as the agent wrote them
+        std::thread::spawn(move || read_raw(reader, tx));
+
+        // Emitter thread: batch chunks into IPC events and feed the trackers
+        let ctx = OutputContext {
+            app: handle.clone(),
+            key: key.clone(),
+            encoding: self.output_encoding,
+            scrollback,
+            screen,
+            agent: agent.clone(),
+            transcript_skip: Cell::new(replayed),
+            line_index,
+        };
         std::thread::spawn(move || {
-            let buf_reader = BufReader::new(reader);
-            let mut batch = String::new();
-            let mut last_flush = std::time::Instant::now();
-
-            for line in buf_reader.lines() {
-                match line {
-                    Ok(text) => {
-                        batch.push_str(&text);
-                        batch.push('\n');
-
-                        let elapsed = last_flush.elapsed().as_millis() as u64;
-                        if elapsed >= BATCH_INTERVAL_MS || batch.len() > 4096 {
-                            let _ = handle.emit(
-                                &format!("pty-output-{}", ws_id),
-                                batch.clone(),
-                            );
-                            batch.clear();
-                            last_flush = std::time::Instant::now();
-                        }
-                    }
-                    Err(_) => break,
-                }
-            }
+            let _done = pump_done_tx;
+            pump_output(rx, &ctx);
+        });
 
-            // Flush remaining
-            if !batch.is_empty() {
-                let _ = handle.emit(&format!("pty-output-{}", ws_id), batch);
-            }
+        let exit_key = key.clone();
+        let started = Instant::now();
+
+        // Exit waiter: reap the child and report how it ended. Grandchildren
+        // may keep the PTY open, so EOF alone can't signal exit.
+        std::thread::spawn(move || {
+            let info = wait();
+
+            let _ = pump_done_rx.recv_timeout(Duration::from_millis(EXIT_FLUSH_TIMEOUT_MS));
 
-            // Mark session as dead
             if let Ok(mut a) = alive_clone.lock() {
                 *a = false;
             }
-            let _ = handle.emit(&format!("pty-exit-{}", ws_id), ());
+            let ws_id = &exit_key.workspace_id;
+            if let Some(hooks) = &agent {
+                record(
+                    &hooks.transcript,
+                    TranscriptEntry::Exit {
+                        time_ms: transcript::now_ms(),
+                        code: info.code,
+                        signal: info.signal.clone(),
+                    },
+                );
+                update_activity(&handle, ws_id, &hooks.activity, |t| t.on_exit());
+                let runtime = hooks
+                    .budget
+                    .as_ref()
+                    .and_then(|w| w.lock().ok()?.take_runtime(Instant::now(), Duration::ZERO));
+                if let Some(secs) = runtime {
+                    budget::record_runtime(&handle, ws_id, secs);
+                }
+                let hit = hooks
+                    .limits
+                    .lock()
+                    .ok()
+                    .and_then(|mut w| w.on_exit(info.signal.as_deref()));
+                if let Some(hit) = hit {
+                    emit_limit_hit(&handle, ws_id, hit);
+                }
+                // A stopped agent's conversation is taken by whoever stopped it
+                let stopped = stopped_clone.lock().map(|s| *s).unwrap_or(false);
+                let resume_failed = hooks.resumed
+                    && info.code.is_some_and(|code| code != 0)
+                    && started.elapsed() < RESUME_FAILURE_WINDOW;
+                // Conversation files are written up to the end
+                let id = hooks
+                    .session_id
+                    .as_ref()
+                    .and_then(|c| c.lock().ok().and_then(|c| c.session_id()));
+                if !stopped {
+                    if resume_failed {
+                        resume::dispatch_failed(&handle, ws_id);
+                    } else if let Some(id) = id {
+                        resume::dispatch(&handle, ws_id, &hooks.agent, id);
+                    }
+                }
+            }
+            let _ = handle.emit(&format!("pty-exit-{}", exit_key.event_id()), info);
+
+            // An exited agent frees a slot for the next queued spawn
+            if exit_key.is_agent() {
+                start_queued_spawns(&handle);
+            }
         });
 
         self.sessions.insert(
-            workspace_id.to_string(),
+            key,
             PtySession {
+                backend,
                 writer,
-                _child: child,
+                pid,
+                label,
+                transcript,
                 alive,
+                stopped,
             },
         );
+    }
+
+    /// Sessions the daemon is hosting (empty without a daemon or if none is
+    /// running; the daemon is not started just to ask)
+    #[cfg(unix)]
+    pub fn daemon_sessions(&self) -> Vec<DaemonSessionInfo> {
+        self.daemon
+            .as_ref()
+            .and_then(|client| client.list().ok())
+            .unwrap_or_default()
+    }
 
+    /// Reattach to a daemon-hosted session left by a previous app run,
+    /// replaying its retained output into a fresh scrollback. `agent` is
+    /// given for agent sessions and `working_dir` is the workspace's worktree.
+    #[cfg(unix)]
+    pub fn reattach(
+        &mut self,
+        session: &DaemonSessionInfo,
+        agent: Option<&AgentAdapter>,
+        working_dir: &str,
+    ) -> Result<(), String> {
+        let client = self
+            .daemon
+            .clone()
+            .ok_or_else(|| "session daemon is not enabled".to_string())?;
+        if self.sessions.contains_key(&session.key) {
+            return Err(format!("session already exists: {}", session.key.event_id()));
+        }
+
+        let io = attach_daemon(&client, &session.key, session.pid, true)?;
+        let hooks = agent.map(|adapter| {
+            self.agent_hooks(&session.key, adapter, None, working_dir, &session.label, true)
+        });
+        self.run_session(session.key.clone(), io, session.label.clone(), hooks);
         Ok(())
     }
 
+    /// Drop a daemon session nobody will reattach to (killing it if alive)
+    #[cfg(unix)]
+    pub fn release_daemon_session(&self, key: &SessionKey) {
+        if let Some(client) = &self.daemon {
+            let _ = client.request(&DaemonRequest::Release { key: key.clone() });
+        }
+    }
+
     /// Write data to a PTY session's stdin
     pub fn write(&mut self, workspace_id: &str, data: &str) -> Result<(), String> {
         let session = self
             .sessions
-            .get_mut(workspace_id)
+            .get_mut(&SessionKey::agent(workspace_id))
             .ok_or_else(|| format!("no session: {workspace_id}"))?;
+        session.record_input(format!("{data}\n"));
 
         session
             .writer
@@ -187,35 +1001,803 @@ impl PtyManager {
             .flush()
             .map_err(|e| format!("flush error: {e}"))?;
 
+        self.input_written(workspace_id);
+        Ok(())
+    }
+
+    /// Resize a session's PTY; the kernel delivers SIGWINCH to its process.
+    /// Without a live session the size is remembered for the next spawn.
+    pub fn resize(&mut self, key: &SessionKey, rows: u16, cols: u16) -> Result<(), String> {
+        if rows == 0 || cols == 0 {
+            return Err(format!("invalid terminal size: {rows}x{cols}"));
+        }
+
+        let size = PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        self.sizes.insert(key.clone(), size);
+        if let Some(Ok(mut screen)) = self.screens.get(key).map(|s| s.lock()) {
+            screen.resize(rows, cols);
+        }
+
+        if let Some(session) = self.sessions.get(key) {
+            session.backend.resize(size)?;
+            record(
+                &session.transcript,
+                TranscriptEntry::Resize {
+                    time_ms: transcript::now_ms(),
+                    cols,
+                    rows,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Size to open a session's PTY with: last known size or the default
+    fn size_for(&self, key: &SessionKey) -> PtySize {
+        self.sizes.get(key).copied().unwrap_or(PtySize {
+            rows: DEFAULT_ROWS,
+            cols: DEFAULT_COLS,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+    }
+
+    /// Drop all state retained for a workspace (called when it is removed)
+    pub fn forget(&mut self, workspace_id: &str) {
+        self.cancel_queued(workspace_id);
+        self.close_terminals(workspace_id);
+        self.sizes.retain(|key, _| key.workspace_id != workspace_id);
+        self.scrollbacks.retain(|key, _| key.workspace_id != workspace_id);
+        self.screens.retain(|key, _| key.workspace_id != workspace_id);
+        self.line_indexes.retain(|key, _| key.workspace_id != workspace_id);
+        self.activities.remove(workspace_id);
+        self.trigger_engines.remove(workspace_id);
+        self.approval_engines.remove(workspace_id);
+        self.session_ids.remove(workspace_id);
+        self.budgets.remove(workspace_id);
+        self.initial_prompts.remove(workspace_id);
+    }
+
+    /// Current activity of a workspace's agent, if it has been spawned
+    pub fn activity(&self, workspace_id: &str) -> Option<AgentActivity> {
+        self.activities
+            .get(workspace_id)
+            .and_then(|t| t.lock().ok().map(|t| t.state()))
+    }
+
+    /// Retained output of a session from sequence number `from` onwards
+    pub fn scrollback(&self, key: &SessionKey, from: Option<u64>) -> Result<ScrollbackSnapshot, String> {
+        let scrollback = self
+            .scrollbacks
+            .get(key)
+            .ok_or_else(|| format!("no scrollback: {}", key.event_id()))?;
+        let sb = scrollback.lock().map_err(|e| e.to_string())?;
+        Ok(sb.snapshot(from))
+    }
+
+    /// Current visible screen of a session as emulated from its output
+    pub fn screen_snapshot(&self, key: &SessionKey, include_cells: bool) -> Result<ScreenSnapshot, String> {
+        let screen = self
+            .screens
+            .get(key)
+            .ok_or_else(|| format!("no screen: {}", key.event_id()))?;
+        let screen = screen.lock().map_err(|e| e.to_string())?;
+        Ok(screen.snapshot(include_cells))
+    }
+
+    /// Indexed output of every session not covered by a transcript
+    pub fn indexed_lines(&self) -> Vec<(SessionKey, Vec<IndexedLine>)> {
+        self.line_indexes
+            .iter()
+            .filter_map(|(key, index)| Some((key.clone(), index.lock().ok()?.lines())))
+            .collect()
+    }
+
+    /// Write raw bytes to a PTY session's stdin — control characters,
+    /// escape sequences and pastes are delivered verbatim
+    pub fn write_raw(&mut self, key: &SessionKey, data: &[u8]) -> Result<(), String> {
+        let session = self
+            .sessions
+            .get_mut(key)
+            .ok_or_else(|| format!("no session: {}", key.event_id()))?;
+        session.record_input(String::from_utf8_lossy(data).into_owned());
+
+        session
+            .writer
+            .write_all(data)
+            .map_err(|e| format!("write error: {e}"))?;
+        session
+            .writer
+            .flush()
+            .map_err(|e| format!("flush error: {e}"))?;
+
+        if key.is_agent() {
+            self.input_written(&key.workspace_id);
+        }
         Ok(())
     }
 
-    /// Kill a PTY session
+    /// Kill a workspace's agent session. The session is detached immediately;
+    /// its process group is terminated in the background (see `terminate_session`).
     pub fn kill(&mut self, workspace_id: &str) -> Result<(), String> {
-        if let Some(session) = self.sessions.remove(workspace_id) {
-            if let Ok(mut alive) = session.alive.lock() {
-                *alive = false;
+        self.cancel_queued(workspace_id);
+        if let Some(run) = self.headless_runs.remove(workspace_id) {
+            run.kill(self.grace.terminate);
+        }
+        if let Some(session) = self.sessions.remove(&SessionKey::agent(workspace_id)) {
+            if let Ok(mut stopped) = session.stopped.lock() {
+                *stopped = true;
             }
-            // Drop writer to close PTY
-            drop(session.writer);
+            let grace = self.grace;
+            std::thread::spawn(move || terminate_session(session, grace));
         }
         Ok(())
     }
 
-    /// Check if a session is alive
+    /// Run a workspace's agent once without a terminal. A workspace runs
+    /// either a headless agent or an agent session, not both.
+    pub fn run_headless(&mut self, workspace_id: &str, request: HeadlessRequest) -> Result<(), String> {
+        if self.is_alive(workspace_id) || self.queue.contains(workspace_id) {
+            return Err(format!("agent already running: {workspace_id}"));
+        }
+        if self.headless_runs.contains_key(workspace_id) {
+            return Err(format!("headless run in progress: {workspace_id}"));
+        }
+        let run = headless::start(&self.app_handle, workspace_id, request)?;
+        self.headless_runs.insert(workspace_id.to_string(), run);
+        Ok(())
+    }
+
+    /// Forget a headless run whose process has exited
+    pub fn headless_finished(&mut self, workspace_id: &str) {
+        self.headless_runs.remove(workspace_id);
+    }
+
+    /// Check if a workspace's agent session is alive
     pub fn is_alive(&self, workspace_id: &str) -> bool {
         self.sessions
-            .get(workspace_id)
+            .get(&SessionKey::agent(workspace_id))
             .and_then(|s| s.alive.lock().ok().map(|a| *a))
             .unwrap_or(false)
     }
 
-    /// List active session IDs
+    /// List workspace IDs with a live agent session
     pub fn active_sessions(&self) -> Vec<String> {
         self.sessions
             .iter()
-            .filter(|(_, s)| s.alive.lock().map(|a| *a).unwrap_or(false))
-            .map(|(id, _)| id.clone())
+            .filter(|(key, s)| key.is_agent() && s.alive.lock().map(|a| *a).unwrap_or(false))
+            .map(|(key, _)| key.workspace_id.clone())
             .collect()
     }
 }
+
+fn spawn_local(cmd: CommandBuilder, size: PtySize) -> Result<SessionIo, String> {
+    let pair = NativePtySystem::default()
+        .openpty(size)
+        .map_err(|e| format!("pty open error: {e}"))?;
+
+    let mut child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| format!("spawn error: {e}"))?;
+
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| format!("reader clone error: {e}"))?;
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| format!("writer error: {e}"))?;
+
+    Ok(SessionIo {
+        pid: child.process_id(),
+        backend: PtyBackend::Local {
+            master: pair.master,
+            killer: child.clone_killer(),
+        },
+        reader,
+        writer,
+        replayed: 0,
+        wait: Box::new(move || {
+            child
+                .wait()
+                .map(|status| ExitInfo::from_status(&status))
+                .unwrap_or_default()
+        }),
+    })
+}
+
+#[cfg(unix)]
+fn spawn_in_daemon(
+    client: &DaemonClient,
+    key: &SessionKey,
+    cmd: &CommandBuilder,
+    size: PtySize,
+    label: &str,
+) -> Result<SessionIo, String> {
+    client.ensure_running()?;
+    let pid = client.spawn(SpawnSpec::from_command(key.clone(), cmd, size, label.to_string()))?;
+    attach_daemon(client, key, pid, false)
+}
+
+/// Attach to a daemon session; its exit is observed through the daemon,
+/// which is then told to forget the session
+#[cfg(unix)]
+fn attach_daemon(
+    client: &DaemonClient,
+    key: &SessionKey,
+    pid: Option<u32>,
+    replay: bool,
+) -> Result<SessionIo, String> {
+    let (stream, replayed) = client.attach(key, replay)?;
+    let reader = stream
+        .try_clone()
+        .map_err(|e| format!("reader clone error: {e}"))?;
+
+    let wait_client = client.clone();
+    let wait_key = key.clone();
+    Ok(SessionIo {
+        backend: PtyBackend::Daemon {
+            client: client.clone(),
+            key: key.clone(),
+        },
+        reader: Box::new(reader),
+        writer: Box::new(stream),
+        pid,
+        replayed,
+        wait: Box::new(move || {
+            let info = wait_client.wait(&wait_key).unwrap_or_default();
+            let _ = wait_client.request(&DaemonRequest::Release { key: wait_key });
+            info
+        }),
+    })
+}
+
+/// Secrets and terminal settings shared by every session
+fn apply_session_env(cmd: &mut CommandBuilder, secret_env: Option<&HashMap<String, String>>) {
+    // Inject Keychain secrets as environment variables
+    if let Some(secrets) = secret_env {
+        for (k, v) in secrets {
+            cmd.env(k, v);
+        }
+    }
+
+    // Force color output
+    cmd.env("FORCE_COLOR", "1");
+    cmd.env("TERM", "xterm-256color");
+}
+
+/// Start queued spawns now that a slot may be free, and move their
+/// workspaces out of `Queued`. Runs on the exit waiter thread, so both
+/// managers are looked up through the app handle and never locked together.
+pub fn start_queued_spawns(app: &AppHandle) {
+    let started = match app.try_state::<Mutex<PtyManager>>() {
+        Some(pty) => match pty.lock() {
+            Ok(mut pty) => pty.start_queued(),
+            Err(_) => return,
+        },
+        None => return,
+    };
+    if started.is_empty() {
+        return;
+    }
+
+    if let Some(ws_mgr) = app.try_state::<Mutex<WorkspaceManager>>() {
+        if let Ok(mut ws) = ws_mgr.lock() {
+            for (workspace_id, result) in started {
+                let state = match result {
+                    Ok(()) => WorkspaceState::Running,
+                    Err(e) => {
+                        eprintln!("queued spawn failed for {workspace_id}: {e}");
+                        WorkspaceState::Stopped
+                    }
+                };
+                let _ = ws.set_state(&workspace_id, state);
+            }
+        }
+    }
+}
+
+/// Staged termination of a session's process tree: EOF on stdin, SIGINT,
+/// SIGTERM, then SIGKILL, waiting out each grace period unless the agent
+/// exits first. The master is held until the end so the PTY hangup doesn't
+/// preempt the graceful stages.
+fn terminate_session(session: PtySession, grace: TerminationGrace) {
+    let PtySession {
+        mut backend,
+        mut writer,
+        pid,
+        alive,
+        ..
+    } = session;
+
+    let exited = || alive.lock().map(|a| !*a).unwrap_or(true);
+    let wait_for_exit = |timeout: Duration| {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if exited() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(EXIT_POLL_INTERVAL_MS));
+        }
+        exited()
+    };
+
+    // Dropping a local writer sends EOF, which well-behaved agents honour;
+    // a daemon session is sent the same bytes explicitly
+    #[cfg(unix)]
+    if matches!(backend, PtyBackend::Daemon { .. }) {
+        let _ = writer.write_all(b"\n\x04");
+    }
+    let _ = writer.flush();
+    drop(writer);
+
+    #[cfg(unix)]
+    if let Some(pid) = pid {
+        // The child leads its own session, so its pgid is its pid; also hit the
+        // terminal's foreground group in case a job was moved to the front.
+        let mut groups = vec![pid as libc::pid_t];
+        if let Some(fg) = backend.foreground_group() {
+            if fg != groups[0] {
+                groups.push(fg);
+            }
+        }
+
+        let stages = [
+            (libc::SIGINT, grace.interrupt),
+            (libc::SIGTERM, grace.terminate),
+        ];
+        for (signal, timeout) in stages {
+            if exited() {
+                break;
+            }
+            signal_groups(&groups, signal);
+            if wait_for_exit(timeout) {
+                break;
+            }
+        }
+        // Final stage; also reaps grandchildren left behind in the group
+        signal_groups(&groups, libc::SIGKILL);
+    }
+
+    #[cfg(not(unix))]
+    let _ = (pid, grace, wait_for_exit);
+
+    if !exited() {
+        backend.force_kill();
+    }
+    drop(backend);
+}
+
+#[cfg(unix)]
+fn signal_groups(groups: &[libc::pid_t], signal: libc::c_int) {
+    for &pgid in groups {
+        // SAFETY: killpg has no memory-safety preconditions; errors (e.g. ESRCH
+        // once the group is gone) are expected and ignored
+        unsafe {
+            libc::killpg(pgid, signal);
+        }
+    }
+}
+
+/// Read raw chunks from the PTY until EOF or error
+fn read_raw(mut reader: Box<dyn Read + Send>, tx: Sender<Vec<u8>>) {
+    let mut buf = [0u8; READ_BUF_SIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                if tx.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+    }
+}
+
+/// Per-session consumers of the output stream, owned by the emitter thread
+struct OutputContext {
+    app: AppHandle,
+    key: SessionKey,
+    encoding: OutputEncoding,
+    scrollback: Arc<Mutex<Scrollback>>,
+    screen: Arc<Mutex<TerminalScreen>>,
+    agent: Option<AgentHooks>,
+    /// Replayed output still to come, which was recorded by an earlier attach
+    transcript_skip: Cell<usize>,
+    /// Set for sessions searched live rather than through a transcript
+    line_index: Option<Arc<Mutex<LineIndex>>>,
+}
+
+impl OutputContext {
+    /// Hand one batch to every consumer. `text` is the decoded output used for
+    /// analysis; `raw` is the exact bytes, used for base64 payloads.
+    fn publish(&self, text: String, raw: &[u8]) {
+        if !raw.is_empty() {
+            if let Ok(mut screen) = self.screen.lock() {
+                screen.process(raw);
+            }
+        }
+        if let (false, Some(hooks)) = (text.is_empty(), &self.agent) {
+            self.update_activity(|t| t.on_output(&text, Instant::now()));
+            let hits = hooks
+                .limits
+                .lock()
+                .map(|mut w| w.on_output(&text))
+                .unwrap_or_default();
+            for hit in hits {
+                emit_limit_hit(&self.app, &self.key.workspace_id, hit);
+            }
+            // Replayed output was already counted by an earlier attach
+            if let Some(fresh) = self.record_output(text.as_str(), raw) {
+                let now_ms = transcript::now_ms();
+                let deltas = hooks
+                    .usage
+                    .as_ref()
+                    .and_then(|tracker| tracker.lock().ok().map(|mut t| t.on_output(&fresh, now_ms)))
+                    .unwrap_or_default();
+                if let Some(Ok(mut watcher)) = hooks.budget.as_ref().map(|w| w.lock()) {
+                    for (_, delta) in &deltas {
+                        watcher.on_usage(delta);
+                    }
+                }
+                usage::dispatch(&self.app, &self.key.workspace_id, now_ms, deltas);
+                self.check_budget();
+
+                // A prompt no longer on screen was answered in the terminal
+                let cleared = hooks.approvals.as_ref().and_then(|engine| {
+                    let mut engine = engine.lock().ok()?;
+                    engine.pending()?;
+                    let screen = self.screen.lock().ok()?.text();
+                    engine.on_screen(&screen)
+                });
+                if let Some(cleared) = cleared {
+                    approvals::dispatch_cleared(&self.app, cleared);
+                }
+
+                // Replayed prompts were answered and triggers fired by an earlier attach
+                let detected = hooks
+                    .approvals
+                    .as_ref()
+                    .and_then(|engine| engine.lock().ok().and_then(|mut e| e.on_output(&fresh, Instant::now())));
+                if let Some(detected) = detected {
+                    approvals::dispatch(&self.app, detected);
+                }
+
+                let id = hooks
+                    .session_id
+                    .as_ref()
+                    .and_then(|c| c.lock().ok().and_then(|mut c| c.on_output(&fresh)));
+                if let Some(id) = id {
+                    resume::dispatch(&self.app, &self.key.workspace_id, &hooks.agent, id);
+                }
+
+                let fired = hooks
+                    .triggers
+                    .as_ref()
+                    .and_then(|engine| engine.lock().ok().map(|mut e| e.on_output(&fresh, Instant::now())))
+                    .unwrap_or_default();
+                for (trigger, action, matched) in fired {
+                    triggers::dispatch(
+                        &self.app,
+                        TriggerFired {
+                            workspace_id: self.key.workspace_id.clone(),
+                            trigger,
+                            action,
+                            matched,
+                        },
+                    );
+                }
+            }
+        }
+        if let (false, Some(index)) = (text.is_empty(), &self.line_index) {
+            if let Ok(mut index) = index.lock() {
+                index.push(transcript::now_ms(), &text);
+            }
+        }
+
+        let payload = match self.encoding {
+            OutputEncoding::Utf8 => text,
+            OutputEncoding::Base64 => base64::engine::general_purpose::STANDARD.encode(raw),
+        };
+        if payload.is_empty() {
+            return;
+        }
+        if let Ok(mut sb) = self.scrollback.lock() {
+            let _ = self
+                .app
+                .emit(&format!("pty-output-{}", self.key.event_id()), sb.push(payload));
+        }
+    }
+
+    /// Record output to the transcript; returns the part that wasn't replayed
+    fn record_output(&self, text: &str, raw: &[u8]) -> Option<String> {
+        let skip = self.transcript_skip.get();
+        let data = if skip == 0 {
+            text.to_string()
+        } else if skip >= raw.len() {
+            self.transcript_skip.set(skip - raw.len());
+            return None;
+        } else {
+            self.transcript_skip.set(0);
+            String::from_utf8_lossy(&raw[skip..]).into_owned()
+        };
+        if let Some(hooks) = &self.agent {
+            record(
+                &hooks.transcript,
+                TranscriptEntry::Output {
+                    time_ms: transcript::now_ms(),
+                    data: data.clone(),
+                },
+            );
+        }
+        Some(data)
+    }
+
+    fn check_budget(&self) {
+        let (hooks, watcher) = match self.agent.as_ref().and_then(|h| Some((h, h.budget.as_ref()?))) {
+            Some(found) => found,
+            None => return,
+        };
+        let working = hooks
+            .activity
+            .lock()
+            .is_ok_and(|t| t.state() == AgentActivity::Working);
+        let now = Instant::now();
+        let (alerts, runtime) = match watcher.lock() {
+            Ok(mut w) => {
+                if working {
+                    w.on_working(now);
+                }
+                (w.check(now), w.take_runtime(now, budget::RUNTIME_RECORD_INTERVAL))
+            }
+            Err(_) => return,
+        };
+        for alert in alerts {
+            budget::dispatch(&self.app, &self.key.workspace_id, alert);
+        }
+        if let Some(secs) = runtime {
+            budget::record_runtime(&self.app, &self.key.workspace_id, secs);
+        }
+    }
+
+    fn update_activity(&self, f: impl FnOnce(&mut ActivityTracker) -> Option<AgentActivity>) {
+        if let Some(hooks) = &self.agent {
+            let change = update_activity(&self.app, &self.key.workspace_id, &hooks.activity, f);
+            if matches!(change, Some(AgentActivity::WaitingForInput | AgentActivity::Idle)) {
+                self.deliver_initial_input(hooks);
+            }
+        }
+    }
+
+    /// Type a pending stdin prompt into the agent now that it waits for input
+    fn deliver_initial_input(&self, hooks: &AgentHooks) {
+        let prompt = match hooks.initial_input.lock().ok().and_then(|mut p| p.take()) {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        let app = self.app.clone();
+        let workspace_id = self.key.workspace_id.clone();
+        std::thread::spawn(move || {
+            let written = app
+                .try_state::<Mutex<PtyManager>>()
+                .ok_or_else(|| "pty manager unavailable".to_string())
+                .and_then(|pty| pty.lock().map_err(|e| e.to_string())?.write(&workspace_id, &prompt));
+            if let Err(e) = written {
+                eprintln!("initial prompt not delivered to {workspace_id}: {e}");
+            }
+        });
+    }
+}
+
+/// Apply a tracker update and emit `agent-status-*` if the state changed
+fn update_activity(
+    app: &AppHandle,
+    workspace_id: &str,
+    tracker: &Mutex<ActivityTracker>,
+    f: impl FnOnce(&mut ActivityTracker) -> Option<AgentActivity>,
+) -> Option<AgentActivity> {
+    let change = tracker.lock().ok().and_then(|mut t| f(&mut t));
+    if let Some(state) = change {
+        let _ = app.emit(&format!("agent-status-{workspace_id}"), state);
+    }
+    change
+}
+
+fn emit_limit_hit(app: &AppHandle, workspace_id: &str, hit: LimitHit) {
+    let _ = app.emit(&format!("resource-limit-{workspace_id}"), hit);
+}
+
+/// Batch raw chunks into sequenced `pty-output-*` events (16ms / 4KB threshold),
+/// recording each batch in the session scrollback before it is emitted.
+/// The timeout-driven flush means partial lines like prompts are emitted
+/// promptly even when no further output follows; quiet periods also drive
+/// activity re-evaluation.
+fn pump_output(rx: Receiver<Vec<u8>>, ctx: &OutputContext) {
+    let interval = Duration::from_millis(BATCH_INTERVAL_MS);
+    let tick = Duration::from_millis(ACTIVITY_TICK_MS);
+    let mut decoder = Utf8Decoder::default();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut batch_start = Instant::now();
+
+    let flush = |pending: &mut Vec<u8>, decoder: &mut Utf8Decoder| {
+        if pending.is_empty() {
+            return;
+        }
+        let text = decoder.decode(pending);
+        ctx.publish(text, pending);
+        pending.clear();
+    };
+
+    loop {
+        let timeout = if pending.is_empty() {
+            tick
+        } else {
+            interval.saturating_sub(batch_start.elapsed())
+        };
+
+        match rx.recv_timeout(timeout) {
+            Ok(chunk) => {
+                if pending.is_empty() {
+                    batch_start = Instant::now();
+                }
+                pending.extend_from_slice(&chunk);
+                if pending.len() >= BATCH_MAX_BYTES || batch_start.elapsed() >= interval {
+                    flush(&mut pending, &mut decoder);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => flush(&mut pending, &mut decoder),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        ctx.update_activity(|t| t.tick(Instant::now()));
+        ctx.check_budget();
+    }
+
+    // Flush remaining, including any dangling partial UTF-8 sequence
+    flush(&mut pending, &mut decoder);
+    ctx.publish(decoder.finish(), &[]);
+}
+
+/// Incremental UTF-8 decoder: an incomplete multi-byte sequence at the end of
+/// a chunk is carried over to the next one instead of being mangled.
+/// Invalid bytes are replaced with U+FFFD rather than ending the stream.
+#[derive(Default)]
+struct Utf8Decoder {
+    carry: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    fn decode(&mut self, bytes: &[u8]) -> String {
+        let mut buf = std::mem::take(&mut self.carry);
+        buf.extend_from_slice(bytes);
+
+        let mut out = String::with_capacity(buf.len());
+        let mut rest = &buf[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    out.push_str(text);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        None => {
+                            // Truncated sequence at the end — wait for more bytes
+                            self.carry = after.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Flush a dangling partial sequence at end of stream
+    fn finish(&mut self) -> String {
+        let carry = std::mem::take(&mut self.carry);
+        String::from_utf8_lossy(&carry).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── SessionKey tests ──
+
+    #[test]
+    fn test_session_key_agent_event_id_is_workspace_id() {
+        let key = SessionKey::agent("ws-1");
+        assert!(key.is_agent());
+        assert_eq!(key.terminal_id, AGENT_TERMINAL);
+        assert_eq!(key.event_id(), "ws-1");
+        assert_eq!(SessionKey::new("ws-1", None), key);
+    }
+
+    #[test]
+    fn test_session_key_terminal_event_id() {
+        let key = SessionKey::new("ws-1", Some("term-ab12"));
+        assert!(!key.is_agent());
+        assert_eq!(key.event_id(), "ws-1:term-ab12");
+    }
+
+    // ── ExitInfo tests ──
+
+    #[test]
+    fn test_exit_info_from_exit_code() {
+        let info = ExitInfo::from_status(&ExitStatus::with_exit_code(3));
+        assert_eq!(info.code, Some(3));
+        assert!(info.signal.is_none());
+    }
+
+    #[test]
+    fn test_exit_info_from_signal() {
+        let info = ExitInfo::from_status(&ExitStatus::with_signal("Interrupt"));
+        assert!(info.code.is_none());
+        assert_eq!(info.signal.as_deref(), Some("Interrupt"));
+    }
+
+    #[test]
+    fn test_exit_info_serializes_both_fields() {
+        let json = serde_json::to_value(ExitInfo {
+            code: Some(0),
+            signal: None,
+        })
+        .unwrap();
+        assert_eq!(json, serde_json::json!({"code": 0, "signal": null}));
+    }
+
+    // ── Utf8Decoder tests ──
+
+    #[test]
+    fn test_decoder_passes_ascii_through() {
+        let mut dec = Utf8Decoder::default();
+        assert_eq!(dec.decode(b"Continue? [y/N] "), "Continue? [y/N] ");
+    }
+
+    #[test]
+    fn test_decoder_preserves_carriage_returns_and_escapes() {
+        let mut dec = Utf8Decoder::default();
+        let input = b"\x1b[2K\rSpinning |\rSpinning /";
+        assert_eq!(dec.decode(input).as_bytes(), input);
+    }
+
+    #[test]
+    fn test_decoder_carries_split_multibyte_sequence() {
+        let mut dec = Utf8Decoder::default();
+        let bytes = "a\u{2714}b".as_bytes(); // ✔ is 3 bytes
+        assert_eq!(dec.decode(&bytes[..2]), "a");
+        assert_eq!(dec.decode(&bytes[2..3]), "");
+        assert_eq!(dec.decode(&bytes[3..]), "\u{2714}b");
+    }
+
+    #[test]
+    fn test_decoder_replaces_invalid_bytes_and_continues() {
+        let mut dec = Utf8Decoder::default();
+        assert_eq!(dec.decode(b"ok\xffstill ok"), "ok\u{FFFD}still ok");
+    }
+
+    #[test]
+    fn test_decoder_finish_flushes_dangling_bytes() {
+        let mut dec = Utf8Decoder::default();
+        assert_eq!(dec.decode(&[b'x', 0xE2, 0x9C]), "x");
+        assert_eq!(dec.finish(), "\u{FFFD}");
+        assert_eq!(dec.finish(), "");
+    }
+}
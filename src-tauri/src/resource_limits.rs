@@ -0,0 +1,311 @@
+use crate::ansi::strip_ansi;
+use crate::config::ResourceLimits;
+use regex::Regex;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// Extra CPU seconds between the soft limit (SIGXCPU, reportable) and the
+/// hard limit (SIGKILL)
+const CPU_KILL_GRACE_SECS: u64 = 5;
+/// Unterminated output kept between chunks so a message split across reads
+/// still matches
+const CARRY_CHARS: usize = 256;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    Memory,
+    CpuTime,
+    Processes,
+    OpenFiles,
+}
+
+/// Payload of the `resource-limit-*` event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LimitHit {
+    pub kind: LimitKind,
+    /// Configured value (MiB, seconds or count, matching the config field)
+    pub limit: u64,
+    /// What gave the hit away: an output line or the exit signal
+    pub detail: String,
+}
+
+/// Error lines that shells, the kernel's errno strings and language runtimes
+/// print when a process runs into each limit. They are matched against whole
+/// lines, case-sensitively, so an agent writing about memory doesn't count.
+const OUTPUT_SIGNATURES: &[(LimitKind, &str)] = &[
+    (LimitKind::Memory, r"^\S.*: Cannot allocate memory(?: \(os error 12\))?$"),
+    (LimitKind::Memory, r"^memory allocation of \d+ bytes failed$"),
+    (LimitKind::Memory, r"^terminate called after throwing an instance of 'std::bad_alloc'$"),
+    (LimitKind::Memory, r"^fatal error: runtime: out of memory$"),
+    (LimitKind::Memory, r"^FATAL ERROR: .* JavaScript heap out of memory$"),
+    (LimitKind::CpuTime, r"^(?:\S.*: )?CPU time limit exceeded(?: \(core dumped\))?$"),
+    (LimitKind::Processes, r"^\S.*: fork: (?:retry: )?Resource temporarily unavailable$"),
+    (LimitKind::Processes, r"^\S.*: [Cc]annot fork$"),
+    (LimitKind::OpenFiles, r"^\S.*: Too many open files(?: \(os error 24\))?$"),
+];
+
+fn signatures() -> &'static [(LimitKind, Regex)] {
+    static SIGNATURES: OnceLock<Vec<(LimitKind, Regex)>> = OnceLock::new();
+    SIGNATURES.get_or_init(|| {
+        OUTPUT_SIGNATURES
+            .iter()
+            .map(|(kind, pattern)| (*kind, Regex::new(pattern).expect("valid limit signature")))
+            .collect()
+    })
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(all(target_os = "linux", not(target_env = "gnu")))]
+type Resource = libc::c_int;
+
+/// The configured limits as (`prlimit` option, resource, values). Values
+/// above the current hard limit are capped, since an unprivileged process
+/// can't raise it.
+#[cfg(target_os = "linux")]
+fn planned(limits: &ResourceLimits) -> Result<Vec<(&'static str, Resource, libc::rlimit)>, String> {
+    let entries = [
+        ("--as", libc::RLIMIT_AS, limits.memory_mb.map(|mb| mb.saturating_mul(1024 * 1024)), 0),
+        ("--cpu", libc::RLIMIT_CPU, limits.cpu_seconds, CPU_KILL_GRACE_SECS),
+        ("--nproc", libc::RLIMIT_NPROC, limits.max_processes, 0),
+        ("--nofile", libc::RLIMIT_NOFILE, limits.max_open_files, 0),
+    ];
+
+    let mut planned = Vec::new();
+    for (option, resource, value, hard_slack) in entries {
+        let value = match value {
+            Some(v) => v,
+            None => continue,
+        };
+
+        let mut current = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: getrlimit only writes the rlimit struct passed in
+        if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+            return Err(format!("getrlimit error: {}", std::io::Error::last_os_error()));
+        }
+
+        let hard = value.saturating_add(hard_slack).min(current.rlim_max);
+        let wanted = libc::rlimit {
+            rlim_cur: value.min(hard),
+            rlim_max: hard,
+        };
+        planned.push((option, resource, wanted));
+    }
+    Ok(planned)
+}
+
+/// Run `command` under util-linux `prlimit`, which sets the limits on itself
+/// and then execs the command, so they hold from its first instruction and
+/// everything it forks inherits them. For PTY sessions, whose spawn can't
+/// run code in the child.
+#[cfg(target_os = "linux")]
+pub fn wrap(command: &str, args: &[String], limits: &ResourceLimits) -> Result<(String, Vec<String>), String> {
+    let prlimit = crate::agent_health::resolve_command("prlimit", std::env::var_os("PATH").as_deref())
+        .ok_or("prlimit not found (util-linux)")?;
+    let mut wrapped: Vec<String> = planned(limits)?
+        .into_iter()
+        .map(|(option, _, limit)| format!("{option}={}:{}", limit.rlim_cur, limit.rlim_max))
+        .collect();
+    wrapped.push("--".into());
+    wrapped.push(command.to_string());
+    wrapped.extend(args.iter().cloned());
+    Ok((prlimit.to_string_lossy().into_owned(), wrapped))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wrap(_command: &str, _args: &[String], _limits: &ResourceLimits) -> Result<(String, Vec<String>), String> {
+    Err("resource limits are only supported on Linux".into())
+}
+
+/// Set the limits in the child between fork and exec
+#[cfg(target_os = "linux")]
+pub fn apply_before_exec(cmd: &mut std::process::Command, limits: &ResourceLimits) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+    let planned = planned(limits)?;
+    // SAFETY: the hook only calls setrlimit, which is async-signal-safe,
+    // and doesn't allocate
+    unsafe {
+        cmd.pre_exec(move || {
+            for (_, resource, limit) in &planned {
+                if libc::setrlimit(*resource, limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply_before_exec(_cmd: &mut std::process::Command, _limits: &ResourceLimits) -> Result<(), String> {
+    Err("resource limits are only supported on Linux".into())
+}
+
+/// Watches a session for signs that one of its configured limits was hit.
+/// rlimit breaches aren't reported to the parent, so this relies on the
+/// errors the agent's tools print and on the exit signal. Each kind is
+/// reported at most once per session.
+pub struct LimitWatcher {
+    limits: ResourceLimits,
+    reported: Vec<LimitKind>,
+    carry: String,
+}
+
+impl LimitWatcher {
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            reported: Vec::new(),
+            carry: String::new(),
+        }
+    }
+
+    pub fn on_output(&mut self, text: &str) -> Vec<LimitHit> {
+        if self.limits.is_empty() {
+            return Vec::new();
+        }
+
+        let window = format!("{}{}", self.carry, strip_ansi(text));
+        // Only complete lines are checked; the rest waits for its newline
+        let complete = window.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let mut hits = Vec::new();
+        for line in window[..complete].lines() {
+            let line = line.trim();
+            for (kind, signature) in signatures() {
+                if signature.is_match(line) {
+                    hits.extend(self.report(*kind, line));
+                }
+            }
+        }
+
+        let tail = &window[complete..];
+        let keep_from = tail
+            .char_indices()
+            .rev()
+            .nth(CARRY_CHARS - 1)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        self.carry = tail[keep_from..].to_string();
+        hits
+    }
+
+    /// Check the exit signal (e.g. "CPU time limit exceeded" for SIGXCPU)
+    pub fn on_exit(&mut self, signal: Option<&str>) -> Option<LimitHit> {
+        let signal = signal?;
+        if signal.to_lowercase().contains("cpu time limit") {
+            return self.report(LimitKind::CpuTime, signal);
+        }
+        None
+    }
+
+    fn report(&mut self, kind: LimitKind, detail: &str) -> Option<LimitHit> {
+        let limit = match kind {
+            LimitKind::Memory => self.limits.memory_mb,
+            LimitKind::CpuTime => self.limits.cpu_seconds,
+            LimitKind::Processes => self.limits.max_processes,
+            LimitKind::OpenFiles => self.limits.max_open_files,
+        }?;
+        if self.reported.contains(&kind) {
+            return None;
+        }
+        self.reported.push(kind);
+        Some(LimitHit {
+            kind,
+            limit,
+            detail: detail.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watcher() -> LimitWatcher {
+        LimitWatcher::new(ResourceLimits {
+            memory_mb: Some(2048),
+            cpu_seconds: Some(600),
+            max_processes: None,
+            max_open_files: Some(256),
+        })
+    }
+
+    #[test]
+    fn test_detects_open_files_in_output() {
+        let mut w = watcher();
+        let hits = w.on_output("compiling\nerror: Too many open files (os error 24)\n");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].kind, LimitKind::OpenFiles);
+        assert_eq!(hits[0].limit, 256);
+        assert_eq!(hits[0].detail, "error: Too many open files (os error 24)");
+    }
+
+    #[test]
+    fn test_reports_each_kind_once() {
+        let mut w = watcher();
+        assert_eq!(w.on_output("memory allocation of 1024 bytes failed\n").len(), 1);
+        assert!(w.on_output("memory allocation of 2048 bytes failed\n").is_empty());
+    }
+
+    #[test]
+    fn test_ignores_limits_that_are_not_configured() {
+        let mut w = watcher();
+        assert!(w.on_output("bash: fork: retry: Resource temporarily unavailable\n").is_empty());
+    }
+
+    #[test]
+    fn test_no_limits_never_reports() {
+        let mut w = LimitWatcher::new(ResourceLimits::default());
+        assert!(w.on_output("Cannot allocate memory\n").is_empty());
+        assert!(w.on_exit(Some("CPU time limit exceeded")).is_none());
+    }
+
+    #[test]
+    fn test_matches_across_chunk_boundary() {
+        let mut w = watcher();
+        assert!(w.on_output("fatal: Too many op").is_empty());
+        let hits = w.on_output("en files\n");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].kind, LimitKind::OpenFiles);
+    }
+
+    #[test]
+    fn test_cpu_limit_from_exit_signal() {
+        let mut w = watcher();
+        let hit = w.on_exit(Some("CPU time limit exceeded")).unwrap();
+        assert_eq!(hit.kind, LimitKind::CpuTime);
+        assert_eq!(hit.limit, 600);
+        assert!(w.on_exit(Some("Killed")).is_none());
+        assert!(w.on_exit(None).is_none());
+    }
+
+    #[test]
+    fn test_chat_about_limits_is_not_a_hit() {
+        let mut w = watcher();
+        let chat = "The service ran out of memory because each worker opens too many open files.\n\
+                    I'll check why it says \"Cannot allocate memory\" in the logs.\n";
+        assert!(w.on_output(chat).is_empty());
+        // A message only counts once its line is complete
+        assert!(w.on_output("node: Cannot allocate memory").is_empty());
+        assert_eq!(w.on_output("\n")[0].kind, LimitKind::Memory);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_limits_are_set_before_exec() {
+        let mut cmd = std::process::Command::new("sh");
+        cmd.args(["-c", "ulimit -n"]);
+        let limits = ResourceLimits {
+            max_open_files: Some(64),
+            ..Default::default()
+        };
+        apply_before_exec(&mut cmd, &limits).unwrap();
+        let output = cmd.output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "64");
+    }
+}
@@ -0,0 +1,243 @@
+use crate::ansi::strip_ansi;
+use crate::commands;
+use crate::config::SessionIdSource;
+use crate::workspace::WorkspaceManager;
+use regex::Regex;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Manager};
+
+/// Stripped output kept so an id split across chunks is still found
+const TAIL_CHARS: usize = 512;
+
+/// Levels below the conversation dir that are searched (codex files its
+/// rollouts by year, month and day)
+const MAX_DEPTH: usize = 4;
+
+/// Start of a conversation file searched for the worktree path
+const HEAD_BYTES: u64 = 64 * 1024;
+
+/// Files written this long before the session was started still count
+const CLOCK_SLACK: Duration = Duration::from_secs(2);
+
+/// Finds the id of the conversation an agent session is having, so a later
+/// spawn can resume it
+pub struct SessionIdCapture {
+    pattern: Option<Regex>,
+    dir: Option<PathBuf>,
+    file_pattern: Option<Regex>,
+    /// Worktree path a file must mention, when the dir isn't per project
+    mention: Option<String>,
+    since: SystemTime,
+    tail: String,
+    found: Option<String>,
+}
+
+impl SessionIdCapture {
+    pub fn new(source: &SessionIdSource, worktree: &str, started: SystemTime) -> Result<Self, String> {
+        let compile = |pattern: &Option<String>| {
+            pattern
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|e| format!("invalid session id pattern: {e}"))
+        };
+        let mention = match &source.dir {
+            Some(dir) if !dir.contains("{project}") => Some(worktree.to_string()),
+            _ => None,
+        };
+        Ok(Self {
+            pattern: compile(&source.pattern)?,
+            dir: source.dir.as_deref().map(|dir| expand_dir(dir, worktree)),
+            file_pattern: compile(&source.file_pattern)?,
+            mention,
+            since: started.checked_sub(CLOCK_SLACK).unwrap_or(started),
+            tail: String::new(),
+            found: None,
+        })
+    }
+
+    /// Id first seen (or changed) in a chunk of raw output
+    pub fn on_output(&mut self, text: &str) -> Option<String> {
+        let pattern = self.pattern.as_ref()?;
+        self.tail.push_str(&strip_ansi(text));
+        let id = pattern
+            .captures_iter(&self.tail)
+            .last()
+            .and_then(|caps| caps.get(1))
+            .map(|m| m.as_str().to_string());
+        if self.tail.len() > TAIL_CHARS {
+            let cut = self.tail.len() - TAIL_CHARS;
+            let cut = (cut..self.tail.len())
+                .find(|&i| self.tail.is_char_boundary(i))
+                .unwrap_or(self.tail.len());
+            self.tail.drain(..cut);
+        }
+
+        let id = id?;
+        if self.found.as_deref() == Some(id.as_str()) {
+            return None;
+        }
+        self.found = Some(id.clone());
+        Some(id)
+    }
+
+    /// Id seen in the output, else the id of the newest conversation file
+    /// written since the session started
+    pub fn session_id(&self) -> Option<String> {
+        self.found.clone().or_else(|| self.newest_file_id())
+    }
+
+    fn newest_file_id(&self) -> Option<String> {
+        let (dir, file_pattern) = (self.dir.as_ref()?, self.file_pattern.as_ref()?);
+        let mut files = Vec::new();
+        collect_files(dir, MAX_DEPTH, &mut files);
+
+        let mut candidates: Vec<(SystemTime, PathBuf, String)> = files
+            .into_iter()
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                let id = file_pattern.captures(name)?.get(1)?.as_str().to_string();
+                let modified = path.metadata().and_then(|m| m.modified()).ok()?;
+                (modified >= self.since).then_some((modified, path, id))
+            })
+            .collect();
+        candidates.sort_by_key(|(modified, _, _)| std::cmp::Reverse(*modified));
+        candidates
+            .into_iter()
+            .find(|(_, path, _)| match &self.mention {
+                Some(worktree) => mentions(path, worktree),
+                None => true,
+            })
+            .map(|(_, _, id)| id)
+    }
+}
+
+/// Expand `~` and `{project}` in a conversation dir
+fn expand_dir(dir: &str, worktree: &str) -> PathBuf {
+    let dir = dir.replace("{project}", &project_key(worktree));
+    match dir.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(rest),
+        None => PathBuf::from(dir),
+    }
+}
+
+/// A path with every char other than letters and digits replaced by `-`,
+/// the way agents name per-project dirs
+pub fn project_key(path: &str) -> String {
+    path.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn collect_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(t) if t.is_dir() && depth > 0 => collect_files(&path, depth - 1, files),
+            Ok(t) if t.is_file() => files.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// Whether the start of a file mentions `text`
+fn mentions(path: &Path, text: &str) -> bool {
+    let mut head = Vec::new();
+    let read = std::fs::File::open(path).and_then(|f| f.take(HEAD_BYTES).read_to_end(&mut head));
+    read.is_ok() && String::from_utf8_lossy(&head).contains(text)
+}
+
+/// Store an id captured from `agent` on the workspace
+pub fn dispatch(app: &AppHandle, workspace_id: &str, agent: &str, session_id: String) {
+    let app = app.clone();
+    let workspace_id = workspace_id.to_string();
+    let agent = agent.to_string();
+    std::thread::spawn(move || {
+        let ws_mgr = match app.try_state::<Mutex<WorkspaceManager>>() {
+            Some(ws_mgr) => ws_mgr,
+            None => return,
+        };
+        let stored = ws_mgr
+            .lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut ws| ws.set_session_id(&workspace_id, &agent, &session_id));
+        if let Err(e) = stored {
+            eprintln!("session id not stored for {workspace_id}: {e}");
+        }
+    });
+}
+
+/// Forget a conversation the agent couldn't resume and start it afresh on
+/// the workspace's task
+pub fn dispatch_failed(app: &AppHandle, workspace_id: &str) {
+    let app = app.clone();
+    let workspace_id = workspace_id.to_string();
+    std::thread::spawn(move || {
+        eprintln!("agent of {workspace_id} couldn't resume its conversation; starting afresh");
+        if let Err(e) = commands::restart_without_resume(&app, &workspace_id) {
+            eprintln!("agent not restarted for {workspace_id}: {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0199a1b2-c3d4-4e5f-8a9b-0c1d2e3f4a5b";
+
+    fn source(pattern: Option<&str>, dir: Option<&Path>, file_pattern: Option<&str>) -> SessionIdSource {
+        SessionIdSource {
+            pattern: pattern.map(str::to_string),
+            dir: dir.map(|d| d.to_string_lossy().into_owned()),
+            file_pattern: file_pattern.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_id_found_in_split_output_once() {
+        let src = source(Some(r"session id: ([0-9a-f-]{36})"), None, None);
+        let mut capture = SessionIdCapture::new(&src, "/wt", SystemTime::now()).unwrap();
+        assert_eq!(capture.on_output("\x1b[2msession id: 0199a1b2-c3d4"), None);
+        assert_eq!(capture.on_output("-4e5f-8a9b-0c1d2e3f4a5b\x1b[0m\n").as_deref(), Some(ID));
+        assert_eq!(capture.on_output("more output\n"), None);
+        assert_eq!(capture.session_id().as_deref(), Some(ID));
+    }
+
+    #[test]
+    fn test_newest_file_in_shared_dir_must_mention_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let started = SystemTime::now();
+        let day = dir.path().join("2026").join("10").join("16");
+        std::fs::create_dir_all(&day).unwrap();
+        let other = "0199a1b2-0000-4000-8000-000000000000";
+        std::fs::write(day.join(format!("rollout-1-{ID}.jsonl")), r#"{"cwd":"/repo/wt-a"}"#).unwrap();
+        std::fs::write(day.join(format!("rollout-2-{other}.jsonl")), r#"{"cwd":"/repo/wt-b"}"#).unwrap();
+
+        let src = source(None, Some(dir.path()), Some(r"^rollout-.+-([0-9a-f-]{36})\.jsonl$"));
+        let capture = SessionIdCapture::new(&src, "/repo/wt-a", started).unwrap();
+        assert_eq!(capture.session_id().as_deref(), Some(ID));
+
+        let capture = SessionIdCapture::new(&src, "/repo/wt-c", started).unwrap();
+        assert_eq!(capture.session_id(), None);
+    }
+
+    #[test]
+    fn test_project_dir_is_expanded() {
+        assert_eq!(project_key("/Users/me/repo/.worktrees/fix-1"), "-Users-me-repo--worktrees-fix-1");
+        let src = SessionIdSource {
+            dir: Some("/data/{project}".into()),
+            ..Default::default()
+        };
+        let capture = SessionIdCapture::new(&src, "/wt/a", SystemTime::now()).unwrap();
+        assert_eq!(capture.dir, Some(PathBuf::from("/data/-wt-a")));
+        assert!(capture.mention.is_none());
+    }
+}
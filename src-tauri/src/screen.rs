@@ -0,0 +1,199 @@
+use serde::Serialize;
+
+/// Terminal state machine fed with a session's raw output, so the visible
+/// screen of full-screen TUIs can be read as text
+pub struct TerminalScreen {
+    parser: vt100::Parser,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CellColor {
+    Default,
+    /// One of the 256 palette colors
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl From<vt100::Color> for CellColor {
+    fn from(color: vt100::Color) -> Self {
+        match color {
+            vt100::Color::Default => CellColor::Default,
+            vt100::Color::Idx(i) => CellColor::Indexed(i),
+            vt100::Color::Rgb(r, g, b) => CellColor::Rgb(r, g, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ScreenCell {
+    /// Grapheme in the cell; empty for blank cells and for the second
+    /// column of a wide character
+    pub text: String,
+    pub fg: CellColor,
+    pub bg: CellColor,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+    /// The character occupies this cell and the next
+    pub wide: bool,
+}
+
+/// Visible grid of a session's terminal
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ScreenSnapshot {
+    pub rows: u16,
+    pub cols: u16,
+    pub cursor_row: u16,
+    pub cursor_col: u16,
+    pub cursor_visible: bool,
+    /// The program switched to the alternate screen (full-screen TUI)
+    pub alternate_screen: bool,
+    /// One string per row, trailing blanks trimmed
+    pub lines: Vec<String>,
+    /// `rows` × `cols` cells, row-major; empty unless requested
+    pub cells: Vec<Vec<ScreenCell>>,
+}
+
+impl TerminalScreen {
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self {
+            parser: vt100::Parser::new(rows, cols, 0),
+        }
+    }
+
+    pub fn process(&mut self, bytes: &[u8]) {
+        self.parser.process(bytes);
+    }
+
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        self.parser.screen_mut().set_size(rows, cols);
+    }
+
+    /// Visible screen as text, one line per row
+    pub fn text(&self) -> String {
+        self.parser.screen().contents()
+    }
+
+    pub fn snapshot(&self, include_cells: bool) -> ScreenSnapshot {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let (cursor_row, cursor_col) = screen.cursor_position();
+
+        let cells = if include_cells {
+            (0..rows)
+                .map(|row| {
+                    (0..cols)
+                        .map(|col| match screen.cell(row, col) {
+                            Some(cell) => ScreenCell {
+                                text: cell.contents().to_string(),
+                                fg: cell.fgcolor().into(),
+                                bg: cell.bgcolor().into(),
+                                bold: cell.bold(),
+                                italic: cell.italic(),
+                                underline: cell.underline(),
+                                inverse: cell.inverse(),
+                                wide: cell.is_wide(),
+                            },
+                            None => ScreenCell::blank(),
+                        })
+                        .collect()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        ScreenSnapshot {
+            rows,
+            cols,
+            cursor_row,
+            cursor_col,
+            cursor_visible: !screen.hide_cursor(),
+            alternate_screen: screen.alternate_screen(),
+            lines: screen.rows(0, cols).collect(),
+            cells,
+        }
+    }
+}
+
+impl ScreenCell {
+    fn blank() -> Self {
+        Self {
+            text: String::new(),
+            fg: CellColor::Default,
+            bg: CellColor::Default,
+            bold: false,
+            italic: false,
+            underline: false,
+            inverse: false,
+            wide: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redraws_leave_only_final_screen() {
+        let mut screen = TerminalScreen::new(3, 20);
+        screen.process(b"working 10%\r");
+        screen.process(b"working 100%\r\n");
+        screen.process(b"\x1b[2J\x1b[Hdone");
+        let snap = screen.snapshot(false);
+        assert_eq!(snap.lines, vec!["done", "", ""]);
+        assert_eq!((snap.cursor_row, snap.cursor_col), (0, 4));
+        assert!(snap.cells.is_empty());
+    }
+
+    #[test]
+    fn test_cells_carry_attributes() {
+        let mut screen = TerminalScreen::new(2, 10);
+        screen.process(b"\x1b[1;31mA\x1b[0m\x1b[48;2;1;2;3mB");
+        let snap = screen.snapshot(true);
+        assert_eq!(snap.cells.len(), 2);
+        assert_eq!(snap.cells[0].len(), 10);
+        let a = &snap.cells[0][0];
+        assert_eq!(a.text, "A");
+        assert!(a.bold);
+        assert_eq!(a.fg, CellColor::Indexed(1));
+        assert_eq!(snap.cells[0][1].bg, CellColor::Rgb(1, 2, 3));
+        assert_eq!(snap.cells[1][0], ScreenCell::blank());
+    }
+
+    #[test]
+    fn test_alternate_screen_and_hidden_cursor() {
+        let mut screen = TerminalScreen::new(5, 20);
+        screen.process(b"shell prompt");
+        screen.process(b"\x1b[?1049h\x1b[?25l\x1b[Htui");
+        let snap = screen.snapshot(false);
+        assert!(snap.alternate_screen);
+        assert!(!snap.cursor_visible);
+        assert_eq!(snap.lines[0], "tui");
+        assert!(screen.text().starts_with("tui"));
+    }
+
+    #[test]
+    fn test_resize_changes_grid() {
+        let mut screen = TerminalScreen::new(24, 80);
+        screen.resize(10, 40);
+        let snap = screen.snapshot(true);
+        assert_eq!((snap.rows, snap.cols), (10, 40));
+        assert_eq!(snap.lines.len(), 10);
+        assert_eq!(snap.cells[9].len(), 40);
+    }
+
+    #[test]
+    fn test_wide_characters() {
+        let mut screen = TerminalScreen::new(1, 10);
+        screen.process("日本".as_bytes());
+        let snap = screen.snapshot(true);
+        assert_eq!(snap.lines[0], "日本");
+        assert!(snap.cells[0][0].wide);
+        assert_eq!(snap.cells[0][1].text, "");
+        assert_eq!(snap.cells[0][2].text, "本");
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// A batch of PTY output tagged with its position in the workspace's stream
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PtyChunk {
+    pub seq: u64,
+    pub data: String,
+}
+
+/// Replay payload returned to a (re)attaching client
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrollbackSnapshot {
+    pub chunks: Vec<PtyChunk>,
+    /// Sequence number the next live chunk will carry
+    pub next_seq: u64,
+    /// True when the requested start has already been evicted, so the
+    /// client should reset its terminal before writing `chunks`
+    pub truncated: bool,
+}
+
+/// Bounded ring buffer of recent PTY output, evicting the oldest chunks
+/// once `max_bytes` is exceeded. The newest chunk is always retained.
+pub struct Scrollback {
+    chunks: VecDeque<PtyChunk>,
+    bytes: usize,
+    max_bytes: usize,
+    next_seq: u64,
+}
+
+impl Scrollback {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            bytes: 0,
+            max_bytes,
+            next_seq: 0,
+        }
+    }
+
+    /// Append output and return it as a sequenced chunk
+    pub fn push(&mut self, data: String) -> PtyChunk {
+        let chunk = PtyChunk {
+            seq: self.next_seq,
+            data,
+        };
+        self.next_seq += 1;
+        self.bytes += chunk.data.len();
+        self.chunks.push_back(chunk.clone());
+
+        while self.bytes > self.max_bytes && self.chunks.len() > 1 {
+            if let Some(evicted) = self.chunks.pop_front() {
+                self.bytes -= evicted.data.len();
+            }
+        }
+
+        chunk
+    }
+
+    /// Chunks with `seq >= from` (everything retained when `from` is None)
+    pub fn snapshot(&self, from: Option<u64>) -> ScrollbackSnapshot {
+        let first_retained = self.chunks.front().map(|c| c.seq).unwrap_or(self.next_seq);
+        let from = from.unwrap_or(0);
+
+        ScrollbackSnapshot {
+            chunks: self
+                .chunks
+                .iter()
+                .filter(|c| c.seq >= from)
+                .cloned()
+                .collect(),
+            next_seq: self.next_seq,
+            truncated: from < first_retained,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_assigns_increasing_seq() {
+        let mut sb = Scrollback::new(1024);
+        assert_eq!(sb.push("a".into()).seq, 0);
+        assert_eq!(sb.push("b".into()).seq, 1);
+        assert_eq!(sb.snapshot(None).next_seq, 2);
+    }
+
+    #[test]
+    fn test_evicts_oldest_when_over_capacity() {
+        let mut sb = Scrollback::new(8);
+        sb.push("aaaa".into());
+        sb.push("bbbb".into());
+        sb.push("cccc".into());
+
+        let snap = sb.snapshot(None);
+        let data: Vec<&str> = snap.chunks.iter().map(|c| c.data.as_str()).collect();
+        assert_eq!(data, vec!["bbbb", "cccc"]);
+        assert!(snap.truncated);
+    }
+
+    #[test]
+    fn test_keeps_newest_chunk_even_if_oversized() {
+        let mut sb = Scrollback::new(4);
+        sb.push("small".into());
+        sb.push("this chunk is larger than the cap".into());
+        let snap = sb.snapshot(None);
+        assert_eq!(snap.chunks.len(), 1);
+        assert_eq!(snap.chunks[0].seq, 1);
+    }
+
+    #[test]
+    fn test_snapshot_resumes_from_seq() {
+        let mut sb = Scrollback::new(1024);
+        for s in ["one", "two", "three"] {
+            sb.push(s.into());
+        }
+        let snap = sb.snapshot(Some(1));
+        assert_eq!(snap.chunks.len(), 2);
+        assert_eq!(snap.chunks[0].data, "two");
+        assert!(!snap.truncated);
+    }
+
+    #[test]
+    fn test_snapshot_up_to_date_client_gets_nothing() {
+        let mut sb = Scrollback::new(1024);
+        sb.push("x".into());
+        let snap = sb.snapshot(Some(1));
+        assert!(snap.chunks.is_empty());
+        assert_eq!(snap.next_seq, 1);
+        assert!(!snap.truncated);
+    }
+
+    #[test]
+    fn test_empty_scrollback_is_not_truncated() {
+        let sb = Scrollback::new(1024);
+        let snap = sb.snapshot(None);
+        assert!(snap.chunks.is_empty());
+        assert!(!snap.truncated);
+    }
+}
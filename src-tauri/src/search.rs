@@ -0,0 +1,498 @@
+use crate::ansi::strip_ansi;
+use crate::transcript::{self, TranscriptEntry};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+
+/// Characters of context kept on each side of a match in a snippet
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+const DEFAULT_LIMIT: usize = 200;
+
+/// A line of terminal output with ANSI stripped, stamped with the time
+/// (Unix epoch millis) the output containing its start arrived
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedLine {
+    pub time_ms: u64,
+    pub text: String,
+}
+
+/// Splits a stream of output chunks into stripped lines. Partial lines are
+/// held until their newline arrives.
+#[derive(Default)]
+pub struct LineSplitter {
+    partial: String,
+    partial_time_ms: u64,
+}
+
+impl LineSplitter {
+    pub fn push(&mut self, time_ms: u64, data: &str) -> Vec<IndexedLine> {
+        let mut lines = Vec::new();
+        let mut rest = data;
+        while let Some(end) = rest.find('\n') {
+            if self.partial.is_empty() {
+                self.partial_time_ms = time_ms;
+            }
+            self.partial.push_str(&rest[..end]);
+            emit_lines(self.partial_time_ms, &std::mem::take(&mut self.partial), &mut lines);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            if self.partial.is_empty() {
+                self.partial_time_ms = time_ms;
+            }
+            self.partial.push_str(rest);
+        }
+        lines
+    }
+
+    /// Flush the trailing partial line
+    pub fn finish(&mut self) -> Vec<IndexedLine> {
+        let mut lines = Vec::new();
+        emit_lines(self.partial_time_ms, &std::mem::take(&mut self.partial), &mut lines);
+        lines
+    }
+}
+
+/// Strip a raw line; a bare `\r` redraw becomes several lines, blank ones
+/// are dropped
+fn emit_lines(time_ms: u64, raw: &str, out: &mut Vec<IndexedLine>) {
+    for text in strip_ansi(raw).split('\n') {
+        let text = text.trim_end();
+        if !text.trim().is_empty() {
+            out.push(IndexedLine {
+                time_ms,
+                text: text.to_string(),
+            });
+        }
+    }
+}
+
+/// Searchable output of a live session, bounded like the scrollback
+pub struct LineIndex {
+    lines: VecDeque<IndexedLine>,
+    splitter: LineSplitter,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl LineIndex {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            splitter: LineSplitter::default(),
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    pub fn push(&mut self, time_ms: u64, data: &str) {
+        for line in self.splitter.push(time_ms, data) {
+            self.bytes += line.text.len();
+            self.lines.push_back(line);
+        }
+        while self.bytes > self.max_bytes {
+            match self.lines.pop_front() {
+                Some(evicted) => self.bytes -= evicted.text.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Indexed lines plus the partial line not yet terminated
+    pub fn lines(&self) -> Vec<IndexedLine> {
+        let mut lines: Vec<IndexedLine> = self.lines.iter().cloned().collect();
+        if !self.splitter.partial.is_empty() {
+            emit_lines(self.splitter.partial_time_ms, &self.splitter.partial, &mut lines);
+        }
+        lines
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SearchFilters {
+    pub repo_path: Option<String>,
+    pub agent: Option<String>,
+    /// Inclusive lower bound, Unix epoch millis
+    pub from_ms: Option<u64>,
+    /// Inclusive upper bound, Unix epoch millis
+    pub to_ms: Option<u64>,
+    /// Treat the query as a regex; otherwise it is a case-insensitive literal
+    #[serde(default)]
+    pub regex: bool,
+    /// Maximum number of hits (default 200)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HitSource {
+    /// A recorded agent transcript (live or archived)
+    Transcript,
+    /// Output of a running app session that isn't recorded
+    Live,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SearchHit {
+    pub workspace_id: String,
+    /// Set for hits in extra terminals
+    pub terminal_id: Option<String>,
+    pub agent: Option<String>,
+    pub repo_path: Option<String>,
+    pub timestamp_ms: u64,
+    pub snippet: String,
+    pub source: HitSource,
+}
+
+/// What is known about the session a line came from
+#[derive(Debug, Clone, Default)]
+pub struct SessionMeta {
+    pub workspace_id: String,
+    pub terminal_id: Option<String>,
+    pub agent: Option<String>,
+    pub repo_path: Option<String>,
+    /// Worktree the session ran in; matched against the repo filter when
+    /// the workspace record is gone
+    pub working_dir: Option<String>,
+}
+
+pub struct SearchQuery {
+    pattern: Regex,
+    filters: SearchFilters,
+}
+
+impl SearchQuery {
+    pub fn new(query: &str, filters: SearchFilters) -> Result<Self, String> {
+        if query.is_empty() {
+            return Err("search query is empty".into());
+        }
+        let pattern = if filters.regex {
+            Regex::new(query)
+        } else {
+            RegexBuilder::new(&regex::escape(query))
+                .case_insensitive(true)
+                .build()
+        }
+        .map_err(|e| format!("invalid search pattern: {e}"))?;
+
+        let mut filters = filters;
+        // Registered repo paths may not be canonical; workspace records are
+        if let Some(repo) = &filters.repo_path {
+            if let Ok(canonical) = std::fs::canonicalize(repo) {
+                filters.repo_path = Some(canonical.to_string_lossy().to_string());
+            }
+        }
+        Ok(Self { pattern, filters })
+    }
+
+    pub fn limit(&self) -> usize {
+        self.filters.limit.unwrap_or(DEFAULT_LIMIT)
+    }
+
+    /// Whether a session passes the repo and agent filters
+    pub fn accepts(&self, meta: &SessionMeta) -> bool {
+        if let Some(agent) = &self.filters.agent {
+            if meta.agent.as_deref() != Some(agent.as_str()) {
+                return false;
+            }
+        }
+        if let Some(repo) = &self.filters.repo_path {
+            let in_repo = match (&meta.repo_path, &meta.working_dir) {
+                (Some(repo_path), _) => repo_path == repo,
+                (None, Some(dir)) => Path::new(dir).starts_with(repo),
+                (None, None) => false,
+            };
+            if !in_repo {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn in_range(&self, time_ms: u64) -> bool {
+        !matches!(self.filters.from_ms, Some(from) if time_ms < from)
+            && !matches!(self.filters.to_ms, Some(to) if time_ms > to)
+    }
+
+    /// Append a hit for every matching line
+    pub fn search_lines(
+        &self,
+        meta: &SessionMeta,
+        lines: &[IndexedLine],
+        source: HitSource,
+        hits: &mut Vec<SearchHit>,
+    ) {
+        for line in lines {
+            if !self.in_range(line.time_ms) {
+                continue;
+            }
+            if let Some(m) = self.pattern.find(&line.text) {
+                hits.push(SearchHit {
+                    workspace_id: meta.workspace_id.clone(),
+                    terminal_id: meta.terminal_id.clone(),
+                    agent: meta.agent.clone(),
+                    repo_path: meta.repo_path.clone(),
+                    timestamp_ms: line.time_ms,
+                    snippet: snippet(&line.text, m.start(), m.end()),
+                    source,
+                });
+            }
+        }
+    }
+
+    /// Search every transcript in `dir`. `workspace_meta` supplies what the
+    /// workspace record knows (None once the workspace has been removed).
+    pub fn search_transcripts(
+        &self,
+        dir: &Path,
+        workspace_meta: impl Fn(&str) -> Option<SessionMeta>,
+        hits: &mut Vec<SearchHit>,
+    ) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let workspace_id = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+            let records = match transcript::read_entries(&path) {
+                Ok(records) => records,
+                Err(_) => continue,
+            };
+            let known = workspace_meta(&workspace_id);
+            self.search_transcript(&workspace_id, known.as_ref(), &records, hits);
+        }
+    }
+
+    fn search_transcript(
+        &self,
+        workspace_id: &str,
+        known: Option<&SessionMeta>,
+        records: &[TranscriptEntry],
+        hits: &mut Vec<SearchHit>,
+    ) {
+        // Each run (from one `Start` to the next) has its own agent
+        let mut meta = SessionMeta {
+            workspace_id: workspace_id.to_string(),
+            repo_path: known.and_then(|k| k.repo_path.clone()),
+            ..Default::default()
+        };
+        let mut splitter = LineSplitter::default();
+        let mut lines = Vec::new();
+
+        for record in records {
+            match record {
+                TranscriptEntry::Start {
+                    agent, working_dir, ..
+                } => {
+                    lines.extend(splitter.finish());
+                    self.flush_run(&meta, &mut lines, hits);
+                    meta.agent = Some(agent.clone());
+                    meta.working_dir = Some(working_dir.clone());
+                }
+                TranscriptEntry::Output { time_ms, data } => {
+                    lines.extend(splitter.push(*time_ms, data));
+                }
+                _ => {}
+            }
+        }
+        lines.extend(splitter.finish());
+        self.flush_run(&meta, &mut lines, hits);
+    }
+
+    fn flush_run(&self, meta: &SessionMeta, lines: &mut Vec<IndexedLine>, hits: &mut Vec<SearchHit>) {
+        if !lines.is_empty() && self.accepts(meta) {
+            self.search_lines(meta, lines, HitSource::Transcript, hits);
+        }
+        lines.clear();
+    }
+}
+
+/// Newest hits first, capped at the query's limit
+pub fn finish_hits(mut hits: Vec<SearchHit>, limit: usize) -> Vec<SearchHit> {
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.timestamp_ms));
+    hits.truncate(limit);
+    hits
+}
+
+/// The matching line, shortened to some context around the match
+fn snippet(line: &str, start: usize, end: usize) -> String {
+    let before: Vec<char> = line[..start].chars().collect();
+    let after: Vec<char> = line[end..].chars().collect();
+
+    let mut out = String::new();
+    if before.len() > SNIPPET_CONTEXT_CHARS {
+        out.push('…');
+        out.extend(&before[before.len() - SNIPPET_CONTEXT_CHARS..]);
+    } else {
+        out.extend(&before);
+    }
+    out.push_str(&line[start..end]);
+    if after.len() > SNIPPET_CONTEXT_CHARS {
+        out.extend(&after[..SNIPPET_CONTEXT_CHARS]);
+        out.push('…');
+    } else {
+        out.extend(&after);
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::TranscriptWriter;
+
+    fn line(time_ms: u64, text: &str) -> IndexedLine {
+        IndexedLine {
+            time_ms,
+            text: text.into(),
+        }
+    }
+
+    fn meta(workspace_id: &str, agent: &str, repo: &str) -> SessionMeta {
+        SessionMeta {
+            workspace_id: workspace_id.into(),
+            agent: Some(agent.into()),
+            repo_path: Some(repo.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_splitter_strips_and_stamps_line_start() {
+        let mut s = LineSplitter::default();
+        assert!(s.push(10, "\x1b[32mrunning the mig").is_empty());
+        let lines = s.push(20, "ration\x1b[0m\r\nok\r\n");
+        assert_eq!(lines, vec![line(10, "running the migration"), line(20, "ok")]);
+        assert!(s.finish().is_empty());
+    }
+
+    #[test]
+    fn test_line_index_evicts_oldest_and_includes_partial() {
+        let mut index = LineIndex::new(10);
+        index.push(1, "first line\nsecond\n");
+        index.push(2, "tail");
+        let texts: Vec<String> = index.lines().into_iter().map(|l| l.text).collect();
+        assert_eq!(texts, vec!["second", "tail"]);
+    }
+
+    #[test]
+    fn test_literal_query_is_case_insensitive_and_escaped() {
+        let q = SearchQuery::new("a.b", SearchFilters::default()).unwrap();
+        let mut hits = Vec::new();
+        let lines = [line(1, "see A.B here"), line(2, "axb")];
+        q.search_lines(&meta("ws", "claude", "/r"), &lines, HitSource::Live, &mut hits);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "see A.B here");
+    }
+
+    #[test]
+    fn test_regex_query_and_invalid_pattern() {
+        let filters = SearchFilters {
+            regex: true,
+            ..Default::default()
+        };
+        let q = SearchQuery::new(r"migrat(e|ion)", filters.clone()).unwrap();
+        let mut hits = Vec::new();
+        let lines = [line(1, "run migrate"), line(2, "nothing")];
+        q.search_lines(&meta("ws", "claude", "/r"), &lines, HitSource::Live, &mut hits);
+        assert_eq!(hits.len(), 1);
+        assert!(SearchQuery::new("(", filters).is_err());
+        assert!(SearchQuery::new("", SearchFilters::default()).is_err());
+    }
+
+    #[test]
+    fn test_filters_on_agent_repo_and_date() {
+        let filters = SearchFilters {
+            agent: Some("codex".into()),
+            repo_path: Some("/repos/app".into()),
+            from_ms: Some(100),
+            to_ms: Some(200),
+            ..Default::default()
+        };
+        let q = SearchQuery::new("x", filters).unwrap();
+        assert!(!q.accepts(&meta("ws", "claude", "/repos/app")));
+        assert!(!q.accepts(&meta("ws", "codex", "/repos/other")));
+        assert!(q.accepts(&meta("ws", "codex", "/repos/app")));
+        let archived = SessionMeta {
+            workspace_id: "gone".into(),
+            agent: Some("codex".into()),
+            working_dir: Some("/repos/app/.worktrees/feat-1".into()),
+            ..Default::default()
+        };
+        assert!(q.accepts(&archived));
+
+        let mut hits = Vec::new();
+        let lines = [line(50, "x"), line(150, "x"), line(250, "x")];
+        q.search_lines(&meta("ws", "codex", "/repos/app"), &lines, HitSource::Live, &mut hits);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].timestamp_ms, 150);
+    }
+
+    #[test]
+    fn test_snippet_trims_long_lines_around_match() {
+        let long = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let s = snippet(&long, 100, 106);
+        assert!(s.starts_with('…') && s.ends_with('…'));
+        assert!(s.contains("needle"));
+        assert_eq!(s.chars().count(), 2 * SNIPPET_CONTEXT_CHARS + 6 + 2);
+    }
+
+    #[test]
+    fn test_search_transcripts_uses_run_agent_and_working_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = transcript::transcript_path(dir.path(), "old-ws").unwrap();
+        let mut writer = TranscriptWriter::open(&path).unwrap();
+        for (time_ms, agent) in [(1_000, "claude"), (2_000, "codex")] {
+            writer.record(&TranscriptEntry::Start {
+                time_ms,
+                agent: agent.into(),
+                label: agent.into(),
+                working_dir: "/repos/app/.worktrees/feat".into(),
+                cols: 80,
+                rows: 24,
+                reattached: false,
+            });
+            writer.record(&TranscriptEntry::Output {
+                time_ms: time_ms + 1,
+                data: format!("{agent} touched the migration\r\n"),
+            });
+        }
+
+        let filters = SearchFilters {
+            agent: Some("codex".into()),
+            repo_path: Some("/repos/app".into()),
+            ..Default::default()
+        };
+        let q = SearchQuery::new("migration", filters).unwrap();
+        let mut hits = Vec::new();
+        q.search_transcripts(dir.path(), |_| None, &mut hits);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].workspace_id, "old-ws");
+        assert_eq!(hits[0].timestamp_ms, 2_001);
+        assert_eq!(hits[0].source, HitSource::Transcript);
+    }
+
+    #[test]
+    fn test_finish_hits_sorts_newest_first_and_limits() {
+        let hit = |t| SearchHit {
+            workspace_id: "ws".into(),
+            terminal_id: None,
+            agent: None,
+            repo_path: None,
+            timestamp_ms: t,
+            snippet: String::new(),
+            source: HitSource::Live,
+        };
+        let hits = finish_hits(vec![hit(1), hit(3), hit(2)], 2);
+        let times: Vec<u64> = hits.iter().map(|h| h.timestamp_ms).collect();
+        assert_eq!(times, vec![3, 2]);
+    }
+}
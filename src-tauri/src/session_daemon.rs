@@ -0,0 +1,671 @@
+//! Optional background process that owns agent PTYs so sessions survive an
+//! app restart. The app is a client: it spawns sessions through the daemon,
+//! attaches to their output over a Unix socket and reattaches on startup.
+//!
+//! Protocol: every connection starts with one JSON request line and gets one
+//! JSON response line. `attach` then switches the connection to raw mode:
+//! PTY output flows to the client and bytes from the client go to the PTY.
+
+use crate::pty_manager::{ExitInfo, SessionKey};
+use portable_pty::{ChildKiller, CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Flag passed to the app executable to run it as the session daemon
+pub const DAEMON_FLAG: &str = "--session-daemon";
+/// Raw output retained per session for replay on reattach
+const REPLAY_BYTES: usize = 1024 * 1024;
+const READ_BUF_SIZE: usize = 4096;
+const STARTUP_TIMEOUT_MS: u64 = 3000;
+const STARTUP_POLL_MS: u64 = 50;
+/// Grace for the PTY to drain after the child exits before attached
+/// clients are disconnected (grandchildren may keep the PTY open)
+const EXIT_DRAIN_MS: u64 = 500;
+/// The daemon exits after this long without any sessions
+const IDLE_EXIT_SECS: u64 = 60;
+/// Output chunks queued for an attached client; a client that falls this
+/// far behind is disconnected rather than stalling the PTY reader
+const CLIENT_QUEUE_CHUNKS: usize = 1024;
+
+pub fn socket_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".ocestrater")
+        .join("sessiond.sock")
+}
+
+/// What to run in a daemon-owned PTY
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnSpec {
+    pub key: SessionKey,
+    pub argv: Vec<String>,
+    pub cwd: Option<String>,
+    pub env: HashMap<String, String>,
+    pub rows: u16,
+    pub cols: u16,
+    pub label: String,
+}
+
+impl SpawnSpec {
+    pub fn from_command(key: SessionKey, cmd: &CommandBuilder, size: PtySize, label: String) -> Self {
+        Self {
+            key,
+            argv: cmd
+                .get_argv()
+                .iter()
+                .map(|a| a.to_string_lossy().into_owned())
+                .collect(),
+            cwd: cmd.get_cwd().map(|c| c.to_string_lossy().into_owned()),
+            env: cmd
+                .iter_extra_env_as_str()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            rows: size.rows,
+            cols: size.cols,
+            label,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonSessionInfo {
+    pub key: SessionKey,
+    pub label: String,
+    pub pid: Option<u32>,
+    pub alive: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum DaemonRequest {
+    Ping,
+    Spawn(SpawnSpec),
+    List,
+    /// Switch to raw mode, optionally replaying retained output first
+    Attach { key: SessionKey, replay: bool },
+    Resize { key: SessionKey, rows: u16, cols: u16 },
+    Kill { key: SessionKey },
+    /// Block until the session's process exits
+    Wait { key: SessionKey },
+    /// Forget a session, killing it if it is still running
+    Release { key: SessionKey },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonResponse {
+    Ok,
+    /// Raw mode follows; the first `replay_bytes` are retained output
+    Attached { replay_bytes: usize },
+    Spawned { pid: Option<u32> },
+    Sessions { sessions: Vec<DaemonSessionInfo> },
+    Exited { exit: ExitInfo },
+    Error { message: String },
+}
+
+// ── Client ──
+
+/// Connection factory for the session daemon; each request uses its own
+/// connection so callers never share socket state
+#[derive(Debug, Clone)]
+pub struct DaemonClient {
+    socket: PathBuf,
+}
+
+impl DaemonClient {
+    pub fn new(socket: PathBuf) -> Self {
+        Self { socket }
+    }
+
+    /// Start the daemon from the current executable unless one is listening
+    pub fn ensure_running(&self) -> Result<(), String> {
+        if self.request(&DaemonRequest::Ping).is_ok() {
+            return Ok(());
+        }
+
+        let exe = std::env::current_exe().map_err(|e| format!("daemon exe error: {e}"))?;
+        start_detached(&exe, &self.socket)?;
+
+        let deadline = Instant::now() + Duration::from_millis(STARTUP_TIMEOUT_MS);
+        while Instant::now() < deadline {
+            if self.request(&DaemonRequest::Ping).is_ok() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(STARTUP_POLL_MS));
+        }
+        Err("session daemon did not start".into())
+    }
+
+    pub fn request(&self, request: &DaemonRequest) -> Result<DaemonResponse, String> {
+        let mut stream = self.send(request)?;
+        match read_message(&mut stream)? {
+            DaemonResponse::Error { message } => Err(message),
+            response => Ok(response),
+        }
+    }
+
+    /// Attach to a session's PTY. Returns the raw stream and how many of
+    /// the bytes that follow are replayed output.
+    pub fn attach(&self, key: &SessionKey, replay: bool) -> Result<(UnixStream, usize), String> {
+        let mut stream = self.send(&DaemonRequest::Attach {
+            key: key.clone(),
+            replay,
+        })?;
+        match read_message(&mut stream)? {
+            DaemonResponse::Attached { replay_bytes } => Ok((stream, replay_bytes)),
+            DaemonResponse::Error { message } => Err(message),
+            other => Err(format!("unexpected daemon response: {other:?}")),
+        }
+    }
+
+    pub fn spawn(&self, spec: SpawnSpec) -> Result<Option<u32>, String> {
+        match self.request(&DaemonRequest::Spawn(spec))? {
+            DaemonResponse::Spawned { pid } => Ok(pid),
+            other => Err(format!("unexpected daemon response: {other:?}")),
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<DaemonSessionInfo>, String> {
+        match self.request(&DaemonRequest::List)? {
+            DaemonResponse::Sessions { sessions } => Ok(sessions),
+            other => Err(format!("unexpected daemon response: {other:?}")),
+        }
+    }
+
+    pub fn wait(&self, key: &SessionKey) -> Result<ExitInfo, String> {
+        match self.request(&DaemonRequest::Wait { key: key.clone() })? {
+            DaemonResponse::Exited { exit } => Ok(exit),
+            other => Err(format!("unexpected daemon response: {other:?}")),
+        }
+    }
+
+    fn send(&self, request: &DaemonRequest) -> Result<UnixStream, String> {
+        let mut stream = UnixStream::connect(&self.socket)
+            .map_err(|e| format!("daemon connect error: {e}"))?;
+        write_message(&mut stream, request)?;
+        Ok(stream)
+    }
+}
+
+fn start_detached(exe: &Path, socket: &Path) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    std::process::Command::new(exe)
+        .arg(DAEMON_FLAG)
+        .arg(socket)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        // Own process group, so signals aimed at the app don't reach it
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("daemon start error: {e}"))?;
+    Ok(())
+}
+
+fn write_message<T: Serialize>(stream: &mut UnixStream, message: &T) -> Result<(), String> {
+    let mut line = serde_json::to_vec(message).map_err(|e| format!("serialize error: {e}"))?;
+    line.push(b'\n');
+    stream
+        .write_all(&line)
+        .map_err(|e| format!("daemon write error: {e}"))
+}
+
+/// Read one JSON line byte by byte, so raw data that follows on the same
+/// connection is left unread
+fn read_message<T: for<'de> Deserialize<'de>>(stream: &mut UnixStream) -> Result<T, String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        match stream.read(&mut byte) {
+            Ok(0) => return Err("daemon closed the connection".into()),
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("daemon read error: {e}")),
+        }
+    }
+    serde_json::from_slice(&line).map_err(|e| format!("daemon message error: {e}"))
+}
+
+// ── Server ──
+
+struct SessionOutput {
+    replay: VecDeque<u8>,
+    /// Output queues of attached clients, each drained by its own thread
+    subscribers: Vec<SyncSender<Arc<[u8]>>>,
+    closed: bool,
+}
+
+impl SessionOutput {
+    fn push(&mut self, data: &[u8]) {
+        self.replay.extend(data);
+        let excess = self.replay.len().saturating_sub(REPLAY_BYTES);
+        self.replay.drain(..excess);
+        let chunk: Arc<[u8]> = data.into();
+        self.subscribers.retain(|tx| tx.try_send(chunk.clone()).is_ok());
+    }
+
+    /// Dropping the queues ends each client once its queue is drained
+    fn close(&mut self) {
+        self.closed = true;
+        self.subscribers.clear();
+    }
+}
+
+struct DaemonSession {
+    label: String,
+    pid: Option<u32>,
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    killer: Mutex<Box<dyn ChildKiller + Send + Sync>>,
+    output: Mutex<SessionOutput>,
+    exit: Mutex<Option<ExitInfo>>,
+    exited: Condvar,
+}
+
+impl DaemonSession {
+    fn alive(&self) -> bool {
+        self.exit.lock().map(|e| e.is_none()).unwrap_or(false)
+    }
+}
+
+type Sessions = Arc<Mutex<HashMap<SessionKey, Arc<DaemonSession>>>>;
+
+/// Run the daemon until it has been idle for `IDLE_EXIT_SECS`
+pub fn run(socket: &Path) -> Result<(), String> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err("session daemon already running".into());
+    }
+    if let Some(parent) = socket.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::remove_file(socket);
+
+    // Created owner-only, so it is never reachable by other users
+    // SAFETY: umask only swaps the process file mode mask
+    let umask = unsafe { libc::umask(0o177) };
+    let bound = UnixListener::bind(socket);
+    unsafe {
+        libc::umask(umask);
+    }
+    let listener = bound.map_err(|e| format!("daemon bind error: {e}"))?;
+
+    let sessions: Sessions = Arc::new(Mutex::new(HashMap::new()));
+    let stopping = Arc::new(AtomicBool::new(false));
+
+    let idle_sessions = sessions.clone();
+    let idle_stopping = stopping.clone();
+    let idle_socket = socket.to_path_buf();
+    std::thread::spawn(move || {
+        let mut idle_since: Option<Instant> = None;
+        loop {
+            std::thread::sleep(Duration::from_secs(5));
+            let empty = idle_sessions.lock().map(|s| s.is_empty()).unwrap_or(false);
+            idle_since = if empty { idle_since.or(Some(Instant::now())) } else { None };
+            if idle_since.is_some_and(|t| t.elapsed() >= Duration::from_secs(IDLE_EXIT_SECS)) {
+                // Wake the accept loop so `run` returns
+                idle_stopping.store(true, Ordering::SeqCst);
+                let _ = UnixStream::connect(&idle_socket);
+                return;
+            }
+        }
+    });
+
+    for stream in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        if let Ok(stream) = stream {
+            let sessions = sessions.clone();
+            std::thread::spawn(move || handle_connection(stream, &sessions));
+        }
+    }
+    let _ = std::fs::remove_file(socket);
+    Ok(())
+}
+
+fn handle_connection(mut stream: UnixStream, sessions: &Sessions) {
+    let request: DaemonRequest = match read_message(&mut stream) {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+
+    if let DaemonRequest::Attach { key, replay } = request {
+        match lookup(sessions, &key) {
+            Ok(session) => attach(stream, &session, replay),
+            Err(message) => {
+                let _ = write_message(&mut stream, &DaemonResponse::Error { message });
+            }
+        }
+        return;
+    }
+
+    let response = handle_request(request, sessions)
+        .unwrap_or_else(|message| DaemonResponse::Error { message });
+    let _ = write_message(&mut stream, &response);
+}
+
+fn handle_request(request: DaemonRequest, sessions: &Sessions) -> Result<DaemonResponse, String> {
+    match request {
+        DaemonRequest::Ping => Ok(DaemonResponse::Ok),
+        DaemonRequest::Spawn(spec) => spawn_session(spec, sessions),
+        DaemonRequest::List => {
+            let map = sessions.lock().map_err(|e| e.to_string())?;
+            let sessions = map
+                .iter()
+                .map(|(key, s)| DaemonSessionInfo {
+                    key: key.clone(),
+                    label: s.label.clone(),
+                    pid: s.pid,
+                    alive: s.alive(),
+                })
+                .collect();
+            Ok(DaemonResponse::Sessions { sessions })
+        }
+        DaemonRequest::Resize { key, rows, cols } => {
+            let session = lookup(sessions, &key)?;
+            let master = session.master.lock().map_err(|e| e.to_string())?;
+            master
+                .resize(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                })
+                .map_err(|e| format!("resize error: {e}"))?;
+            Ok(DaemonResponse::Ok)
+        }
+        DaemonRequest::Kill { key } => {
+            let session = lookup(sessions, &key)?;
+            let mut killer = session.killer.lock().map_err(|e| e.to_string())?;
+            let _ = killer.kill();
+            Ok(DaemonResponse::Ok)
+        }
+        DaemonRequest::Wait { key } => {
+            let session = lookup(sessions, &key)?;
+            let mut exit = session.exit.lock().map_err(|e| e.to_string())?;
+            while exit.is_none() {
+                exit = session.exited.wait(exit).map_err(|e| e.to_string())?;
+            }
+            Ok(DaemonResponse::Exited {
+                exit: exit.clone().unwrap_or_default(),
+            })
+        }
+        DaemonRequest::Release { key } => {
+            let removed = sessions.lock().map_err(|e| e.to_string())?.remove(&key);
+            if let Some(session) = removed {
+                if session.alive() {
+                    if let Ok(mut killer) = session.killer.lock() {
+                        let _ = killer.kill();
+                    }
+                }
+            }
+            Ok(DaemonResponse::Ok)
+        }
+        DaemonRequest::Attach { .. } => Err("attach must be the only request on a connection".into()),
+    }
+}
+
+fn lookup(sessions: &Sessions, key: &SessionKey) -> Result<Arc<DaemonSession>, String> {
+    sessions
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(key)
+        .cloned()
+        .ok_or_else(|| format!("no daemon session: {}", key.event_id()))
+}
+
+fn spawn_session(spec: SpawnSpec, sessions: &Sessions) -> Result<DaemonResponse, String> {
+    if lookup(sessions, &spec.key).is_ok_and(|s| s.alive()) {
+        return Err(format!("session already exists: {}", spec.key.event_id()));
+    }
+
+    let pair = NativePtySystem::default()
+        .openpty(PtySize {
+            rows: spec.rows,
+            cols: spec.cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| format!("pty open error: {e}"))?;
+
+    let mut cmd = CommandBuilder::from_argv(spec.argv.iter().map(Into::into).collect());
+    if let Some(cwd) = &spec.cwd {
+        cmd.cwd(cwd);
+    }
+    for (k, v) in &spec.env {
+        cmd.env(k, v);
+    }
+
+    let mut child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| format!("spawn error: {e}"))?;
+    let mut reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| format!("reader clone error: {e}"))?;
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| format!("writer error: {e}"))?;
+
+    let pid = child.process_id();
+    let session = Arc::new(DaemonSession {
+        label: spec.label,
+        pid,
+        master: Mutex::new(pair.master),
+        writer: Mutex::new(writer),
+        killer: Mutex::new(child.clone_killer()),
+        output: Mutex::new(SessionOutput {
+            replay: VecDeque::new(),
+            subscribers: Vec::new(),
+            closed: false,
+        }),
+        exit: Mutex::new(None),
+        exited: Condvar::new(),
+    });
+
+    let reading = session.clone();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; READ_BUF_SIZE];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if let Ok(mut out) = reading.output.lock() {
+                        out.push(&buf[..n]);
+                    }
+                }
+            }
+        }
+        if let Ok(mut out) = reading.output.lock() {
+            out.close();
+        }
+    });
+
+    let waiting = session.clone();
+    std::thread::spawn(move || {
+        let info = child
+            .wait()
+            .map(|status| ExitInfo::from_status(&status))
+            .unwrap_or_default();
+        if let Ok(mut exit) = waiting.exit.lock() {
+            *exit = Some(info);
+        }
+        waiting.exited.notify_all();
+
+        std::thread::sleep(Duration::from_millis(EXIT_DRAIN_MS));
+        if let Ok(mut out) = waiting.output.lock() {
+            out.close();
+        }
+    });
+
+    sessions
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(spec.key, session);
+    Ok(DaemonResponse::Spawned { pid })
+}
+
+/// Serve an attached client: replay, subscribe to live output, and forward
+/// the client's bytes to the PTY until it disconnects. Output is written
+/// from the client's own thread, so a slow client never holds up the PTY.
+fn attach(mut stream: UnixStream, session: &DaemonSession, replay: bool) {
+    // Replay and subscription are taken together, so no output falls between
+    let (replayed, queue) = {
+        let mut out = match session.output.lock() {
+            Ok(out) => out,
+            Err(_) => return,
+        };
+        let replayed: Vec<u8> = if replay { out.replay.iter().copied().collect() } else { Vec::new() };
+        let (tx, rx) = mpsc::sync_channel(CLIENT_QUEUE_CHUNKS);
+        if !out.closed {
+            out.subscribers.push(tx);
+        }
+        (replayed, rx)
+    };
+
+    let replay_bytes = replayed.len();
+    if write_message(&mut stream, &DaemonResponse::Attached { replay_bytes }).is_err() {
+        return;
+    }
+    match stream.try_clone() {
+        Ok(client) => {
+            std::thread::spawn(move || forward_output(client, &replayed, queue));
+        }
+        Err(_) => return,
+    }
+
+    let mut buf = [0u8; READ_BUF_SIZE];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let mut writer = match session.writer.lock() {
+                    Ok(w) => w,
+                    Err(_) => break,
+                };
+                if writer.write_all(&buf[..n]).and_then(|_| writer.flush()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Write the replay, then queued output, to an attached client; the
+/// connection is closed once the queue ends or the client stops reading
+fn forward_output(mut client: UnixStream, replayed: &[u8], queue: Receiver<Arc<[u8]>>) {
+    if client.write_all(replayed).is_ok() {
+        for chunk in queue {
+            if client.write_all(&chunk).is_err() {
+                break;
+            }
+        }
+    }
+    let _ = client.shutdown(Shutdown::Both);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_wire_format() {
+        let req = DaemonRequest::Resize {
+            key: SessionKey::agent("ws-1"),
+            rows: 24,
+            cols: 80,
+        };
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["op"], "resize");
+        assert_eq!(json["key"]["workspace_id"], "ws-1");
+        assert_eq!(json["key"]["terminal_id"], "agent");
+    }
+
+    #[test]
+    fn test_spawn_request_roundtrip() {
+        let mut cmd = CommandBuilder::new("claude");
+        cmd.args(["--model", "sonnet"]);
+        cmd.cwd("/tmp/wt");
+        cmd.env("API_KEY", "k");
+        let size = PtySize {
+            rows: 30,
+            cols: 100,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        let spec = SpawnSpec::from_command(SessionKey::agent("ws"), &cmd, size, "claude".into());
+        assert_eq!(spec.argv, vec!["claude", "--model", "sonnet"]);
+        assert_eq!(spec.cwd.as_deref(), Some("/tmp/wt"));
+        assert_eq!(spec.env.get("API_KEY").map(String::as_str), Some("k"));
+
+        let json = serde_json::to_string(&DaemonRequest::Spawn(spec)).unwrap();
+        let parsed: DaemonRequest = serde_json::from_str(&json).unwrap();
+        assert!(matches!(parsed, DaemonRequest::Spawn(s) if s.rows == 30 && s.label == "claude"));
+    }
+
+    #[test]
+    fn test_replay_buffer_is_bounded() {
+        let mut out = SessionOutput {
+            replay: VecDeque::new(),
+            subscribers: Vec::new(),
+            closed: false,
+        };
+        out.push(&vec![b'a'; REPLAY_BYTES]);
+        out.push(b"tail");
+        assert_eq!(out.replay.len(), REPLAY_BYTES);
+        assert!(out.replay.iter().rev().take(4).eq(b"liat".iter()));
+    }
+
+    #[test]
+    fn test_daemon_spawn_attach_wait() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("d.sock");
+        let server_socket = socket.clone();
+        std::thread::spawn(move || run(&server_socket));
+        let client = DaemonClient::new(socket);
+        let deadline = Instant::now() + Duration::from_secs(3);
+        while client.request(&DaemonRequest::Ping).is_err() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        let key = SessionKey::agent("ws");
+        let mut cmd = CommandBuilder::new("sh");
+        cmd.args(["-c", "read line; echo got:$line; exit 3"]);
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        let pid = client
+            .spawn(SpawnSpec::from_command(key.clone(), &cmd, size, "sh".into()))
+            .unwrap();
+        assert!(pid.is_some());
+
+        let (mut stream, _) = client.attach(&key, true).unwrap();
+        stream.write_all(b"hello\n").unwrap();
+        let mut output = String::new();
+        let _ = stream.read_to_string(&mut output);
+        assert!(output.contains("got:hello"), "{output:?}");
+
+        assert_eq!(client.wait(&key).unwrap().code, Some(3));
+        let sessions = client.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(!sessions[0].alive);
+
+        client.request(&DaemonRequest::Release { key }).unwrap();
+        assert!(client.list().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Queue entry as reported to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QueuedSpawnInfo {
+    pub workspace_id: String,
+    /// 1-based; position 1 starts next
+    pub position: usize,
+    pub priority: i32,
+}
+
+struct QueueEntry<T> {
+    workspace_id: String,
+    priority: i32,
+    request: T,
+}
+
+/// Pending agent spawns waiting for a free session slot. Higher priority
+/// entries go first; equal priorities are served in arrival order. Entries
+/// can be moved explicitly, which takes precedence over priority.
+pub struct SpawnQueue<T> {
+    entries: VecDeque<QueueEntry<T>>,
+}
+
+impl<T> SpawnQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Enqueue a spawn and return its 1-based position
+    pub fn push(&mut self, workspace_id: &str, priority: i32, request: T) -> Result<usize, String> {
+        if self.contains(workspace_id) {
+            return Err(format!("spawn already queued: {workspace_id}"));
+        }
+
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.priority < priority)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(
+            index,
+            QueueEntry {
+                workspace_id: workspace_id.to_string(),
+                priority,
+                request,
+            },
+        );
+        Ok(index + 1)
+    }
+
+    /// Take the next spawn to start
+    pub fn pop(&mut self) -> Option<(String, T)> {
+        self.entries
+            .pop_front()
+            .map(|e| (e.workspace_id, e.request))
+    }
+
+    /// Cancel a queued spawn, returning its request if it was queued
+    pub fn remove(&mut self, workspace_id: &str) -> Option<T> {
+        let index = self.index_of(workspace_id)?;
+        self.entries.remove(index).map(|e| e.request)
+    }
+
+    /// Move a queued spawn to a 1-based position (clamped to the queue length)
+    pub fn move_to(&mut self, workspace_id: &str, position: usize) -> Result<usize, String> {
+        let index = self
+            .index_of(workspace_id)
+            .ok_or_else(|| format!("spawn not queued: {workspace_id}"))?;
+        if position == 0 {
+            return Err("queue positions start at 1".into());
+        }
+
+        let entry = self
+            .entries
+            .remove(index)
+            .ok_or_else(|| format!("spawn not queued: {workspace_id}"))?;
+        let target = (position - 1).min(self.entries.len());
+        self.entries.insert(target, entry);
+        Ok(target + 1)
+    }
+
+    pub fn contains(&self, workspace_id: &str) -> bool {
+        self.index_of(workspace_id).is_some()
+    }
+
+    /// 1-based position of a queued spawn
+    pub fn position(&self, workspace_id: &str) -> Option<usize> {
+        self.index_of(workspace_id).map(|i| i + 1)
+    }
+
+    pub fn list(&self) -> Vec<QueuedSpawnInfo> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| QueuedSpawnInfo {
+                workspace_id: e.workspace_id.clone(),
+                position: i + 1,
+                priority: e.priority,
+            })
+            .collect()
+    }
+
+    fn index_of(&self, workspace_id: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|e| e.workspace_id == workspace_id)
+    }
+}
+
+impl<T> Default for SpawnQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(queue: &SpawnQueue<()>) -> Vec<String> {
+        queue.list().into_iter().map(|q| q.workspace_id).collect()
+    }
+
+    #[test]
+    fn test_fifo_for_equal_priority() {
+        let mut q = SpawnQueue::new();
+        assert_eq!(q.push("a", 0, ()).unwrap(), 1);
+        assert_eq!(q.push("b", 0, ()).unwrap(), 2);
+        assert_eq!(q.push("c", 0, ()).unwrap(), 3);
+        assert_eq!(q.pop().map(|(id, _)| id), Some("a".to_string()));
+        assert_eq!(ids(&q), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_higher_priority_goes_first() {
+        let mut q = SpawnQueue::new();
+        q.push("low", 0, ()).unwrap();
+        q.push("high", 5, ()).unwrap();
+        assert_eq!(q.push("mid", 1, ()).unwrap(), 2);
+        q.push("high2", 5, ()).unwrap();
+        assert_eq!(ids(&q), vec!["high", "high2", "mid", "low"]);
+    }
+
+    #[test]
+    fn test_duplicate_push_is_rejected() {
+        let mut q = SpawnQueue::new();
+        q.push("a", 0, ()).unwrap();
+        assert!(q.push("a", 1, ()).unwrap_err().contains("already queued"));
+    }
+
+    #[test]
+    fn test_remove_cancels_and_shifts_positions() {
+        let mut q = SpawnQueue::new();
+        q.push("a", 0, 1).unwrap();
+        q.push("b", 0, 2).unwrap();
+        assert_eq!(q.remove("a"), Some(1));
+        assert_eq!(q.remove("a"), None);
+        assert_eq!(q.position("b"), Some(1));
+    }
+
+    #[test]
+    fn test_move_to_reorders() {
+        let mut q = SpawnQueue::new();
+        for id in ["a", "b", "c"] {
+            q.push(id, 0, ()).unwrap();
+        }
+        assert_eq!(q.move_to("c", 1).unwrap(), 1);
+        assert_eq!(ids(&q), vec!["c", "a", "b"]);
+        assert_eq!(q.move_to("c", 99).unwrap(), 3);
+        assert_eq!(ids(&q), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_move_to_rejects_unknown_and_zero() {
+        let mut q = SpawnQueue::new();
+        q.push("a", 0, ()).unwrap();
+        assert!(q.move_to("x", 1).is_err());
+        assert!(q.move_to("a", 0).is_err());
+    }
+
+    #[test]
+    fn test_empty_queue() {
+        let mut q: SpawnQueue<()> = SpawnQueue::new();
+        assert!(q.is_empty());
+        assert!(q.pop().is_none());
+        assert_eq!(q.position("a"), None);
+    }
+}
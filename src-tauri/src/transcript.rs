@@ -0,0 +1,355 @@
+use crate::ansi::strip_ansi;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One line of a transcript file (JSON Lines). Times are Unix epoch millis.
+/// A workspace's transcript accumulates every agent run, each opened by a
+/// `Start` entry.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TranscriptEntry {
+    Start {
+        time_ms: u64,
+        agent: String,
+        label: String,
+        working_dir: String,
+        cols: u16,
+        rows: u16,
+        /// The app reattached to a session that was already running
+        #[serde(default)]
+        reattached: bool,
+    },
+    Output {
+        time_ms: u64,
+        data: String,
+    },
+    Input {
+        time_ms: u64,
+        data: String,
+    },
+    Resize {
+        time_ms: u64,
+        cols: u16,
+        rows: u16,
+    },
+    Exit {
+        time_ms: u64,
+        code: Option<u32>,
+        signal: Option<String>,
+    },
+}
+
+impl TranscriptEntry {
+    pub fn time_ms(&self) -> u64 {
+        match self {
+            TranscriptEntry::Start { time_ms, .. }
+            | TranscriptEntry::Output { time_ms, .. }
+            | TranscriptEntry::Input { time_ms, .. }
+            | TranscriptEntry::Resize { time_ms, .. }
+            | TranscriptEntry::Exit { time_ms, .. } => *time_ms,
+        }
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub fn transcripts_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".ocestrater")
+        .join("transcripts")
+}
+
+/// Transcript file of a workspace. Ids come from the frontend, so anything
+/// that could escape the directory is rejected.
+pub fn transcript_path(dir: &Path, workspace_id: &str) -> Result<PathBuf, String> {
+    let valid = !workspace_id.is_empty()
+        && workspace_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("invalid workspace id: {workspace_id}"));
+    }
+    Ok(dir.join(format!("{workspace_id}.jsonl")))
+}
+
+/// Appends entries to a transcript file, one JSON line per entry. Files and
+/// their directory are private to the user.
+pub struct TranscriptWriter {
+    file: File,
+}
+
+impl TranscriptWriter {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            let mut dir = std::fs::DirBuilder::new();
+            dir.recursive(true);
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut dir, 0o700);
+            dir.create(parent).map_err(|e| format!("mkdir error: {e}"))?;
+            restrict(parent, 0o700)?;
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options
+            .open(path)
+            .map_err(|e| format!("transcript open error: {e}"))?;
+        // Files and dirs from before may still be readable by others
+        restrict(path, 0o600)?;
+        Ok(Self { file })
+    }
+
+    /// Append an entry. Recording is best effort: a failed write is logged
+    /// and never interrupts the session.
+    pub fn record(&mut self, entry: &TranscriptEntry) {
+        let result = serde_json::to_string(entry)
+            .map_err(|e| format!("serialize error: {e}"))
+            .and_then(|line| {
+                writeln!(self.file, "{line}").map_err(|e| format!("write error: {e}"))
+            });
+        if let Err(e) = result {
+            eprintln!("transcript {e}");
+        }
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn restrict(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("chmod error: {e}"))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn restrict(_path: &Path, _mode: u32) -> Result<(), String> {
+    Ok(())
+}
+
+/// Read a transcript. Lines that don't parse (e.g. a line still being
+/// written) are skipped.
+pub fn read_entries(path: &Path) -> Result<Vec<TranscriptEntry>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("transcript read error: {e}"))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Agent output as plain text with ANSI sequences stripped. Output is joined
+/// per run before stripping so escape sequences split across chunks are
+/// still removed.
+pub fn to_plain_text(entries: &[TranscriptEntry]) -> String {
+    let mut text = String::new();
+    let mut run = String::new();
+    for entry in entries {
+        match entry {
+            TranscriptEntry::Start { .. } if !run.is_empty() => {
+                text.push_str(&strip_ansi(&run));
+                if !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                run.clear();
+            }
+            TranscriptEntry::Output { data, .. } => run.push_str(data),
+            _ => {}
+        }
+    }
+    text.push_str(&strip_ansi(&run));
+    text
+}
+
+/// Render a transcript as an asciicast v2 recording. The header takes the
+/// terminal size of the first run; later runs are marked with `m` events.
+pub fn to_asciicast(entries: &[TranscriptEntry]) -> Result<String, String> {
+    let (start_ms, cols, rows, label) = entries
+        .iter()
+        .find_map(|e| match e {
+            TranscriptEntry::Start {
+                time_ms,
+                cols,
+                rows,
+                label,
+                ..
+            } => Some((*time_ms, *cols, *rows, label.clone())),
+            _ => None,
+        })
+        .ok_or_else(|| "transcript is empty".to_string())?;
+
+    let header = serde_json::json!({
+        "version": 2,
+        "width": cols,
+        "height": rows,
+        "timestamp": start_ms / 1000,
+        "title": label,
+        "env": { "TERM": "xterm-256color" },
+    });
+    let mut out = header.to_string();
+    out.push('\n');
+
+    let mut first = true;
+    for entry in entries {
+        let elapsed = entry.time_ms().saturating_sub(start_ms) as f64 / 1000.0;
+        let (code, data) = match entry {
+            TranscriptEntry::Start { .. } if first => {
+                first = false;
+                continue;
+            }
+            TranscriptEntry::Start { label, .. } => ("m", label.clone()),
+            TranscriptEntry::Output { data, .. } => ("o", data.clone()),
+            TranscriptEntry::Input { data, .. } => ("i", data.clone()),
+            TranscriptEntry::Resize { cols, rows, .. } => ("r", format!("{cols}x{rows}")),
+            TranscriptEntry::Exit { code, signal, .. } => {
+                let how = match (code, signal) {
+                    (_, Some(signal)) => signal.clone(),
+                    (Some(code), None) => format!("code {code}"),
+                    (None, None) => "unknown status".to_string(),
+                };
+                ("m", format!("exited ({how})"))
+            }
+        };
+        out.push_str(&serde_json::json!([elapsed, code, data]).to_string());
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(time_ms: u64) -> TranscriptEntry {
+        TranscriptEntry::Start {
+            time_ms,
+            agent: "claude".into(),
+            label: "claude --model opus".into(),
+            working_dir: "/tmp/repo/.worktrees/feat".into(),
+            cols: 120,
+            rows: 40,
+            reattached: false,
+        }
+    }
+
+    fn output(time_ms: u64, data: &str) -> TranscriptEntry {
+        TranscriptEntry::Output {
+            time_ms,
+            data: data.into(),
+        }
+    }
+
+    #[test]
+    fn test_writer_appends_and_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = transcript_path(dir.path(), "ws-1").unwrap();
+        let mut writer = TranscriptWriter::open(&path).unwrap();
+        writer.record(&start(1_000));
+        writer.record(&output(1_500, "hello\r\n"));
+        drop(writer);
+
+        let mut writer = TranscriptWriter::open(&path).unwrap();
+        writer.record(&TranscriptEntry::Input {
+            time_ms: 2_000,
+            data: "y\n".into(),
+        });
+
+        let entries = read_entries(&path).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], output(1_500, "hello\r\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_writer_keeps_transcripts_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = transcript_path(&dir.path().join("transcripts"), "ws-1").unwrap();
+        TranscriptWriter::open(&path).unwrap();
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        // A transcript left readable by an older version is tightened
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        TranscriptWriter::open(&path).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn test_read_skips_partial_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.jsonl");
+        std::fs::write(&path, "{\"kind\":\"output\",\"time_ms\":1,\"data\":\"a\"}\n{\"kind\":\"outp").unwrap();
+        assert_eq!(read_entries(&path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_transcript_path_rejects_traversal() {
+        let dir = Path::new("/tmp/transcripts");
+        assert!(transcript_path(dir, "../etc/passwd").is_err());
+        assert!(transcript_path(dir, "").is_err());
+        assert_eq!(
+            transcript_path(dir, "abc-123").unwrap(),
+            PathBuf::from("/tmp/transcripts/abc-123.jsonl")
+        );
+    }
+
+    #[test]
+    fn test_plain_text_strips_split_escape_sequences() {
+        let entries = vec![
+            start(0),
+            output(10, "\x1b[1"),
+            output(20, ";32mdone\x1b[0m\r\n"),
+            TranscriptEntry::Input {
+                time_ms: 30,
+                data: "secret input".into(),
+            },
+            start(40),
+            output(50, "second run"),
+        ];
+        assert_eq!(to_plain_text(&entries), "done\nsecond run");
+    }
+
+    #[test]
+    fn test_asciicast_header_and_events() {
+        let entries = vec![
+            start(10_000),
+            output(10_250, "hi"),
+            TranscriptEntry::Resize {
+                time_ms: 11_000,
+                cols: 80,
+                rows: 24,
+            },
+            TranscriptEntry::Exit {
+                time_ms: 12_000,
+                code: Some(0),
+                signal: None,
+            },
+        ];
+        let cast = to_asciicast(&entries).unwrap();
+        let lines: Vec<serde_json::Value> = cast
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 120);
+        assert_eq!(lines[0]["height"], 40);
+        assert_eq!(lines[0]["timestamp"], 10);
+        assert_eq!(lines[1], serde_json::json!([0.25, "o", "hi"]));
+        assert_eq!(lines[2], serde_json::json!([1.0, "r", "80x24"]));
+        assert_eq!(lines[3], serde_json::json!([2.0, "m", "exited (code 0)"]));
+    }
+
+    #[test]
+    fn test_asciicast_requires_a_run() {
+        assert!(to_asciicast(&[]).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::ansi::{complete_len, strip_ansi};
+use crate::commands;
+use crate::config::{TriggerAction, TriggerRule};
+use crate::pty_manager::{PtyManager, SessionKey};
+use crate::workspace::WorkspaceManager;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Unterminated output kept between chunks so a line split across reads
+/// still matches
+const CARRY_CHARS: usize = 256;
+
+/// Payload of the `trigger-fired` event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TriggerFired {
+    pub workspace_id: String,
+    pub trigger: String,
+    pub action: TriggerAction,
+    /// The matched output
+    pub matched: String,
+}
+
+/// A trigger as shown for one workspace
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkspaceTrigger {
+    #[serde(flatten)]
+    pub rule: TriggerRule,
+    /// False when switched off in config or for this workspace
+    pub active: bool,
+}
+
+struct CompiledTrigger {
+    rule: TriggerRule,
+    pattern: Regex,
+    last_fired: Option<Instant>,
+}
+
+/// Matches a workspace's agent output against its trigger rules. Disabled
+/// rules and rules still cooling down are skipped.
+pub struct TriggerEngine {
+    triggers: Vec<CompiledTrigger>,
+    disabled: HashSet<String>,
+    carry: String,
+    /// Raw escape sequence cut off at the end of the last chunk
+    partial: String,
+}
+
+impl TriggerEngine {
+    /// Rules with invalid patterns are logged and left out
+    pub fn new(rules: &[TriggerRule], disabled: &[String]) -> Self {
+        let triggers = rules
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(pattern) => Some(CompiledTrigger {
+                    rule: rule.clone(),
+                    pattern,
+                    last_fired: None,
+                }),
+                Err(e) => {
+                    eprintln!("invalid trigger pattern in {}: {e}", rule.name);
+                    None
+                }
+            })
+            .collect();
+        Self {
+            triggers,
+            disabled: disabled.iter().cloned().collect(),
+            carry: String::new(),
+            partial: String::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triggers.is_empty()
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if enabled {
+            self.disabled.remove(name);
+        } else {
+            self.disabled.insert(name.to_string());
+        }
+    }
+
+    /// Check a chunk of fresh (not replayed) output; returns the triggers
+    /// that fired as (rule name, action, matched text)
+    pub fn on_output(&mut self, text: &str, now: Instant) -> Vec<(String, TriggerAction, String)> {
+        if self.triggers.is_empty() {
+            return Vec::new();
+        }
+
+        // Strip only whole escapes, so one split across reads can't leak
+        // its parameters into the window
+        let raw = format!("{}{}", self.partial, text);
+        let mut end = complete_len(&raw);
+        if raw.len() - end > CARRY_CHARS {
+            end = raw.len();
+        }
+        self.partial = raw[end..].to_string();
+        let window = format!("{}{}", self.carry, strip_ansi(&raw[..end]));
+        let mut fired = Vec::new();
+        // Output before this offset already matched a trigger
+        let mut consumed = 0;
+        for trigger in &mut self.triggers {
+            if !trigger.rule.enabled || self.disabled.contains(&trigger.rule.name) {
+                continue;
+            }
+            let Some(m) = trigger.pattern.find(&window) else {
+                continue;
+            };
+            // A match held back by the cooldown is still consumed, so it
+            // can't fire later on stale output
+            consumed = consumed.max(m.end());
+            let cooling = trigger.last_fired.is_some_and(|at| {
+                now.duration_since(at) < Duration::from_millis(trigger.rule.cooldown_ms)
+            });
+            if !cooling {
+                trigger.last_fired = Some(now);
+                fired.push((
+                    trigger.rule.name.clone(),
+                    trigger.rule.action.clone(),
+                    m.as_str().to_string(),
+                ));
+            }
+        }
+
+        // Carry the unterminated tail (minus anything that already matched)
+        let tail_start = window.rfind('\n').map(|i| i + 1).unwrap_or(0).max(consumed);
+        let tail = &window[tail_start..];
+        let keep_from = tail
+            .char_indices()
+            .rev()
+            .nth(CARRY_CHARS - 1)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        self.carry = tail[keep_from..].to_string();
+        fired
+    }
+}
+
+/// Announce a fired trigger and run its action off the output thread
+pub fn dispatch(app: &AppHandle, fired: TriggerFired) {
+    let _ = app.emit("trigger-fired", fired.clone());
+    if matches!(fired.action, TriggerAction::Notify { .. }) {
+        return;
+    }
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = run_action(&app, &fired) {
+            eprintln!("trigger {} failed in {}: {e}", fired.trigger, fired.workspace_id);
+        }
+    });
+}
+
+fn run_action(app: &AppHandle, fired: &TriggerFired) -> Result<(), String> {
+    let ws_id = &fired.workspace_id;
+    match &fired.action {
+        TriggerAction::Notify { .. } => Ok(()),
+        TriggerAction::AutoReply { text, submit } => {
+            let pty_mgr = app
+                .try_state::<Mutex<PtyManager>>()
+                .ok_or_else(|| "pty manager unavailable".to_string())?;
+            let mut pty = pty_mgr.lock().map_err(|e| e.to_string())?;
+            if *submit {
+                pty.write(ws_id, text)
+            } else {
+                pty.write_raw(&SessionKey::agent(ws_id), text.as_bytes())
+            }
+        }
+        TriggerAction::RunSnippet { snippet } => {
+            let ws_mgr = app
+                .try_state::<Mutex<WorkspaceManager>>()
+                .ok_or_else(|| "workspace manager unavailable".to_string())?;
+            commands::start_snippet(app, &ws_mgr, ws_id, snippet)
+        }
+        TriggerAction::StopWorkspace => {
+            let (ws_mgr, pty_mgr) = match (
+                app.try_state::<Mutex<WorkspaceManager>>(),
+                app.try_state::<Mutex<PtyManager>>(),
+            ) {
+                (Some(ws), Some(pty)) => (ws, pty),
+                _ => return Err("app state unavailable".into()),
+            };
+            commands::stop_workspace_sessions(&ws_mgr, &pty_mgr, ws_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, pattern: &str, cooldown_ms: u64) -> TriggerRule {
+        TriggerRule {
+            name: name.into(),
+            pattern: pattern.into(),
+            action: TriggerAction::Notify { message: None },
+            cooldown_ms,
+            enabled: true,
+        }
+    }
+
+    fn names(fired: &[(String, TriggerAction, String)]) -> Vec<&str> {
+        fired.iter().map(|(name, _, _)| name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_matches_stripped_output() {
+        let mut engine = TriggerEngine::new(&[rule("tests", r"\d+ tests? failed", 0)], &[]);
+        let fired = engine.on_output("\x1b[31m3 tests failed\x1b[0m\r\n", Instant::now());
+        assert_eq!(names(&fired), vec!["tests"]);
+        assert_eq!(fired[0].2, "3 tests failed");
+    }
+
+    #[test]
+    fn test_cooldown_suppresses_repeat() {
+        let mut engine = TriggerEngine::new(&[rule("rl", "rate limit", 10_000)], &[]);
+        let t0 = Instant::now();
+        assert_eq!(engine.on_output("hit rate limit\n", t0).len(), 1);
+        assert!(engine.on_output("rate limit again\n", t0 + Duration::from_secs(5)).is_empty());
+        assert_eq!(engine.on_output("rate limit\n", t0 + Duration::from_secs(11)).len(), 1);
+    }
+
+    #[test]
+    fn test_cooled_down_match_is_not_carried() {
+        let mut engine = TriggerEngine::new(&[rule("rl", "rate limit", 10_000)], &[]);
+        let t0 = Instant::now();
+        assert_eq!(engine.on_output("rate limit", t0).len(), 1);
+        // Suppressed by the cooldown, without a trailing newline
+        assert!(engine.on_output(" rate limit", t0 + Duration::from_secs(5)).is_empty());
+        // The suppressed match doesn't fire once the cooldown has passed
+        assert!(engine.on_output(" ok", t0 + Duration::from_secs(11)).is_empty());
+    }
+
+    #[test]
+    fn test_match_across_chunks_fires_once() {
+        let mut engine = TriggerEngine::new(&[rule("proceed", r"Do you want to proceed\?", 0)], &[]);
+        let now = Instant::now();
+        assert!(engine.on_output("Do you want to ", now).is_empty());
+        assert_eq!(engine.on_output("proceed? (y/n)", now).len(), 1);
+        // The matched text is not carried into the next check
+        assert!(engine.on_output(" ", now).is_empty());
+    }
+
+    #[test]
+    fn test_escape_split_across_chunks_is_stripped() {
+        let mut engine = TriggerEngine::new(&[rule("tests", r"(?m)^\d+ tests? failed", 0)], &[]);
+        let now = Instant::now();
+        assert!(engine.on_output("ok\n\x1b[3", now).is_empty());
+        let fired = engine.on_output("1m3 tests failed\x1b[0m\n", now);
+        assert_eq!(names(&fired), vec!["tests"]);
+        assert_eq!(fired[0].2, "3 tests failed");
+    }
+
+    #[test]
+    fn test_workspace_switch_and_config_disable() {
+        let disabled_in_config = TriggerRule {
+            enabled: false,
+            ..rule("off", "boom", 0)
+        };
+        let mut engine = TriggerEngine::new(
+            &[rule("a", "boom", 0), rule("b", "boom", 0), disabled_in_config],
+            &["b".to_string()],
+        );
+        let now = Instant::now();
+        assert_eq!(names(&engine.on_output("boom\n", now)), vec!["a"]);
+        engine.set_enabled("b", true);
+        engine.set_enabled("a", false);
+        assert_eq!(names(&engine.on_output("boom\n", now)), vec!["b"]);
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_skipped() {
+        let engine = TriggerEngine::new(&[rule("bad", "(", 0)], &[]);
+        assert!(engine.is_empty());
+    }
+}
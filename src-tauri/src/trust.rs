@@ -227,7 +227,7 @@ fn chrono_iso8601_now() -> String {
     )
 }
 
-fn days_to_ymd(days: u64) -> (u64, u64, u64) {
+pub(crate) fn days_to_ymd(days: u64) -> (u64, u64, u64) {
     // Algorithm adapted from Howard Hinnant's civil_from_days
     let z = days as i64 + 719468;
     let era = if z >= 0 { z } else { z - 146096 } / 146097;
@@ -0,0 +1,590 @@
+use crate::config::{EventFormat, ModelCost};
+use crate::search::LineSplitter;
+use crate::workspace::WorkspaceManager;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Model of usage the agent reported without naming one, such as Claude's
+/// session cost that covers every model in its summary
+pub const UNATTRIBUTED_MODEL: &str = "";
+
+/// Usage arrives with most redraws, so workspace records are written at most
+/// this often while it does
+const USAGE_PERSIST_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Claude Code's `/cost` summary: session cost and per-model token counts
+const CLAUDE_PATTERNS: &[&str] = &[
+    r"Total cost:\s+\$(?P<cost>[\d.,]+)",
+    r"^\s*(?P<model>[\w.\-]+):\s+(?P<input>[\d.,]+[kKmM]?) input, (?P<output>[\d.,]+[kKmM]?) output",
+];
+/// Codex's exit summary
+const CODEX_PATTERNS: &[&str] = &[
+    r"Token usage: total=[\d.,]+[kKmM]? input=(?P<input>[\d.,]+[kKmM]?)(?: \(\+ [\d.,]+[kKmM]? cached\))? output=(?P<output>[\d.,]+[kKmM]?)",
+];
+/// Rows of Gemini CLI's model usage table (model, requests, input, output)
+const GEMINI_PATTERNS: &[&str] = &[
+    r"^[\s│]*(?P<model>gemini-[\w.\-]+)\s+\d+\s+(?P<input>[\d.,]+[kKmM]?)\s+(?P<output>[\d.,]+[kKmM]?)",
+];
+
+/// Tokens and cost accumulated for one bucket
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct UsageCounts {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// Only as reported by the agent
+    #[serde(default)]
+    pub cost_usd: f64,
+}
+
+impl UsageCounts {
+    pub fn add(&mut self, other: &UsageCounts) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.input_tokens == 0 && self.output_tokens == 0 && self.cost_usd == 0.0
+    }
+}
+
+/// Usage of one model on one (UTC) day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsageEntry {
+    /// `YYYY-MM-DD`
+    pub day: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub counts: UsageCounts,
+}
+
+/// Everything a workspace has used, stored on its record
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WorkspaceUsage {
+    #[serde(default)]
+    pub entries: Vec<UsageEntry>,
+    /// Wall-clock time its agents ran, across sessions
+    #[serde(default)]
+    pub runtime_secs: f64,
+}
+
+impl WorkspaceUsage {
+    pub fn add(&mut self, day: &str, model: &str, counts: &UsageCounts) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.day == day && e.model == model)
+        {
+            Some(entry) => entry.counts.add(counts),
+            None => self.entries.push(UsageEntry {
+                day: day.to_string(),
+                model: model.to_string(),
+                counts: *counts,
+            }),
+        }
+    }
+
+    pub fn total(&self) -> UsageCounts {
+        let mut total = UsageCounts::default();
+        for entry in &self.entries {
+            total.add(&entry.counts);
+        }
+        total
+    }
+}
+
+/// Totals over a set of workspaces, broken down three ways
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct UsageReport {
+    pub total: UsageCounts,
+    pub by_workspace: BTreeMap<String, UsageCounts>,
+    pub by_model: BTreeMap<String, UsageCounts>,
+    pub by_day: BTreeMap<String, UsageCounts>,
+    /// Usage not tied to any one model (left out of `by_model`)
+    pub unattributed: UsageCounts,
+}
+
+impl UsageReport {
+    /// Sum the entries of `(workspace id, usage)` pairs whose day falls in
+    /// the inclusive `from_day..=to_day` range
+    pub fn collect<'a>(
+        usages: impl IntoIterator<Item = (&'a str, &'a WorkspaceUsage)>,
+        from_day: Option<&str>,
+        to_day: Option<&str>,
+    ) -> Self {
+        let mut report = UsageReport::default();
+        for (workspace_id, usage) in usages {
+            for entry in &usage.entries {
+                let day = entry.day.as_str();
+                if from_day.is_some_and(|from| day < from) || to_day.is_some_and(|to| day > to) {
+                    continue;
+                }
+                report.total.add(&entry.counts);
+                report
+                    .by_workspace
+                    .entry(workspace_id.to_string())
+                    .or_default()
+                    .add(&entry.counts);
+                if entry.model == UNATTRIBUTED_MODEL {
+                    report.unattributed.add(&entry.counts);
+                } else {
+                    report
+                        .by_model
+                        .entry(entry.model.clone())
+                        .or_default()
+                        .add(&entry.counts);
+                }
+                report
+                    .by_day
+                    .entry(entry.day.clone())
+                    .or_default()
+                    .add(&entry.counts);
+            }
+        }
+        report
+    }
+}
+
+/// Regex pulling usage out of an agent's terminal output. Named groups
+/// `input`, `output` and `cost` carry the values (all optional; token counts
+/// may use k/M suffixes); `model` names the model when the output does.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UsagePattern {
+    pub pattern: String,
+    /// The values are session running totals rather than per-turn amounts
+    #[serde(default = "default_cumulative")]
+    pub cumulative: bool,
+}
+
+fn default_cumulative() -> bool {
+    true
+}
+
+/// Usage figures read from one line of output
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageReading {
+    pub model: Option<String>,
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub cost_usd: Option<f64>,
+    pub cumulative: bool,
+}
+
+/// Built-in usage patterns and structured format for the known agents
+pub fn builtin_extractor(agent: &str) -> (Vec<UsagePattern>, Option<EventFormat>) {
+    let (patterns, format) = match agent {
+        "claude" => (CLAUDE_PATTERNS, Some(EventFormat::ClaudeJson)),
+        "codex" => (CODEX_PATTERNS, Some(EventFormat::CodexJson)),
+        "gemini" => (GEMINI_PATTERNS, Some(EventFormat::GeminiJson)),
+        _ => (&[][..], None),
+    };
+    let patterns = patterns
+        .iter()
+        .map(|p| UsagePattern {
+            pattern: p.to_string(),
+            cumulative: true,
+        })
+        .collect();
+    (patterns, format)
+}
+
+/// Reads usage from single lines of stripped output
+pub struct UsageExtractor {
+    patterns: Vec<(Regex, bool)>,
+    format: Option<EventFormat>,
+}
+
+impl UsageExtractor {
+    /// Patterns that don't compile are logged and left out
+    pub fn new(patterns: &[UsagePattern], format: Option<EventFormat>) -> Self {
+        let patterns = patterns
+            .iter()
+            .filter_map(|p| match Regex::new(&p.pattern) {
+                Ok(re) => Some((re, p.cumulative)),
+                Err(e) => {
+                    eprintln!("ignoring invalid usage pattern {:?}: {e}", p.pattern);
+                    None
+                }
+            })
+            .collect();
+        Self { patterns, format }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && self.format.is_none()
+    }
+
+    pub fn extract(&self, line: &str) -> Vec<UsageReading> {
+        let trimmed = line.trim();
+        if let (Some(format), true) = (self.format, trimmed.starts_with('{')) {
+            if let Ok(event) = serde_json::from_str::<Value>(trimmed) {
+                return parse_event(format, &event);
+            }
+        }
+
+        self.patterns
+            .iter()
+            .filter_map(|(re, cumulative)| {
+                let caps = re.captures(line)?;
+                let reading = UsageReading {
+                    model: caps.name("model").map(|m| m.as_str().to_string()),
+                    input_tokens: caps.name("input").and_then(|m| parse_tokens(m.as_str())),
+                    output_tokens: caps.name("output").and_then(|m| parse_tokens(m.as_str())),
+                    cost_usd: caps
+                        .name("cost")
+                        .and_then(|m| m.as_str().replace(',', "").parse().ok()),
+                    cumulative: *cumulative,
+                };
+                let empty = reading.input_tokens.is_none()
+                    && reading.output_tokens.is_none()
+                    && reading.cost_usd.is_none();
+                (!empty).then_some(reading)
+            })
+            .collect()
+    }
+}
+
+/// `12,345`, `4.5k` or `1.2M` as a token count
+fn parse_tokens(s: &str) -> Option<u64> {
+    let s = s.replace(',', "");
+    let (number, scale) = match s.chars().last()? {
+        'k' | 'K' => (&s[..s.len() - 1], 1_000.0),
+        'm' | 'M' => (&s[..s.len() - 1], 1_000_000.0),
+        _ => (s.as_str(), 1.0),
+    };
+    let value: f64 = number.parse().ok()?;
+    Some((value * scale).round() as u64)
+}
+
+fn parse_event(format: EventFormat, event: &Value) -> Vec<UsageReading> {
+    let u64_at = |v: &Value, key: &str| v.get(key).and_then(Value::as_u64);
+    match format {
+        EventFormat::ClaudeJson => {
+            if event.get("type").and_then(Value::as_str) != Some("result") {
+                return Vec::new();
+            }
+            // Cached prompt tokens are still prompt tokens
+            let input = event.get("usage").map(|usage| {
+                ["input_tokens", "cache_read_input_tokens", "cache_creation_input_tokens"]
+                    .iter()
+                    .filter_map(|key| u64_at(usage, key))
+                    .sum()
+            });
+            vec![UsageReading {
+                model: None,
+                input_tokens: input,
+                output_tokens: event.get("usage").and_then(|u| u64_at(u, "output_tokens")),
+                cost_usd: event.get("total_cost_usd").and_then(Value::as_f64),
+                cumulative: true,
+            }]
+        }
+        EventFormat::CodexJson => {
+            if event.get("type").and_then(Value::as_str) != Some("turn.completed") {
+                return Vec::new();
+            }
+            match event.get("usage") {
+                Some(usage) => vec![UsageReading {
+                    model: None,
+                    input_tokens: u64_at(usage, "input_tokens"),
+                    output_tokens: u64_at(usage, "output_tokens"),
+                    cost_usd: None,
+                    cumulative: false,
+                }],
+                None => Vec::new(),
+            }
+        }
+        EventFormat::GeminiJson => {
+            let stats = match event.get("stats") {
+                Some(stats) => stats,
+                None => return Vec::new(),
+            };
+            if let Some(models) = stats.get("models").and_then(Value::as_object) {
+                return models
+                    .iter()
+                    .map(|(model, m)| {
+                        let tokens = m.get("tokens");
+                        UsageReading {
+                            model: Some(model.clone()),
+                            input_tokens: tokens.and_then(|t| u64_at(t, "prompt")),
+                            output_tokens: tokens.and_then(|t| u64_at(t, "candidates")),
+                            cost_usd: None,
+                            cumulative: true,
+                        }
+                    })
+                    .collect();
+            }
+            vec![UsageReading {
+                model: None,
+                input_tokens: u64_at(stats, "input_tokens"),
+                output_tokens: u64_at(stats, "output_tokens"),
+                cost_usd: None,
+                cumulative: true,
+            }]
+        }
+    }
+}
+
+/// Turns an agent session's output into usage increments per model.
+/// Running totals are diffed against the last value seen, so a summary that
+/// is printed or redrawn repeatedly is only counted once.
+pub struct UsageTracker {
+    extractor: UsageExtractor,
+    splitter: LineSplitter,
+    /// Model charged when the output doesn't name one
+    default_model: String,
+    /// Last running totals per model
+    last: HashMap<String, UsageReading>,
+    /// Token prices of models whose cost the agent doesn't report
+    costs: HashMap<String, ModelCost>,
+}
+
+impl UsageTracker {
+    pub fn new(extractor: UsageExtractor, default_model: String) -> Self {
+        Self {
+            extractor,
+            splitter: LineSplitter::default(),
+            default_model,
+            last: HashMap::new(),
+            costs: HashMap::new(),
+        }
+    }
+
+    /// Charge tokens by these prices when a reading carries no cost
+    pub fn with_costs(mut self, costs: HashMap<String, ModelCost>) -> Self {
+        self.costs = costs;
+        self
+    }
+
+    /// Feed a chunk of raw output; returns the new usage per model
+    pub fn on_output(&mut self, text: &str, now_ms: u64) -> Vec<(String, UsageCounts)> {
+        let mut deltas: Vec<(String, UsageCounts)> = Vec::new();
+        for line in self.splitter.push(now_ms, text) {
+            for reading in self.extractor.extract(&line.text) {
+                // A cost on its own (Claude's `/cost` total) spans all the
+                // models listed with it, so it isn't charged to any one
+                let cost_only = reading.input_tokens.is_none() && reading.output_tokens.is_none();
+                let model = match &reading.model {
+                    Some(model) => model.clone(),
+                    None if cost_only => UNATTRIBUTED_MODEL.to_string(),
+                    None => self.default_model.clone(),
+                };
+                let priced = reading.cost_usd.is_some();
+                let mut delta = self.delta(&model, reading);
+                if let (false, Some(cost)) = (priced, self.costs.get(&model)) {
+                    delta.cost_usd = (delta.input_tokens as f64 * cost.input_per_mtok
+                        + delta.output_tokens as f64 * cost.output_per_mtok)
+                        / 1_000_000.0;
+                }
+                if delta.is_zero() {
+                    continue;
+                }
+                match deltas.iter_mut().find(|(m, _)| *m == model) {
+                    Some((_, counts)) => counts.add(&delta),
+                    None => deltas.push((model, delta)),
+                }
+            }
+        }
+        deltas
+    }
+
+    fn delta(&mut self, model: &str, reading: UsageReading) -> UsageCounts {
+        if !reading.cumulative {
+            return UsageCounts {
+                input_tokens: reading.input_tokens.unwrap_or(0),
+                output_tokens: reading.output_tokens.unwrap_or(0),
+                cost_usd: reading.cost_usd.unwrap_or(0.0),
+            };
+        }
+
+        // A total lower than the last one means the agent started counting
+        // afresh (e.g. a new conversation)
+        let last = self.last.entry(model.to_string()).or_default();
+        let mut delta = UsageCounts::default();
+        if let Some(input) = reading.input_tokens {
+            let prev = last.input_tokens.unwrap_or(0);
+            delta.input_tokens = if input >= prev { input - prev } else { input };
+            last.input_tokens = Some(input);
+        }
+        if let Some(output) = reading.output_tokens {
+            let prev = last.output_tokens.unwrap_or(0);
+            delta.output_tokens = if output >= prev { output - prev } else { output };
+            last.output_tokens = Some(output);
+        }
+        if let Some(cost) = reading.cost_usd {
+            let prev = last.cost_usd.unwrap_or(0.0);
+            delta.cost_usd = if cost >= prev { cost - prev } else { cost };
+            last.cost_usd = Some(cost);
+        }
+        delta
+    }
+}
+
+/// UTC calendar day of a Unix epoch millis timestamp, as `YYYY-MM-DD`
+pub fn day_of(time_ms: u64) -> String {
+    let (year, month, day) = crate::trust::days_to_ymd(time_ms / 86_400_000);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Add a session's new usage to its workspace record off the output thread,
+/// then emit `usage-updated-{id}` with the workspace's totals. Writes to
+/// disk are batched per `USAGE_PERSIST_INTERVAL`.
+pub fn dispatch(app: &AppHandle, workspace_id: &str, time_ms: u64, deltas: Vec<(String, UsageCounts)>) {
+    if deltas.is_empty() {
+        return;
+    }
+    let app = app.clone();
+    let workspace_id = workspace_id.to_string();
+    std::thread::spawn(move || {
+        let ws_mgr = match app.try_state::<Mutex<WorkspaceManager>>() {
+            Some(ws_mgr) => ws_mgr,
+            None => return,
+        };
+        let recorded = ws_mgr.lock().map_err(|e| e.to_string()).and_then(|mut ws| {
+            let usage = ws.record_usage(&workspace_id, &day_of(time_ms), &deltas)?;
+            Ok((usage, ws.persist_batched(USAGE_PERSIST_INTERVAL)))
+        });
+        match recorded {
+            Ok((usage, flush_in)) => {
+                let report = UsageReport::collect([(workspace_id.as_str(), &usage)], None, None);
+                let _ = app.emit(&format!("usage-updated-{workspace_id}"), report);
+                if let Some(wait) = flush_in {
+                    std::thread::sleep(wait);
+                    if let Ok(mut ws) = ws_mgr.lock() {
+                        ws.flush();
+                    }
+                }
+            }
+            Err(e) => eprintln!("usage not recorded for {workspace_id}: {e}"),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(agent: &str) -> UsageTracker {
+        let (patterns, format) = builtin_extractor(agent);
+        UsageTracker::new(UsageExtractor::new(&patterns, format), agent.to_string())
+    }
+
+    #[test]
+    fn test_parse_tokens_suffixes() {
+        assert_eq!(parse_tokens("12,345"), Some(12_345));
+        assert_eq!(parse_tokens("4.5k"), Some(4_500));
+        assert_eq!(parse_tokens("1.2M"), Some(1_200_000));
+        assert_eq!(parse_tokens("x"), None);
+    }
+
+    #[test]
+    fn test_claude_cost_summary_counted_once() {
+        let mut t = tracker("claude");
+        let summary = "Total cost:            $0.0500\r\n\
+                       Usage by model:\r\n\
+                       \x1b[2m    claude-sonnet:  1.2k input, 300 output, 0 cache read\x1b[0m\r\n";
+        let deltas = t.on_output(summary, 0);
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].0, UNATTRIBUTED_MODEL);
+        assert!((deltas[0].1.cost_usd - 0.05).abs() < 1e-9);
+        assert_eq!(deltas[1].0, "claude-sonnet");
+        assert_eq!((deltas[1].1.input_tokens, deltas[1].1.output_tokens), (1_200, 300));
+        assert_eq!(deltas[1].1.cost_usd, 0.0);
+
+        // The session cost is reported apart from the per-model counts
+        let mut usage = WorkspaceUsage::default();
+        for (model, counts) in &deltas {
+            usage.add("2025-01-01", model, counts);
+        }
+        let report = UsageReport::collect([("ws", &usage)], None, None);
+        assert_eq!(report.by_model.keys().collect::<Vec<_>>(), vec!["claude-sonnet"]);
+        assert!((report.unattributed.cost_usd - 0.05).abs() < 1e-9);
+        assert!((report.total.cost_usd - 0.05).abs() < 1e-9);
+
+        // A redraw of the same summary adds nothing; a later one adds the difference
+        assert!(t.on_output(summary, 1).is_empty());
+        let later = t.on_output("Total cost: $0.0800\n", 2);
+        assert!((later[0].1.cost_usd - 0.03).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_codex_exit_line_and_json_turns() {
+        let mut t = tracker("codex");
+        let deltas =
+            t.on_output("Token usage: total=12,345 input=10,000 (+ 2,000 cached) output=2,345\n", 0);
+        assert_eq!(deltas[0].1.input_tokens, 10_000);
+        assert_eq!(deltas[0].1.output_tokens, 2_345);
+
+        let turn = r#"{"type":"turn.completed","usage":{"input_tokens":100,"cached_input_tokens":50,"output_tokens":20}}"#;
+        let first = t.on_output(&format!("{turn}\n{turn}\n"), 1);
+        assert_eq!(first[0].1.input_tokens, 200);
+        assert_eq!(first[0].1.output_tokens, 40);
+    }
+
+    #[test]
+    fn test_model_prices_charge_unpriced_tokens() {
+        let cost = ModelCost {
+            input_per_mtok: 2.0,
+            output_per_mtok: 8.0,
+        };
+        let mut t = tracker("codex").with_costs(HashMap::from([("codex".to_string(), cost)]));
+        let deltas = t.on_output("Token usage: total=1.5M input=1M output=500k\n", 0);
+        assert!((deltas[0].1.cost_usd - 6.0).abs() < 1e-9);
+
+        // A reported cost is kept as it is
+        let mut claude = tracker("claude").with_costs(HashMap::from([("claude".to_string(), cost)]));
+        let result = r#"{"type":"result","total_cost_usd":0.25,"usage":{"input_tokens":1000000,"output_tokens":0}}"#;
+        let deltas = claude.on_output(&format!("{result}\n"), 0);
+        assert!((deltas[0].1.cost_usd - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_claude_and_gemini_json_results() {
+        let mut claude = tracker("claude");
+        let result = r#"{"type":"result","total_cost_usd":0.25,"usage":{"input_tokens":10,"cache_read_input_tokens":90,"output_tokens":5}}"#;
+        let deltas = claude.on_output(&format!("{result}\n"), 0);
+        assert_eq!(deltas[0].1.input_tokens, 100);
+        assert!((deltas[0].1.cost_usd - 0.25).abs() < 1e-9);
+
+        let mut gemini = tracker("gemini");
+        let stats = r#"{"response":"ok","stats":{"models":{"gemini-2.5-pro":{"tokens":{"prompt":700,"candidates":70}}}}}"#;
+        let deltas = gemini.on_output(&format!("{stats}\n"), 0);
+        assert_eq!(deltas, vec![(
+            "gemini-2.5-pro".to_string(),
+            UsageCounts { input_tokens: 700, output_tokens: 70, cost_usd: 0.0 },
+        )]);
+    }
+
+    #[test]
+    fn test_report_groups_and_filters_days() {
+        let mut a = WorkspaceUsage::default();
+        let counts = UsageCounts { input_tokens: 10, output_tokens: 1, cost_usd: 0.5 };
+        a.add("2026-01-01", "opus", &counts);
+        a.add("2026-01-01", "opus", &counts);
+        a.add("2026-01-02", "sonnet", &counts);
+        let mut b = WorkspaceUsage::default();
+        b.add("2026-01-02", "opus", &counts);
+        assert_eq!(a.entries.len(), 2);
+
+        let report = UsageReport::collect([("a", &a), ("b", &b)], None, None);
+        assert_eq!(report.total.input_tokens, 40);
+        assert_eq!(report.by_model["opus"].input_tokens, 30);
+        assert_eq!(report.by_workspace["a"].output_tokens, 3);
+
+        let day2 = UsageReport::collect([("a", &a), ("b", &b)], Some("2026-01-02"), None);
+        assert_eq!(day2.total.input_tokens, 20);
+        assert_eq!(day2.by_day.keys().collect::<Vec<_>>(), vec!["2026-01-02"]);
+    }
+
+    #[test]
+    fn test_day_of() {
+        assert_eq!(day_of(0), "1970-01-01");
+        assert_eq!(day_of(1_767_225_600_000 + 3_600_000), "2026-01-01");
+    }
+}
@@ -1,15 +1,35 @@
+use crate::activity::AgentActivity;
+use crate::config::PermissionMode;
+use crate::headless::HeadlessResult;
+use crate::usage::{UsageCounts, WorkspaceUsage};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process::Command;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum WorkspaceState {
     Creating,
+    /// Waiting in the spawn queue for a free agent slot
+    Queued,
     Running,
     Stopping,
     Stopped,
     Cleaning,
+    /// Interrupted or stopped for reaching a budget limit
+    BudgetExceeded,
+}
+
+/// An agent that worked in a workspace before it was switched out
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PastAgent {
+    pub agent: String,
+    /// Its conversation, resumed if the workspace switches back to it
+    pub session_id: Option<String>,
+    /// When it was switched out
+    pub until_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,19 +41,123 @@ pub struct WorkspaceInfo {
     pub worktree_path: String,
     pub agent: String,
     pub state: WorkspaceState,
+    /// Live agent activity, filled in from the PTY manager when listed
+    #[serde(default)]
+    pub activity: Option<AgentActivity>,
+    /// 1-based position in the spawn queue while `Queued`
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+    /// Output triggers switched off for this workspace
+    #[serde(default)]
+    pub disabled_triggers: Vec<String>,
+    /// Tokens and cost reported by the workspace's agents
+    #[serde(default)]
+    pub usage: WorkspaceUsage,
+    /// Task the workspace was created with
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Outcome of the latest headless run
+    #[serde(default)]
+    pub last_result: Option<HeadlessResult>,
+    /// Permission mode the agent was last started with
+    #[serde(default)]
+    pub permission_mode: Option<PermissionMode>,
+    /// Conversation of the agent, resumed when it is started again
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Agents switched out of this workspace, oldest first
+    #[serde(default)]
+    pub agent_history: Vec<PastAgent>,
 }
 
 pub struct WorkspaceManager {
     workspaces: HashMap<String, WorkspaceInfo>,
+    /// Where workspace records are persisted; `None` keeps them in memory only
+    store_path: Option<PathBuf>,
+    persisted_at: Option<Instant>,
+    /// Changes held back by `persist_batched` that aren't on disk yet
+    unsaved: bool,
 }
 
 impl WorkspaceManager {
     pub fn new() -> Self {
         Self {
             workspaces: HashMap::new(),
+            store_path: None,
+            persisted_at: None,
+            unsaved: false,
+        }
+    }
+
+    /// Load persisted workspaces from ~/.ocestrater/workspaces.json
+    pub fn load_or_default() -> Self {
+        Self::load_from(Self::store_path())
+    }
+
+    pub fn load_from(path: PathBuf) -> Self {
+        let workspaces = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Vec<WorkspaceInfo>>(&content).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|ws| (ws.id.clone(), ws))
+            .collect();
+        Self {
+            workspaces,
+            store_path: Some(path),
+            persisted_at: None,
+            unsaved: false,
         }
     }
 
+    fn store_path() -> PathBuf {
+        dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".ocestrater")
+            .join("workspaces.json")
+    }
+
+    /// Write workspace records to disk; failures are logged, not fatal
+    fn persist(&mut self) {
+        self.persisted_at = Some(Instant::now());
+        self.unsaved = false;
+        let path = match &self.store_path {
+            Some(p) => p,
+            None => return,
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let mut records: Vec<&WorkspaceInfo> = self.workspaces.values().collect();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        let result = serde_json::to_string_pretty(&records)
+            .map_err(|e| format!("serialize error: {e}"))
+            .and_then(|json| std::fs::write(path, json).map_err(|e| format!("write error: {e}")));
+        if let Err(e) = result {
+            eprintln!("failed to persist workspaces: {e}");
+        }
+    }
+
+    /// After a restart, mark workspaces whose agent is no longer running as
+    /// stopped. `live_agents` holds the workspace ids with a live agent.
+    pub fn reconcile_sessions(&mut self, live_agents: &[String]) {
+        for ws in self.workspaces.values_mut() {
+            let active = matches!(
+                ws.state,
+                WorkspaceState::Creating
+                    | WorkspaceState::Queued
+                    | WorkspaceState::Running
+                    | WorkspaceState::Stopping
+            );
+            if active && !live_agents.contains(&ws.id) {
+                ws.state = WorkspaceState::Stopped;
+            }
+            ws.activity = None;
+            ws.queue_position = None;
+        }
+        self.persist();
+    }
+
     /// Create a new workspace with an isolated git worktree
     pub fn create(
         &mut self,
@@ -42,6 +166,7 @@ impl WorkspaceManager {
         branch: &str,
         agent: &str,
         worktree_dir: &str,
+        prompt: Option<&str>,
     ) -> Result<WorkspaceInfo, String> {
         // Canonicalize repo_path to resolve symlinks and prevent path traversal
         let canonical_repo = std::fs::canonicalize(repo_path)
@@ -88,6 +213,15 @@ impl WorkspaceManager {
             worktree_path: canonical_wt_str.clone(),
             agent: agent.to_string(),
             state: WorkspaceState::Creating,
+            activity: None,
+            queue_position: None,
+            disabled_triggers: vec![],
+            usage: WorkspaceUsage::default(),
+            prompt: prompt.map(str::to_string),
+            last_result: None,
+            permission_mode: None,
+            session_id: None,
+            agent_history: vec![],
         };
 
         self.workspaces.insert(id.clone(), ws.clone());
@@ -111,10 +245,190 @@ impl WorkspaceManager {
         if let Some(ws) = self.workspaces.get_mut(&id) {
             ws.state = WorkspaceState::Running;
         }
+        self.persist();
 
         Ok(self.workspaces[&id].clone())
     }
 
+    /// Set a workspace's lifecycle state
+    pub fn set_state(&mut self, workspace_id: &str, state: WorkspaceState) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        ws.state = state;
+        self.persist();
+        Ok(())
+    }
+
+    pub fn set_permission_mode(&mut self, workspace_id: &str, mode: PermissionMode) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        ws.permission_mode = Some(mode);
+        self.persist();
+        Ok(())
+    }
+
+    /// Store the conversation `agent` runs in. Ignored once the workspace
+    /// has moved on to another agent, whose conversation it isn't.
+    pub fn set_session_id(&mut self, workspace_id: &str, agent: &str, session_id: &str) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        if ws.agent == agent && ws.session_id.as_deref() != Some(session_id) {
+            ws.session_id = Some(session_id.to_string());
+            self.persist();
+        }
+        Ok(())
+    }
+
+    /// Forget a workspace's conversation, e.g. one its agent can't resume
+    pub fn clear_session_id(&mut self, workspace_id: &str) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        if ws.session_id.take().is_some() {
+            self.persist();
+        }
+        Ok(())
+    }
+
+    /// Hand a workspace to another agent. The current one goes into the
+    /// history with its conversation (`session_id` when known, else the
+    /// stored one); the conversation the new agent had here, if it worked
+    /// here before, is returned to be resumed.
+    pub fn switch_agent(
+        &mut self,
+        workspace_id: &str,
+        agent: &str,
+        session_id: Option<String>,
+        now_ms: u64,
+    ) -> Result<Option<String>, String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        let previous = PastAgent {
+            agent: std::mem::replace(&mut ws.agent, agent.to_string()),
+            session_id: session_id.or_else(|| ws.session_id.take()),
+            until_ms: now_ms,
+        };
+        ws.agent_history.push(previous);
+        ws.session_id = ws
+            .agent_history
+            .iter()
+            .rev()
+            .find(|past| past.agent == agent)
+            .and_then(|past| past.session_id.clone());
+        let resumed = ws.session_id.clone();
+        self.persist();
+        Ok(resumed)
+    }
+
+    /// Undo the latest `switch_agent` whose new agent couldn't be started:
+    /// the previous agent and its conversation are restored
+    pub fn revert_switch_agent(&mut self, workspace_id: &str) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        let previous = ws
+            .agent_history
+            .pop()
+            .ok_or_else(|| format!("no agent switch to revert: {workspace_id}"))?;
+        ws.agent = previous.agent;
+        ws.session_id = previous.session_id;
+        self.persist();
+        Ok(())
+    }
+
+    /// Switch an output trigger on or off for one workspace
+    pub fn set_trigger_enabled(&mut self, workspace_id: &str, trigger: &str, enabled: bool) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        ws.disabled_triggers.retain(|t| t != trigger);
+        if !enabled {
+            ws.disabled_triggers.push(trigger.to_string());
+        }
+        self.persist();
+        Ok(())
+    }
+
+    /// Write records unless they were written within `interval`. When the
+    /// write is held back and no flush is due yet, returns how long to wait
+    /// before calling `flush`.
+    pub fn persist_batched(&mut self, interval: Duration) -> Option<Duration> {
+        let since = self.persisted_at.map(|at| at.elapsed());
+        match since {
+            Some(since) if since < interval => {
+                let first = !self.unsaved;
+                self.unsaved = true;
+                first.then(|| interval - since)
+            }
+            _ => {
+                self.persist();
+                None
+            }
+        }
+    }
+
+    /// Write changes held back by `persist_batched`, if any
+    pub fn flush(&mut self) {
+        if self.unsaved {
+            self.persist();
+        }
+    }
+
+    /// Add per-model usage for `day` and return the workspace's usage. It is
+    /// not written to disk; see `persist_batched`.
+    pub fn record_usage(
+        &mut self,
+        workspace_id: &str,
+        day: &str,
+        deltas: &[(String, UsageCounts)],
+    ) -> Result<WorkspaceUsage, String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        for (model, counts) in deltas {
+            ws.usage.add(day, model, counts);
+        }
+        Ok(ws.usage.clone())
+    }
+
+    /// Add agent runtime to a workspace's usage
+    pub fn record_runtime(&mut self, workspace_id: &str, secs: f64) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        ws.usage.runtime_secs += secs;
+        self.persist();
+        Ok(())
+    }
+
+    /// Store a headless run's result; a workspace still marked running is
+    /// stopped, one stopped for its budget keeps that state
+    pub fn finish_headless_run(&mut self, workspace_id: &str, result: HeadlessResult) -> Result<(), String> {
+        let ws = self
+            .workspaces
+            .get_mut(workspace_id)
+            .ok_or_else(|| format!("workspace not found: {workspace_id}"))?;
+        ws.last_result = Some(result);
+        if ws.state == WorkspaceState::Running {
+            ws.state = WorkspaceState::Stopped;
+        }
+        self.persist();
+        Ok(())
+    }
+
     /// Stop a workspace: transition to Stopping → Stopped
     pub fn stop(&mut self, workspace_id: &str) -> Result<(), String> {
         let ws = self
@@ -125,6 +439,7 @@ impl WorkspaceManager {
         ws.state = WorkspaceState::Stopping;
         // PTY kill is handled by PtyManager; we just update state
         ws.state = WorkspaceState::Stopped;
+        self.persist();
         Ok(())
     }
 
@@ -159,6 +474,7 @@ impl WorkspaceManager {
         }
 
         self.workspaces.remove(workspace_id);
+        self.persist();
         Ok(())
     }
 
@@ -212,6 +528,15 @@ mod tests {
             worktree_path: "/tmp/test-repo/.worktrees/feature-branch".to_string(),
             agent: "claude".to_string(),
             state,
+            activity: None,
+            queue_position: None,
+            disabled_triggers: vec![],
+            usage: WorkspaceUsage::default(),
+            prompt: None,
+            last_result: None,
+            permission_mode: None,
+            session_id: None,
+            agent_history: vec![],
         };
         mgr.workspaces.insert(id.clone(), ws);
         (mgr, id)
@@ -328,6 +653,15 @@ mod tests {
                 worktree_path: "/repo/a/.wt/branch-1".to_string(),
                 agent: "claude".to_string(),
                 state: WorkspaceState::Running,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
         mgr.workspaces.insert(
@@ -340,6 +674,15 @@ mod tests {
                 worktree_path: "/repo/b/.wt/branch-2".to_string(),
                 agent: "codex".to_string(),
                 state: WorkspaceState::Stopped,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
 
@@ -361,6 +704,15 @@ mod tests {
                 worktree_path: "/repo/a/.wt/branch-1".to_string(),
                 agent: "claude".to_string(),
                 state: WorkspaceState::Running,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
         mgr.workspaces.insert(
@@ -373,6 +725,15 @@ mod tests {
                 worktree_path: "/repo/b/.wt/branch-2".to_string(),
                 agent: "codex".to_string(),
                 state: WorkspaceState::Stopped,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
         mgr.workspaces.insert(
@@ -385,6 +746,15 @@ mod tests {
                 worktree_path: "/repo/a/.wt/branch-3".to_string(),
                 agent: "gemini".to_string(),
                 state: WorkspaceState::Running,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
 
@@ -430,6 +800,15 @@ mod tests {
                 worktree_path: "/repo/a/.wt/feature-a".to_string(),
                 agent: "claude".to_string(),
                 state: WorkspaceState::Running,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
         mgr.workspaces.insert(
@@ -442,6 +821,15 @@ mod tests {
                 worktree_path: "/repo/b/.wt/feature-b".to_string(),
                 agent: "codex".to_string(),
                 state: WorkspaceState::Stopped,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
         mgr.workspaces.insert(
@@ -454,6 +842,15 @@ mod tests {
                 worktree_path: "/repo/a/.wt/feature-c".to_string(),
                 agent: "gemini".to_string(),
                 state: WorkspaceState::Creating,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
 
@@ -483,6 +880,15 @@ mod tests {
                 worktree_path: "/repo/actual/.wt/main".to_string(),
                 agent: "claude".to_string(),
                 state: WorkspaceState::Running,
+                activity: None,
+                queue_position: None,
+                disabled_triggers: vec![],
+                usage: WorkspaceUsage::default(),
+                prompt: None,
+                last_result: None,
+                permission_mode: None,
+                session_id: None,
+                agent_history: vec![],
             },
         );
 
@@ -522,6 +928,15 @@ mod tests {
             worktree_path: "/home/user/project/.worktrees/feature-awesome-abc12345".to_string(),
             agent: "claude".to_string(),
             state: WorkspaceState::Running,
+            activity: None,
+            queue_position: None,
+            disabled_triggers: vec![],
+            usage: WorkspaceUsage::default(),
+            prompt: None,
+            last_result: None,
+            permission_mode: None,
+            session_id: None,
+            agent_history: vec![],
         };
 
         let json = serde_json::to_string(&ws).unwrap();
@@ -534,14 +949,31 @@ mod tests {
         assert_eq!(parsed.state, WorkspaceState::Running);
     }
 
+    #[test]
+    fn test_set_state_queued_then_running() {
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.set_state(&id, WorkspaceState::Queued).unwrap();
+        assert_eq!(mgr.get(&id).unwrap().state, WorkspaceState::Queued);
+        mgr.set_state(&id, WorkspaceState::Running).unwrap();
+        assert_eq!(mgr.get(&id).unwrap().state, WorkspaceState::Running);
+    }
+
+    #[test]
+    fn test_set_state_nonexistent_returns_error() {
+        let mut mgr = WorkspaceManager::new();
+        assert!(mgr.set_state("missing", WorkspaceState::Queued).is_err());
+    }
+
     #[test]
     fn test_workspace_state_serde_roundtrip_all_variants() {
         let variants = vec![
             WorkspaceState::Creating,
+            WorkspaceState::Queued,
             WorkspaceState::Running,
             WorkspaceState::Stopping,
             WorkspaceState::Stopped,
             WorkspaceState::Cleaning,
+            WorkspaceState::BudgetExceeded,
         ];
         for state in variants {
             let json = serde_json::to_string(&state).unwrap();
@@ -571,4 +1003,179 @@ mod tests {
         assert_eq!(ws.branch, "feature-branch");
         assert_eq!(ws.agent, "claude");
     }
+
+    #[test]
+    fn test_set_trigger_enabled_toggles_once() {
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.set_trigger_enabled(&id, "rate-limit", false).unwrap();
+        mgr.set_trigger_enabled(&id, "rate-limit", false).unwrap();
+        assert_eq!(mgr.get(&id).unwrap().disabled_triggers, vec!["rate-limit"]);
+        mgr.set_trigger_enabled(&id, "rate-limit", true).unwrap();
+        assert!(mgr.get(&id).unwrap().disabled_triggers.is_empty());
+        assert!(mgr.set_trigger_enabled("nope", "x", true).is_err());
+    }
+
+    #[test]
+    fn test_record_usage_accumulates_and_batches_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json");
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.store_path = Some(path.clone());
+        let interval = Duration::from_secs(60);
+        let counts = UsageCounts { input_tokens: 100, output_tokens: 10, cost_usd: 0.1 };
+        let first = mgr.record_usage(&id, "2026-03-01", &[("opus".into(), counts)]).unwrap();
+        assert_eq!(mgr.persist_batched(interval), None);
+        let usage = mgr.record_usage(&id, "2026-03-01", &[("opus".into(), counts)]).unwrap();
+        assert_eq!(usage.entries.len(), 1);
+        assert_eq!(usage.total().input_tokens, 200);
+
+        // Held back within the interval, with a single flush scheduled
+        assert!(mgr.persist_batched(interval).is_some());
+        assert_eq!(mgr.persist_batched(interval), None);
+        let on_disk = WorkspaceManager::load_from(path.clone());
+        assert_eq!(on_disk.get(&id).unwrap().usage, first);
+
+        mgr.flush();
+        let reloaded = WorkspaceManager::load_from(path);
+        assert_eq!(reloaded.get(&id).unwrap().usage, usage);
+    }
+
+    #[test]
+    fn test_usage_survives_restart_without_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json");
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.store_path = Some(path.clone());
+        let counts = UsageCounts { input_tokens: 100, output_tokens: 10, cost_usd: 0.1 };
+        let usage = mgr.record_usage(&id, "2026-03-01", &[("opus".into(), counts)]).unwrap();
+        mgr.persist_batched(Duration::from_secs(5));
+        drop(mgr);
+
+        // Startup without the daemon: nothing to reattach
+        let mut reloaded = WorkspaceManager::load_from(path);
+        reloaded.reconcile_sessions(&[]);
+        let ws = reloaded.get(&id).unwrap();
+        assert_eq!(ws.state, WorkspaceState::Stopped);
+        assert_eq!(ws.usage, usage);
+    }
+
+    #[test]
+    fn test_spent_budget_stays_spent_after_reload() {
+        use crate::budget::{self, BudgetKind, BudgetUsage};
+        use crate::config::BudgetLimits;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json");
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.store_path = Some(path.clone());
+        let counts = UsageCounts { input_tokens: 900, output_tokens: 100, cost_usd: 0.0 };
+        mgr.record_usage(&id, "2026-03-01", &[("opus".into(), counts)]).unwrap();
+        mgr.record_runtime(&id, 30.0).unwrap();
+        drop(mgr);
+
+        let limits = BudgetLimits {
+            max_tokens: Some(1000),
+            ..Default::default()
+        };
+        let reloaded = WorkspaceManager::load_from(path);
+        let used = BudgetUsage::of(&reloaded.get(&id).unwrap().usage);
+        assert_eq!(used.runtime_secs, 30.0);
+        assert_eq!(budget::exceeded_by(&limits, &used), Some(BudgetKind::Tokens));
+    }
+
+    #[test]
+    fn test_finish_headless_run_stores_result() {
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        let result = HeadlessResult { text: "done".into(), is_error: false, finished_at: 1 };
+        mgr.finish_headless_run(&id, result.clone()).unwrap();
+        let ws = mgr.get(&id).unwrap();
+        assert_eq!(ws.state, WorkspaceState::Stopped);
+        assert_eq!(ws.last_result, Some(result));
+
+        mgr.set_state(&id, WorkspaceState::BudgetExceeded).unwrap();
+        let failed = HeadlessResult { text: "killed".into(), is_error: true, finished_at: 2 };
+        mgr.finish_headless_run(&id, failed).unwrap();
+        assert_eq!(mgr.get(&id).unwrap().state, WorkspaceState::BudgetExceeded);
+    }
+
+    #[test]
+    fn test_clear_session_id() {
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.set_session_id(&id, "claude", "claude-1").unwrap();
+        mgr.clear_session_id(&id).unwrap();
+        assert_eq!(mgr.get(&id).unwrap().session_id, None);
+        assert!(mgr.clear_session_id("nope").is_err());
+    }
+
+    #[test]
+    fn test_switch_agent_keeps_history_and_resumes_earlier_conversation() {
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.set_session_id(&id, "claude", "claude-1").unwrap();
+
+        assert_eq!(mgr.switch_agent(&id, "codex", Some("claude-2".into()), 10).unwrap(), None);
+        let ws = mgr.get(&id).unwrap();
+        assert_eq!(ws.agent, "codex");
+        assert_eq!(ws.session_id, None);
+        assert_eq!(
+            ws.agent_history,
+            vec![PastAgent { agent: "claude".into(), session_id: Some("claude-2".into()), until_ms: 10 }]
+        );
+
+        // The old agent's late report doesn't land on the new agent
+        mgr.set_session_id(&id, "claude", "claude-3").unwrap();
+        assert_eq!(mgr.get(&id).unwrap().session_id, None);
+        mgr.set_session_id(&id, "codex", "codex-1").unwrap();
+        let resumed = mgr.switch_agent(&id, "claude", None, 20).unwrap();
+        assert_eq!(resumed.as_deref(), Some("claude-2"));
+        let ws = mgr.get(&id).unwrap();
+        assert_eq!(ws.agent_history[1].session_id.as_deref(), Some("codex-1"));
+        assert_eq!(ws.session_id.as_deref(), Some("claude-2"));
+    }
+
+    #[test]
+    fn test_revert_switch_agent_restores_previous_agent() {
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.switch_agent(&id, "codex", Some("claude-1".into()), 10).unwrap();
+        mgr.revert_switch_agent(&id).unwrap();
+        let ws = mgr.get(&id).unwrap();
+        assert_eq!(ws.agent, "claude");
+        assert_eq!(ws.session_id.as_deref(), Some("claude-1"));
+        assert!(ws.agent_history.is_empty());
+        assert!(mgr.revert_switch_agent(&id).is_err());
+    }
+
+    // ── Persistence ──
+
+    #[test]
+    fn test_state_changes_persist_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json");
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        mgr.store_path = Some(path.clone());
+        mgr.set_state(&id, WorkspaceState::Queued).unwrap();
+
+        let reloaded = WorkspaceManager::load_from(path);
+        let ws = reloaded.get(&id).unwrap();
+        assert_eq!(ws.state, WorkspaceState::Queued);
+        assert_eq!(ws.branch, "feature-branch");
+    }
+
+    #[test]
+    fn test_load_from_missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let mgr = WorkspaceManager::load_from(dir.path().join("nope.json"));
+        assert!(mgr.list(None).is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_stops_workspaces_without_live_agent() {
+        let (mut mgr, id) = make_manager_with_workspace(WorkspaceState::Running);
+        let mut other = mgr.get(&id).unwrap().clone();
+        other.id = "live-ws".to_string();
+        mgr.workspaces.insert(other.id.clone(), other);
+
+        mgr.reconcile_sessions(&["live-ws".to_string()]);
+        assert_eq!(mgr.get(&id).unwrap().state, WorkspaceState::Stopped);
+        assert_eq!(mgr.get("live-ws").unwrap().state, WorkspaceState::Running);
+    }
 }
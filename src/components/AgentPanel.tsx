@@ -13,12 +13,13 @@ import {
 import { sendToAgent, switchAgentModel, getConfig } from "../lib/tauri";
 import { registerAction, unregisterAction } from "../lib/action-registry";
 import ModelSelector from "./ModelSelector";
+import type { ModelEntry } from "../lib/types";
 
 export default function AgentPanel() {
   let termContainer!: HTMLDivElement;
   const [input, setInput] = createSignal("");
   const [switching, setSwitching] = createSignal(false);
-  const [agentModels, setAgentModels] = createSignal<Record<string, string[]>>({});
+  const [agentModels, setAgentModels] = createSignal<Record<string, ModelEntry[]>>({});
   let prevId: string | null = null;
   let inputRef: HTMLTextAreaElement | undefined;
 
@@ -26,8 +27,8 @@ export default function AgentPanel() {
   onMount(async () => {
     try {
       const config = await getConfig();
-      const agents = (config.agents ?? {}) as Record<string, { models?: string[] }>;
-      const modelMap: Record<string, string[]> = {};
+      const agents = (config.agents ?? {}) as Record<string, { models?: ModelEntry[] }>;
+      const modelMap: Record<string, ModelEntry[]> = {};
       for (const [name, agentConf] of Object.entries(agents)) {
         if (agentConf.models && agentConf.models.length > 0) {
           modelMap[name] = agentConf.models;
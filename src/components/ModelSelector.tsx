@@ -1,7 +1,8 @@
 import { createSignal, For, Show, onCleanup } from "solid-js";
+import type { ModelEntry } from "../lib/types";
 
 interface Props {
-  models: string[];
+  models: ModelEntry[];
   current: string | null;
   onSelect: (model: string) => void;
   disabled?: boolean;
@@ -16,6 +17,12 @@ export default function ModelSelector(props: Props) {
     setOpen(!open());
   }
 
+  function label(id: string | null): string {
+    if (!id) return "default";
+    const entry = props.models.find((m) => m.id === id);
+    return entry?.display_name ?? id;
+  }
+
   function handleSelect(model: string) {
     if (model !== props.current) {
       props.onSelect(model);
@@ -82,7 +89,7 @@ export default function ModelSelector(props: Props) {
             }
           >
             <span class="model-name">
-              {props.current || "default"}
+              {label(props.current)}
             </span>
             <span class="model-chevron">&#9662;</span>
           </Show>
@@ -94,16 +101,16 @@ export default function ModelSelector(props: Props) {
               {(model) => (
                 <button
                   class="model-option"
-                  classList={{ selected: model === props.current }}
+                  classList={{ selected: model.id === props.current }}
                   onClick={() => {
-                    handleSelect(model);
+                    handleSelect(model.id);
                     detachListeners();
                   }}
                 >
                   <span class="model-check">
-                    {model === props.current ? "\u2713" : ""}
+                    {model.id === props.current ? "\u2713" : ""}
                   </span>
-                  <span>{model}</span>
+                  <span>{model.display_name ?? model.id}</span>
                 </button>
               )}
             </For>
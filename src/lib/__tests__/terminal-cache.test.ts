@@ -8,6 +8,7 @@ vi.mock("@xterm/xterm", () => {
     write = vi.fn();
     writeln = vi.fn();
     onData = vi.fn();
+    onResize = vi.fn();
     loadAddon = vi.fn();
     open = vi.fn();
   }
@@ -33,6 +34,10 @@ vi.mock("@xterm/addon-webgl", () => {
 vi.mock("../tauri", () => ({
   onPtyOutput: vi.fn().mockImplementation(() => Promise.resolve(vi.fn())),
   onPtyExit: vi.fn().mockImplementation(() => Promise.resolve(vi.fn())),
+  resizePty: vi.fn().mockImplementation(() => Promise.resolve()),
+  getPtyScrollback: vi
+    .fn()
+    .mockImplementation(() => Promise.resolve({ chunks: [], next_seq: 0, truncated: false })),
 }));
 
 // Mock workspace-store functions
@@ -10,6 +10,29 @@ import type {
   TrustStatus,
   TrustRequiredPayload,
   ShortcutConfig,
+  PtyChunk,
+  PtyExitInfo,
+  KeyInput,
+  ScrollbackSnapshot,
+  AgentActivity,
+  QueuedSpawnInfo,
+  LimitHit,
+  TerminalInfo,
+  SearchFilters,
+  SearchHit,
+  ScreenSnapshot,
+  WorkspaceTrigger,
+  TriggerFired,
+  WorkspaceUsage,
+  UsageReport,
+  BudgetAlert,
+  AgentHealth,
+  AgentEvent,
+  HeadlessResult,
+  PermissionMode,
+  PendingApproval,
+  ApprovalRecord,
+  PastAgent,
 } from "./types";
 
 // ── Config ──
@@ -50,7 +73,23 @@ export interface WorkspaceInfo {
   branch: string;
   worktree_path: string;
   agent: string;
-  state: "Creating" | "Running" | "Stopping" | "Stopped" | "Cleaning";
+  state:
+    | "Creating"
+    | "Queued"
+    | "Running"
+    | "Stopping"
+    | "Stopped"
+    | "Cleaning"
+    | "BudgetExceeded";
+  activity: AgentActivity | null;
+  queue_position: number | null;
+  disabled_triggers: string[];
+  usage: WorkspaceUsage;
+  prompt: string | null;
+  last_result: HeadlessResult | null;
+  permission_mode: PermissionMode | null;
+  session_id: string | null;
+  agent_history: PastAgent[];
 }
 
 export async function createWorkspace(args: {
@@ -58,6 +97,10 @@ export async function createWorkspace(args: {
   repo_alias: string;
   branch: string;
   agent?: string;
+  rows?: number;
+  cols?: number;
+  priority?: number;
+  prompt?: string;
 }): Promise<WorkspaceInfo> {
   return invoke("create_workspace", { args });
 }
@@ -76,33 +119,272 @@ export async function listWorkspaces(
   return invoke("list_workspaces", { repoPath });
 }
 
+// ── Spawn Queue ──
+
+export async function listSpawnQueue(): Promise<QueuedSpawnInfo[]> {
+  return invoke("list_spawn_queue");
+}
+
+export async function cancelQueuedSpawn(workspaceId: string) {
+  return invoke("cancel_queued_spawn", { workspaceId });
+}
+
+export async function moveQueuedSpawn(
+  workspaceId: string,
+  position: number,
+): Promise<number> {
+  return invoke("move_queued_spawn", { workspaceId, position });
+}
+
+export function onSpawnQueueChanged(
+  callback: (queue: QueuedSpawnInfo[]) => void,
+): Promise<UnlistenFn> {
+  return listen<QueuedSpawnInfo[]>("spawn-queue-changed", (event) => {
+    callback(event.payload);
+  });
+}
+
 // ── Agent ──
 
 export async function sendToAgent(workspaceId: string, message: string) {
   return invoke("send_to_agent", { workspaceId, message });
 }
 
+export async function sendKeys(
+  workspaceId: string,
+  keys: KeyInput[],
+  terminalId?: string,
+): Promise<void> {
+  return invoke("send_keys", { workspaceId, terminalId, keys });
+}
+
+export async function writeRaw(
+  workspaceId: string,
+  data: number[],
+  terminalId?: string,
+): Promise<void> {
+  return invoke("write_raw", { workspaceId, terminalId, data });
+}
+
+export async function resizePty(
+  workspaceId: string,
+  rows: number,
+  cols: number,
+  terminalId?: string,
+): Promise<void> {
+  return invoke("resize_pty", { workspaceId, terminalId, rows, cols });
+}
+
+export async function getPtyScrollback(
+  workspaceId: string,
+  fromSeq?: number,
+  terminalId?: string,
+): Promise<ScrollbackSnapshot> {
+  return invoke("get_pty_scrollback", { workspaceId, terminalId, fromSeq });
+}
+
+export async function getScreenSnapshot(
+  workspaceId: string,
+  options: { terminalId?: string; includeCells?: boolean } = {},
+): Promise<ScreenSnapshot> {
+  return invoke("get_screen_snapshot", { workspaceId, ...options });
+}
+
+// ── Terminals ──
+
+export async function openTerminal(
+  workspaceId: string,
+  options: { command?: string; rows?: number; cols?: number } = {},
+): Promise<TerminalInfo> {
+  return invoke("open_terminal", { workspaceId, ...options });
+}
+
+export async function listTerminals(
+  workspaceId: string,
+): Promise<TerminalInfo[]> {
+  return invoke("list_terminals", { workspaceId });
+}
+
+export async function closeTerminal(
+  workspaceId: string,
+  terminalId: string,
+): Promise<void> {
+  return invoke("close_terminal", { workspaceId, terminalId });
+}
+
+// ── Transcripts ──
+
+export async function getTranscript(workspaceId: string): Promise<string> {
+  return invoke("get_transcript", { workspaceId });
+}
+
+export async function exportTranscriptAsciicast(
+  workspaceId: string,
+  destPath: string,
+): Promise<void> {
+  return invoke("export_transcript_asciicast", { workspaceId, destPath });
+}
+
 export async function getAgents(): Promise<string[]> {
   return invoke("get_agents");
 }
 
+export async function checkAgents(repoPath?: string): Promise<AgentHealth[]> {
+  return invoke("check_agents", { repoPath });
+}
+
+// ── Search ──
+
+export async function searchSessions(
+  query: string,
+  filters?: SearchFilters,
+): Promise<SearchHit[]> {
+  return invoke("search_sessions", { query, filters });
+}
+
+// ── Triggers ──
+
+export async function listTriggers(
+  workspaceId: string,
+): Promise<WorkspaceTrigger[]> {
+  return invoke("list_triggers", { workspaceId });
+}
+
+export async function setTriggerEnabled(
+  workspaceId: string,
+  trigger: string,
+  enabled: boolean,
+): Promise<void> {
+  return invoke("set_trigger_enabled", { workspaceId, trigger, enabled });
+}
+
+export function onTriggerFired(
+  callback: (fired: TriggerFired) => void,
+): Promise<UnlistenFn> {
+  return listen<TriggerFired>("trigger-fired", (event) => {
+    callback(event.payload);
+  });
+}
+
+// ── Usage ──
+
+export async function getWorkspaceUsage(
+  workspaceId: string,
+): Promise<UsageReport> {
+  return invoke("get_workspace_usage", { workspaceId });
+}
+
+export async function getUsageSummary(
+  options: { repoPath?: string; fromDay?: string; toDay?: string } = {},
+): Promise<UsageReport> {
+  return invoke("get_usage_summary", options);
+}
+
+export function onUsageUpdated(
+  workspaceId: string,
+  callback: (report: UsageReport) => void,
+): Promise<UnlistenFn> {
+  return listen<UsageReport>(`usage-updated-${workspaceId}`, (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onBudgetAlert(
+  workspaceId: string,
+  callback: (alert: BudgetAlert) => void,
+): Promise<UnlistenFn> {
+  return listen<BudgetAlert>(`budget-alert-${workspaceId}`, (event) => {
+    callback(event.payload);
+  });
+}
+
+// ── Approvals ──
+
+export async function listPendingApprovals(): Promise<PendingApproval[]> {
+  return invoke("list_pending_approvals");
+}
+
+export async function answerApproval(
+  workspaceId: string,
+  approvalId: string,
+  approve: boolean,
+): Promise<void> {
+  return invoke("answer_approval", { workspaceId, approvalId, approve });
+}
+
+export async function getApprovalLog(
+  options: { workspaceId?: string; limit?: number } = {},
+): Promise<ApprovalRecord[]> {
+  return invoke("get_approval_log", options);
+}
+
+export function onApprovalRequired(
+  callback: (pending: PendingApproval) => void,
+): Promise<UnlistenFn> {
+  return listen<PendingApproval>("approval-required", (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onApprovalDecided(
+  callback: (record: ApprovalRecord) => void,
+): Promise<UnlistenFn> {
+  return listen<ApprovalRecord>("approval-decided", (event) => {
+    callback(event.payload);
+  });
+}
+
+/** A waiting prompt was answered in the terminal instead */
+export function onApprovalCleared(
+  callback: (pending: PendingApproval) => void,
+): Promise<UnlistenFn> {
+  return listen<PendingApproval>("approval-cleared", (event) => {
+    callback(event.payload);
+  });
+}
+
 // ── PTY Events ──
 
+/** Event suffix of a session: the agent uses the bare workspace id */
+function ptyEventId(workspaceId: string, terminalId?: string): string {
+  return terminalId ? `${workspaceId}:${terminalId}` : workspaceId;
+}
+
 export function onPtyOutput(
   workspaceId: string,
-  callback: (data: string) => void,
+  callback: (data: string, seq: number) => void,
+  terminalId?: string,
 ): Promise<UnlistenFn> {
-  return listen<string>(`pty-output-${workspaceId}`, (event) => {
-    callback(event.payload);
+  return listen<PtyChunk>(`pty-output-${ptyEventId(workspaceId, terminalId)}`, (event) => {
+    callback(event.payload.data, event.payload.seq);
   });
 }
 
 export function onPtyExit(
   workspaceId: string,
-  callback: () => void,
+  callback: (exit: PtyExitInfo) => void,
+  terminalId?: string,
+): Promise<UnlistenFn> {
+  return listen<PtyExitInfo>(`pty-exit-${ptyEventId(workspaceId, terminalId)}`, (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onResourceLimit(
+  workspaceId: string,
+  callback: (hit: LimitHit) => void,
 ): Promise<UnlistenFn> {
-  return listen(`pty-exit-${workspaceId}`, () => {
-    callback();
+  return listen<LimitHit>(`resource-limit-${workspaceId}`, (event) => {
+    callback(event.payload);
+  });
+}
+
+export function onAgentStatus(
+  workspaceId: string,
+  callback: (activity: AgentActivity) => void,
+): Promise<UnlistenFn> {
+  return listen<AgentActivity>(`agent-status-${workspaceId}`, (event) => {
+    callback(event.payload);
   });
 }
 
@@ -272,3 +554,35 @@ export async function switchAgentModel(
 ): Promise<void> {
   return invoke("switch_agent_model", { workspaceId, model });
 }
+
+export async function restartAgent(workspaceId: string): Promise<void> {
+  return invoke("restart_agent", { workspaceId });
+}
+
+export async function switchAgent(args: {
+  workspace_id: string;
+  agent: string;
+  model?: string;
+  handoff?: boolean;
+}): Promise<void> {
+  return invoke("switch_agent", { args });
+}
+
+// ── Headless Runs ──
+
+export async function runHeadless(
+  workspaceId: string,
+  prompt: string,
+  model?: string,
+): Promise<void> {
+  return invoke("run_headless", { workspaceId, prompt, model });
+}
+
+export function onAgentEvent(
+  workspaceId: string,
+  callback: (event: AgentEvent) => void,
+): Promise<UnlistenFn> {
+  return listen<AgentEvent>(`agent-event-${workspaceId}`, (event) => {
+    callback(event.payload);
+  });
+}
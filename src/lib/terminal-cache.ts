@@ -1,7 +1,8 @@
 import { Terminal } from "@xterm/xterm";
 import { FitAddon } from "@xterm/addon-fit";
 import type { UnlistenFn } from "@tauri-apps/api/event";
-import { onPtyOutput, onPtyExit } from "./tauri";
+import { onPtyOutput, onPtyExit, resizePty, getPtyScrollback } from "./tauri";
+import type { PtyChunk } from "./types";
 import {
   markNewOutput,
   updateWorkspaceStatus,
@@ -48,18 +49,60 @@ export function createTerminal(workspaceId: string): CachedTerminal {
   // Open terminal into the offscreen container
   terminal.open(element);
 
+  // Keep the backend PTY in sync with the fitted terminal size
+  terminal.onResize(({ rows, cols }) => {
+    resizePty(workspaceId, rows, cols).catch(() => {
+      // Session may not be spawned yet; size is remembered backend-side
+    });
+  });
+
   // Subscribe to IPC events
   const unlistenHandles: UnlistenFn[] = [];
 
-  onPtyOutput(workspaceId, (data) => {
+  // Live chunks are held back until the scrollback replay below has been
+  // written, then only those newer than the replay are applied
+  let held: PtyChunk[] | null = [];
+
+  onPtyOutput(workspaceId, (data, seq) => {
+    if (held) {
+      held.push({ seq, data });
+      return;
+    }
     terminal.write(data);
     markNewOutput(workspaceId);
     incrementOutputBytes(workspaceId, data.length);
   }).then((fn) => unlistenHandles.push(fn));
 
-  onPtyExit(workspaceId, () => {
+  // Rebuild earlier output after a webview reload or remount
+  getPtyScrollback(workspaceId)
+    .then((snapshot) => {
+      for (const chunk of snapshot.chunks) {
+        terminal.write(chunk.data);
+      }
+      for (const chunk of held ?? []) {
+        if (chunk.seq >= snapshot.next_seq) {
+          terminal.write(chunk.data);
+        }
+      }
+    })
+    .catch(() => {
+      // No session yet — nothing to replay
+      for (const chunk of held ?? []) {
+        terminal.write(chunk.data);
+      }
+    })
+    .finally(() => {
+      held = null;
+    });
+
+  onPtyExit(workspaceId, (exit) => {
     updateWorkspaceStatus(workspaceId, "stopped");
-    terminal.writeln("\r\n\x1b[90m[Process exited]\x1b[0m");
+    const reason = exit?.signal
+      ? ` (${exit.signal})`
+      : exit?.code != null
+        ? ` with code ${exit.code}`
+        : "";
+    terminal.writeln(`\r\n\x1b[90m[Process exited${reason}]\x1b[0m`);
   }).then((fn) => unlistenHandles.push(fn));
 
   const entry: CachedTerminal = {
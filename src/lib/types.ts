@@ -62,6 +62,255 @@ export interface MergeResult {
 export type MergeStrategy = "merge" | "squash" | "rebase";
 export type FileVersion = "base" | "working";
 
+// ── PTY Types ──
+
+export interface PtyChunk {
+  seq: number;
+  data: string;
+}
+
+export interface PtyExitInfo {
+  code: number | null;
+  signal: string | null;
+}
+
+export type KeyInput =
+  | { type: "text"; text: string }
+  | { type: "key"; name: string }
+  | { type: "paste"; text: string };
+
+export interface ScrollbackSnapshot {
+  chunks: PtyChunk[];
+  next_seq: number;
+  truncated: boolean;
+}
+
+export type CellColor =
+  | "default"
+  | { indexed: number }
+  | { rgb: [number, number, number] };
+
+export interface ScreenCell {
+  text: string;
+  fg: CellColor;
+  bg: CellColor;
+  bold: boolean;
+  italic: boolean;
+  underline: boolean;
+  inverse: boolean;
+  wide: boolean;
+}
+
+export interface ScreenSnapshot {
+  rows: number;
+  cols: number;
+  cursor_row: number;
+  cursor_col: number;
+  cursor_visible: boolean;
+  alternate_screen: boolean;
+  lines: string[];
+  cells: ScreenCell[][];
+}
+
+export interface TerminalInfo {
+  workspace_id: string;
+  terminal_id: string;
+  label: string;
+  alive: boolean;
+}
+
+export type AgentActivity = "working" | "waiting_for_input" | "idle" | "exited";
+
+export interface LimitHit {
+  kind: "memory" | "cpu_time" | "processes" | "open_files";
+  limit: number;
+  detail: string;
+}
+
+export interface QueuedSpawnInfo {
+  workspace_id: string;
+  position: number;
+  priority: number;
+}
+
+// ── Search Types ──
+
+export interface SearchFilters {
+  repo_path?: string;
+  agent?: string;
+  /** Inclusive bounds, Unix epoch millis */
+  from_ms?: number;
+  to_ms?: number;
+  /** Regex query; otherwise a case-insensitive literal */
+  regex?: boolean;
+  limit?: number;
+}
+
+export interface SearchHit {
+  workspace_id: string;
+  terminal_id: string | null;
+  agent: string | null;
+  repo_path: string | null;
+  timestamp_ms: number;
+  snippet: string;
+  source: "transcript" | "live";
+}
+
+// ── Trigger Types ──
+
+export type TriggerAction =
+  | { type: "notify"; message?: string | null }
+  | { type: "auto_reply"; text: string; submit?: boolean }
+  | { type: "run_snippet"; snippet: string }
+  | { type: "stop_workspace" };
+
+export interface TriggerRule {
+  name: string;
+  pattern: string;
+  action: TriggerAction;
+  cooldown_ms?: number;
+  enabled?: boolean;
+}
+
+export interface WorkspaceTrigger extends TriggerRule {
+  active: boolean;
+}
+
+export interface TriggerFired {
+  workspace_id: string;
+  trigger: string;
+  action: TriggerAction;
+  matched: string;
+}
+
+// ── Agent Types ──
+
+export interface AgentHealth {
+  agent: string;
+  command: string;
+  path: string | null;
+  version: string | null;
+  version_error: string | null;
+  missing_credentials: string[];
+  ready: boolean;
+}
+
+export interface ModelCost {
+  input_per_mtok: number;
+  output_per_mtok: number;
+}
+
+export interface ModelEntry {
+  id: string;
+  display_name?: string;
+  args?: string[];
+  env?: Record<string, string>;
+  cost?: ModelCost;
+}
+
+// ── Usage Types ──
+
+export interface UsageCounts {
+  input_tokens: number;
+  output_tokens: number;
+  cost_usd: number;
+}
+
+export interface UsageEntry extends UsageCounts {
+  /** YYYY-MM-DD (UTC) */
+  day: string;
+  model: string;
+}
+
+export interface WorkspaceUsage {
+  entries: UsageEntry[];
+  /** Wall-clock time its agents ran, across sessions */
+  runtime_secs: number;
+}
+
+export interface UsageReport {
+  total: UsageCounts;
+  by_workspace: Record<string, UsageCounts>;
+  by_model: Record<string, UsageCounts>;
+  by_day: Record<string, UsageCounts>;
+  /** Usage not tied to any one model (e.g. Claude's session cost) */
+  unattributed: UsageCounts;
+}
+
+// ── Agent History Types ──
+
+export interface PastAgent {
+  agent: string;
+  session_id: string | null;
+  until_ms: number;
+}
+
+// ── Permission Types ──
+
+export type PermissionMode = "auto" | "ask" | "read_only";
+
+// ── Approval Types ──
+
+export type ApprovalDecision = "approve" | "deny" | "ask" | "manual";
+
+export interface ApprovalRule {
+  name: string;
+  pattern: string;
+  decision: ApprovalDecision;
+}
+
+export interface PendingApproval {
+  id: string;
+  workspace_id: string;
+  request: string;
+  time_ms: number;
+}
+
+export interface ApprovalRecord {
+  id: string;
+  workspace_id: string;
+  request: string;
+  decision: ApprovalDecision;
+  rule: string | null;
+  time_ms: number;
+  error?: string | null;
+}
+
+// ── Headless Run Types ──
+
+export type AgentEvent =
+  | { type: "assistant_message"; text: string }
+  | { type: "tool_call"; name: string; input: unknown }
+  | { type: "file_edit"; path: string }
+  | { type: "result"; text: string; is_error: boolean }
+  | { type: "usage"; model: string; counts: UsageCounts };
+
+export interface HeadlessResult {
+  text: string;
+  is_error: boolean;
+  finished_at: number;
+}
+
+// ── Budget Types ──
+
+export type BudgetAction = "interrupt" | "stop";
+
+export interface BudgetLimits {
+  max_cost_usd?: number | null;
+  max_runtime_secs?: number | null;
+  max_tokens?: number | null;
+  warn_percent?: number | null;
+  on_exceed?: BudgetAction | null;
+}
+
+export interface BudgetAlert {
+  kind: "cost" | "runtime" | "tokens";
+  level: "warning" | "exceeded";
+  limit: number;
+  used: number;
+  action: BudgetAction | null;
+}
+
 // ── Snippet Types ──
 
 export type SnippetCategory = "setup" | "build" | "test" | "lint" | "deploy" | "custom";